@@ -0,0 +1,146 @@
+use crate::attributes::PatchLookup;
+use crate::cue_engine::{CueTiming, RecordMode};
+use crate::presets::{Preset, PresetId};
+use crate::AppState;
+
+// Console operations shared by the Tauri commands, the CLI dispatcher and the web remote.
+// Locks are taken in the usual order: fixtures, library, programmer, then engines.
+
+/// Callers journal `Domain::Programmer` so the cleared programmer can be undone
+pub fn blackout(state: &AppState) -> Result<String, String> {
+    // The output loop re-renders every frame, so playback and the programmer
+    // have to be cleared as well for the blackout to hold
+    state.cue_engine.lock().map_err(|e| e.to_string())?.release_all();
+    state.effects_engine.lock().map_err(|e| e.to_string())?.stop_all();
+    state.chaser_engine.lock().map_err(|e| e.to_string())?.stop_all();
+    state.programmer.lock().map_err(|e| e.to_string())?.clear();
+
+    let mut engine = state.dmx_engine.lock().map_err(|e| e.to_string())?;
+    engine.blackout();
+    for universe in 0..=255 {
+        let _ = engine.send_dmx(universe);
+    }
+    Ok("Blackout activated".to_string())
+}
+
+pub fn record_cue(
+    state: &AppState,
+    cue_list: u32,
+    cue_number: f64,
+    name: Option<String>,
+    timing: Option<CueTiming>,
+    mode: RecordMode,
+    block: bool,
+) -> Result<String, String> {
+    let programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+    if programmer.is_empty() {
+        return Err("Programmer is empty".to_string());
+    }
+
+    let running_effects = state.effects_engine.lock().map_err(|e| e.to_string())?.running();
+    let preset_refs = state.presets.lock().map_err(|e| e.to_string())?.programmer_refs(&programmer);
+
+    let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let list = cue_engine.list_mut(cue_list);
+    list.record(
+        cue_number,
+        name.unwrap_or_else(|| format!("Cue {}", cue_number)),
+        &programmer,
+        timing,
+        mode,
+        block,
+    );
+    if !running_effects.is_empty() {
+        list.set_effects(cue_number, running_effects)?;
+    }
+    list.link_presets(cue_number, &preset_refs)?;
+
+    let mode_label = match mode {
+        RecordMode::Tracking => "tracking",
+        RecordMode::CueOnly => "cue only",
+    };
+    Ok(format!("Recorded cue {} in list {} ({})", cue_number, cue_list, mode_label))
+}
+
+pub fn go(state: &AppState, cue_list: u32) -> Result<String, String> {
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+
+    let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let t = cue_engine.now();
+    let number = cue_engine.go(cue_list, t, &|key| lookup.feature_set(key))?;
+    Ok(format!("Cue list {} GO cue {}", cue_list, number))
+}
+
+pub fn goto(state: &AppState, cue_list: u32, cue_number: f64) -> Result<String, String> {
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+
+    let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let t = cue_engine.now();
+    cue_engine.goto(cue_list, cue_number, t, &|key| lookup.feature_set(key))?;
+    Ok(format!("Cue list {} GOTO cue {}", cue_list, cue_number))
+}
+
+/// Record a preset from the programmer. With `merge` the recorded values are added to
+/// the existing preset instead of replacing it.
+pub fn record_preset(
+    state: &AppState,
+    id: PresetId,
+    name: Option<String>,
+    global: bool,
+    merge: bool,
+) -> Result<String, String> {
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+    let programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+
+    let mut presets = state.presets.lock().map_err(|e| e.to_string())?;
+    let existing = presets.get(id).ok().cloned();
+    if merge && existing.is_none() {
+        return Err(format!("Preset {} not found", id));
+    }
+    let name = name
+        .or_else(|| existing.as_ref().map(|p| p.name.clone()))
+        .unwrap_or_else(|| format!("Preset {}", id));
+    let mut preset = Preset::record(id, name, &programmer, &lookup, global)?;
+    if let Some(existing) = existing.filter(|_| merge) {
+        let recorded = preset;
+        preset = Preset { name: recorded.name, ..existing };
+        preset.values.extend(recorded.values);
+        for (fixture_id, values) in recorded.fixtures {
+            preset.fixtures.entry(fixture_id).or_default().extend(values);
+        }
+    }
+
+    // Cues that reference the preset follow the new values
+    let updated = state
+        .cue_engine
+        .lock()
+        .map_err(|e| e.to_string())?
+        .update_preset_refs(id, &|key| preset.value_for(key, &lookup));
+    presets.store(preset);
+    Ok(format!("Recorded preset {} ({} cues updated)", id, updated))
+}
+
+/// Put a preset into the programmer for the given fixtures, or the active selection
+pub fn apply_preset(state: &AppState, id: PresetId, fixture_ids: Option<Vec<String>>) -> Result<String, String> {
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+    let fixture_ids = match fixture_ids {
+        Some(ids) => ids,
+        None => state.selection.lock().map_err(|e| e.to_string())?.active(),
+    };
+    if fixture_ids.is_empty() {
+        return Err("No fixtures selected".to_string());
+    }
+
+    let mut programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+    let mut presets = state.presets.lock().map_err(|e| e.to_string())?;
+    let count = presets.apply(id, &fixture_ids, &lookup, &mut programmer)?;
+    Ok(format!("Applied preset {} to {} channels", id, count))
+}
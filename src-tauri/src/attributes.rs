@@ -0,0 +1,168 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{Fixture, FixtureChannel, GdtfFixtureType};
+
+/// Feature sets used to group fixture attributes for timing, presets and effects.
+/// Numbering matches `featureSetMapping.js` on the frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeatureSet {
+    Intensity,
+    Position,
+    Color,
+    Focus,
+    Gobo,
+    Beam,
+    Other,
+}
+
+impl FeatureSet {
+    /// Classify a GDTF/demo-show channel type (e.g. "Dimmer", "Pan", "ColorRGB_Red")
+    pub fn from_channel_type(channel_type: &str) -> Self {
+        let t = channel_type.to_lowercase();
+
+        if t.contains("dimmer") || t.contains("intensity") {
+            FeatureSet::Intensity
+        } else if t.starts_with("pan") || t.starts_with("tilt") || t.contains("position") {
+            FeatureSet::Position
+        } else if t.starts_with("color")
+            || t.starts_with("colour")
+            || ["red", "green", "blue", "white", "amber", "uv", "cyan", "magenta", "yellow", "cto", "hue", "saturation"]
+                .iter()
+                .any(|c| t == *c || t.ends_with(&format!("_{}", c)))
+        {
+            FeatureSet::Color
+        } else if t.contains("focus") {
+            FeatureSet::Focus
+        } else if t.contains("gobo") || t.contains("prism") {
+            FeatureSet::Gobo
+        } else if ["iris", "frost", "zoom", "shutter", "strobe"].iter().any(|b| t.contains(b)) {
+            FeatureSet::Beam
+        } else {
+            FeatureSet::Other
+        }
+    }
+
+    /// Feature set for a dot-notation number ("3" in "3.5"). Video sets (7, 8) have no
+    /// DMX attributes and aren't handled here.
+    pub fn from_number(number: u32) -> Option<Self> {
+        match number {
+            1 => Some(FeatureSet::Intensity),
+            2 => Some(FeatureSet::Position),
+            3 => Some(FeatureSet::Color),
+            4 => Some(FeatureSet::Focus),
+            5 => Some(FeatureSet::Gobo),
+            6 => Some(FeatureSet::Beam),
+            _ => None,
+        }
+    }
+
+    /// Feature set for a CLI/show file name ("color", "colour", "position", ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "intensity" | "dimmer" => Some(FeatureSet::Intensity),
+            "position" => Some(FeatureSet::Position),
+            "color" | "colour" => Some(FeatureSet::Color),
+            "focus" => Some(FeatureSet::Focus),
+            "gobo" => Some(FeatureSet::Gobo),
+            "beam" => Some(FeatureSet::Beam),
+            _ => None,
+        }
+    }
+
+    /// Dot-notation number, if this feature set can hold presets
+    pub fn number(self) -> Option<u32> {
+        match self {
+            FeatureSet::Intensity => Some(1),
+            FeatureSet::Position => Some(2),
+            FeatureSet::Color => Some(3),
+            FeatureSet::Focus => Some(4),
+            FeatureSet::Gobo => Some(5),
+            FeatureSet::Beam => Some(6),
+            FeatureSet::Other => None,
+        }
+    }
+}
+
+/// Build the programmer/cue key for a fixture channel ("fixture_id:offset")
+pub fn channel_key(fixture_id: &str, offset: u16) -> String {
+    format!("{}:{}", fixture_id, offset)
+}
+
+/// Split a "fixture_id:offset" key back into its parts
+pub fn parse_channel_key(key: &str) -> Option<(&str, u16)> {
+    let (fixture_id, offset) = key.rsplit_once(':')?;
+    Some((fixture_id, offset.parse().ok()?))
+}
+
+/// Read-only view over the patch used to resolve channel keys to DMX addresses
+/// and attribute types
+pub struct PatchLookup<'a> {
+    pub fixtures: &'a HashMap<String, Fixture>,
+    pub library: &'a HashMap<String, GdtfFixtureType>,
+}
+
+impl<'a> PatchLookup<'a> {
+    pub fn new(
+        fixtures: &'a HashMap<String, Fixture>,
+        library: &'a HashMap<String, GdtfFixtureType>,
+    ) -> Self {
+        PatchLookup { fixtures, library }
+    }
+
+    /// Library definition of a fixture channel, if the fixture type is known
+    pub fn channel(&self, fixture_id: &str, offset: u16) -> Option<&'a FixtureChannel> {
+        let fixture = self.fixtures.get(fixture_id)?;
+        let fixture_type = self.library.get(&fixture.fixture_type)?;
+        fixture_type.channels.iter().find(|c| c.offset == offset)
+    }
+
+    /// Channel type ("Dimmer", "Pan", ...) for a fixture channel, if the fixture type is known
+    pub fn channel_type(&self, fixture_id: &str, offset: u16) -> Option<&'a str> {
+        self.channel(fixture_id, offset).map(|c| c.channel_type.as_str())
+    }
+
+    /// Every channel of a fixture's type
+    pub fn channels(&self, fixture_id: &str) -> &'a [FixtureChannel] {
+        self.fixtures
+            .get(fixture_id)
+            .and_then(|f| self.library.get(&f.fixture_type))
+            .map(|t| t.channels.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Feature set for a programmer/cue key. Unknown channels are treated as `Other`.
+    pub fn feature_set(&self, key: &str) -> FeatureSet {
+        parse_channel_key(key)
+            .and_then(|(fixture_id, offset)| self.channel_type(fixture_id, offset))
+            .map(FeatureSet::from_channel_type)
+            .unwrap_or(FeatureSet::Other)
+    }
+
+    /// Offset of the first channel on a fixture matching an attribute name or channel type
+    pub fn find_channel(&self, fixture_id: &str, attribute: &str) -> Option<u16> {
+        let fixture = self.fixtures.get(fixture_id)?;
+        let fixture_type = self.library.get(&fixture.fixture_type)?;
+        fixture_type
+            .channels
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(attribute) || c.channel_type.eq_ignore_ascii_case(attribute))
+            .map(|c| c.offset)
+    }
+
+    /// Universe and absolute DMX channel (1-512) for a programmer/cue key
+    pub fn address(&self, key: &str) -> Option<(u8, u16)> {
+        let (fixture_id, offset) = parse_channel_key(key)?;
+        let fixture = self.fixtures.get(fixture_id)?;
+        if offset >= fixture.channel_count {
+            return None;
+        }
+        let channel = fixture.dmx_address + offset;
+        if channel == 0 || channel > 512 {
+            return None;
+        }
+        Some((fixture.universe, channel))
+    }
+}
@@ -0,0 +1,265 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::show_file::{self, LoadedShow, Preferences, ShowFileError};
+use crate::AppState;
+
+/// Present while the app is running; left behind if it crashes
+const SESSION_FILE: &str = "session.json";
+
+/// Where autosaves and the session marker live
+pub fn autosave_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("RoControl")
+        .join("Autosave")
+}
+
+/// Rotating autosave file; index 1 is the newest
+fn autosave_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("autosave-{}.json", index))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionMarker {
+    pid: u32,
+    started: u64,
+    /// Show file the session was working on, restored along with the autosave
+    show_path: Option<PathBuf>,
+}
+
+/// Autosave that can be restored after an unclean shutdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryInfo {
+    pub autosave_path: PathBuf,
+    pub show_name: String,
+    /// Unix time the autosave was written
+    pub saved_at: u64,
+    pub show_path: Option<PathBuf>,
+}
+
+/// Look for a session marker left by a crashed run and the newest autosave it produced
+pub fn check_recovery(dir: &Path) -> Option<RecoveryInfo> {
+    let marker: SessionMarker = serde_json::from_str(&fs::read_to_string(dir.join(SESSION_FILE)).ok()?).ok()?;
+    let autosave_path = autosave_path(dir, 1);
+    let saved_at = fs::metadata(&autosave_path)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Autosaves older than the crashed session belong to an earlier, cleanly closed run
+    if saved_at < marker.started {
+        return None;
+    }
+
+    let show_name = fs::read_to_string(&autosave_path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|v| v["show_info"]["name"].as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    Some(RecoveryInfo {
+        autosave_path,
+        show_name,
+        saved_at,
+        show_path: marker.show_path,
+    })
+}
+
+fn write_marker(dir: &Path, show_path: Option<PathBuf>, started: u64) -> Result<(), ShowFileError> {
+    let marker = SessionMarker {
+        pid: std::process::id(),
+        started,
+        show_path,
+    };
+    let json = serde_json::to_string(&marker).map_err(|e| ShowFileError::State(e.to_string()))?;
+    show_file::write_atomic(&dir.join(SESSION_FILE), &json)
+}
+
+/// Mark the session as running so a crash can be detected on the next start
+pub fn begin_session(dir: &Path) -> Result<(), ShowFileError> {
+    write_marker(dir, None, unix_time())
+}
+
+/// Load the crashed session's autosave, keeping the show path it was working on
+pub fn restore(state: &AppState) -> Result<LoadedShow, ShowFileError> {
+    let state_err = |e: String| ShowFileError::State(e);
+    let info = state
+        .recovery
+        .lock()
+        .map_err(|e| state_err(e.to_string()))?
+        .clone()
+        .ok_or_else(|| state_err("No autosave to restore".to_string()))?;
+    let loaded = show_file::load_show_file(state, &info.autosave_path)?;
+    // Keep saving to the show the crashed session was working on, not the autosave
+    state.show.lock().map_err(|e| state_err(e.to_string()))?.path = info.show_path;
+    *state.recovery.lock().map_err(|e| state_err(e.to_string()))? = None;
+    Ok(loaded)
+}
+
+/// Remove the session marker on a clean shutdown
+pub fn end_session(dir: &Path) {
+    let _ = fs::remove_file(dir.join(SESSION_FILE));
+}
+
+/// Shift autosave-1..N-1 up by one, dropping the oldest
+fn rotate(dir: &Path, keep: usize) {
+    let keep = keep.max(1);
+    let _ = fs::remove_file(autosave_path(dir, keep));
+    for index in (1..keep).rev() {
+        let from = autosave_path(dir, index);
+        if from.exists() {
+            let _ = fs::rename(&from, autosave_path(dir, index + 1));
+        }
+    }
+}
+
+/// Longest time between autosaves, in seconds
+const MAX_INTERVAL: u64 = 24 * 60 * 60;
+/// Most rotating autosave files kept
+const MAX_BACKUPS: usize = 100;
+
+/// Autosave preferences of the running show. They're saved with the show.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AutosaveSettings {
+    pub enabled: bool,
+    /// Seconds between autosaves
+    pub interval: u64,
+    /// Rotating autosave files kept
+    pub backups: usize,
+}
+
+impl AutosaveSettings {
+    pub fn from_preferences(preferences: &Preferences) -> Self {
+        AutosaveSettings {
+            enabled: preferences.auto_save,
+            interval: preferences.auto_save_interval,
+            backups: preferences.auto_save_backups,
+        }
+    }
+
+    /// Store the settings in the show's preferences if they're in range
+    pub fn apply(&self, preferences: &mut Preferences) -> Result<(), String> {
+        if !(1..=MAX_INTERVAL).contains(&self.interval) {
+            return Err(format!("The autosave interval must be 1-{} seconds", MAX_INTERVAL));
+        }
+        if !(1..=MAX_BACKUPS).contains(&self.backups) {
+            return Err(format!("Keep 1-{} autosave files", MAX_BACKUPS));
+        }
+        preferences.auto_save = self.enabled;
+        preferences.auto_save_interval = self.interval;
+        preferences.auto_save_backups = self.backups;
+        Ok(())
+    }
+}
+
+pub fn settings(state: &AppState) -> Result<AutosaveSettings, String> {
+    let show = state.show.lock().map_err(|e| e.to_string())?;
+    Ok(AutosaveSettings::from_preferences(&show.preferences))
+}
+
+/// Change the autosave settings. The running autosave picks them up within a second.
+pub fn set_settings(state: &AppState, settings: &AutosaveSettings) -> Result<AutosaveSettings, String> {
+    let mut show = state.show.lock().map_err(|e| e.to_string())?;
+    settings.apply(&mut show.preferences)?;
+    Ok(AutosaveSettings::from_preferences(&show.preferences))
+}
+
+/// Write an autosave of the running show, rotating older copies
+fn autosave(state: &AppState, dir: &Path, json: &str, started: u64) -> Result<(), ShowFileError> {
+    let (keep, show_path) = {
+        let show = state.show.lock().map_err(|e| ShowFileError::State(e.to_string()))?;
+        (show.preferences.auto_save_backups, show.path.clone())
+    };
+    rotate(dir, keep);
+    show_file::write_atomic(&autosave_path(dir, 1), json)?;
+    write_marker(dir, show_path, started)
+}
+
+/// Start the background autosave. The interval and backup count come from the show's
+/// preferences; an autosave is only written when the show changed since the last one.
+pub fn spawn(state: AppState, dir: PathBuf) {
+    thread::spawn(move || {
+        let started = unix_time();
+        let mut last_json = show_file::show_json(&state).ok();
+        let mut last_save = Instant::now();
+
+        loop {
+            thread::sleep(Duration::from_secs(1));
+
+            let (enabled, interval) = match state.show.lock() {
+                Ok(show) => (show.preferences.auto_save, show.preferences.auto_save_interval.max(1)),
+                Err(_) => continue,
+            };
+            // Don't rotate away the crashed session's autosave before the user decides on it
+            let recovery_pending = state.recovery.lock().map(|r| r.is_some()).unwrap_or(true);
+            if !enabled || recovery_pending || last_save.elapsed() < Duration::from_secs(interval) {
+                continue;
+            }
+            last_save = Instant::now();
+
+            let json = match show_file::show_json(&state) {
+                Ok(json) => json,
+                Err(e) => {
+                    eprintln!("[Autosave] Failed to collect show: {}", e);
+                    state.health.report_error("autosave", &e);
+                    continue;
+                }
+            };
+            if last_json.as_deref() == Some(json.as_str()) {
+                continue;
+            }
+            match autosave(&state, &dir, &json, started) {
+                Ok(()) => last_json = Some(json),
+                Err(e) => {
+                    eprintln!("[Autosave] {}", e);
+                    state.health.report_error("autosave", &e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_keeps_newest() {
+        let dir = std::env::temp_dir().join(format!("rocontrol-autosave-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for generation in 1..=4 {
+            rotate(&dir, 3);
+            fs::write(autosave_path(&dir, 1), generation.to_string()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(autosave_path(&dir, 1)).unwrap(), "4");
+        assert_eq!(fs::read_to_string(autosave_path(&dir, 3)).unwrap(), "2");
+        assert!(!autosave_path(&dir, 4).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_settings_are_checked_before_they_apply() {
+        let mut preferences = Preferences::default();
+        let settings = AutosaveSettings { enabled: true, interval: 60, backups: 10 };
+        settings.apply(&mut preferences).unwrap();
+        assert_eq!(AutosaveSettings::from_preferences(&preferences), settings);
+
+        assert!(AutosaveSettings { interval: 0, ..settings.clone() }.apply(&mut preferences).is_err());
+        assert!(AutosaveSettings { backups: 0, ..settings.clone() }.apply(&mut preferences).is_err());
+        assert!(AutosaveSettings { enabled: false, backups: MAX_BACKUPS + 1, ..settings.clone() }.apply(&mut preferences).is_err());
+        assert_eq!(AutosaveSettings::from_preferences(&preferences), settings);
+    }
+}
@@ -0,0 +1,409 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
+
+/// Taps further apart than this start a new tempo measurement
+const TAP_RESET_SECONDS: f64 = 2.0;
+/// Number of tap intervals averaged for the tempo
+const TAP_HISTORY: usize = 8;
+/// Tempo range for set and tapped BPM
+const MIN_BPM: f64 = 1.0;
+const MAX_BPM: f64 = 999.0;
+
+fn default_bpm() -> f64 {
+    120.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChaseDirection {
+    #[default]
+    Forward,
+    Backward,
+    Bounce,
+    Random,
+}
+
+/// One look in a chase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChaseStep {
+    #[serde(default)]
+    pub name: String,
+    /// fixture_id:channel -> value, same keys as the programmer
+    #[serde(default)]
+    pub values: HashMap<String, u8>,
+    /// Step time in seconds. When unset the step lasts one beat at the chaser's BPM.
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChaserRuntime {
+    running: bool,
+    step: usize,
+    previous: Option<usize>,
+    step_started: f64,
+    bouncing_back: bool,
+    rng: u64,
+    taps: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chaser {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub steps: Vec<ChaseStep>,
+    #[serde(default = "default_bpm")]
+    pub bpm: f64,
+    /// Portion of each step spent crossfading from the previous step (0-100%)
+    #[serde(default)]
+    pub crossfade: f32,
+    #[serde(default)]
+    pub direction: ChaseDirection,
+    #[serde(skip)]
+    runtime: ChaserRuntime,
+}
+
+/// Playback state of a chaser for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChaserStatus {
+    pub id: u32,
+    pub name: String,
+    pub running: bool,
+    pub step: usize,
+    pub step_count: usize,
+    pub bpm: f64,
+}
+
+impl Chaser {
+    fn step_duration(&self, index: usize) -> f64 {
+        self.steps
+            .get(index)
+            .and_then(|s| s.duration)
+            .unwrap_or(60.0 / self.bpm.max(1.0))
+            .max(0.01)
+    }
+
+    fn next_step(&mut self) -> usize {
+        let count = self.steps.len();
+        if count <= 1 {
+            return 0;
+        }
+        let current = self.runtime.step;
+        match self.direction {
+            ChaseDirection::Forward => (current + 1) % count,
+            ChaseDirection::Backward => (current + count - 1) % count,
+            ChaseDirection::Bounce => {
+                if self.runtime.bouncing_back && current == 0 {
+                    self.runtime.bouncing_back = false;
+                } else if !self.runtime.bouncing_back && current == count - 1 {
+                    self.runtime.bouncing_back = true;
+                }
+                if self.runtime.bouncing_back {
+                    current - 1
+                } else {
+                    current + 1
+                }
+            }
+            ChaseDirection::Random => {
+                // xorshift - good enough for picking steps, and avoids repeating the current one
+                let mut x = self.runtime.rng.max(1);
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                self.runtime.rng = x;
+                let pick = (x % (count as u64 - 1)) as usize;
+                if pick >= current {
+                    pick + 1
+                } else {
+                    pick
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, t: f64) {
+        let first = match self.direction {
+            ChaseDirection::Backward => self.steps.len().saturating_sub(1),
+            _ => 0,
+        };
+        self.runtime.running = true;
+        self.runtime.step = first;
+        self.runtime.previous = None;
+        self.runtime.step_started = t;
+        self.runtime.bouncing_back = false;
+        self.runtime.rng = (t.to_bits() ^ 0x9E37_79B9_7F4A_7C15) | 1;
+    }
+
+    /// Register a tap and return the new tempo once two taps are close enough together
+    fn tap(&mut self, t: f64) -> Option<f64> {
+        let taps = &mut self.runtime.taps;
+        // A double press (or two clients tapping together) isn't a beat
+        if taps.last().is_some_and(|last| t <= *last) {
+            return None;
+        }
+        if taps.last().is_some_and(|last| t - last > TAP_RESET_SECONDS) {
+            taps.clear();
+        }
+        taps.push(t);
+        if taps.len() > TAP_HISTORY + 1 {
+            taps.remove(0);
+        }
+        if taps.len() < 2 {
+            return None;
+        }
+
+        let interval = (taps[taps.len() - 1] - taps[0]) / (taps.len() - 1) as f64;
+        self.bpm = (60.0 / interval).clamp(MIN_BPM, MAX_BPM);
+        // Land the step change on the beat that was just tapped
+        if self.runtime.running {
+            self.runtime.step_started = t;
+        }
+        Some(self.bpm)
+    }
+
+    /// Advance steps that have elapsed by time `t`, starting each one at its exact due time
+    fn update(&mut self, t: f64) {
+        if !self.runtime.running || self.steps.is_empty() {
+            return;
+        }
+        loop {
+            let due = self.runtime.step_started + self.step_duration(self.runtime.step);
+            if due > t {
+                break;
+            }
+            self.runtime.previous = Some(self.runtime.step);
+            self.runtime.step = self.next_step();
+            self.runtime.step_started = due;
+        }
+    }
+
+    fn render(&self, t: f64, output: &mut HashMap<String, f32>) {
+        if !self.runtime.running {
+            return;
+        }
+        let Some(current) = self.steps.get(self.runtime.step) else {
+            return;
+        };
+
+        let fade = self.step_duration(self.runtime.step) * (self.crossfade.clamp(0.0, 100.0) as f64 / 100.0);
+        let progress = if fade > 0.0 {
+            ((t - self.runtime.step_started) / fade).clamp(0.0, 1.0) as f32
+        } else {
+            1.0
+        };
+        let previous = self.runtime.previous.and_then(|i| self.steps.get(i));
+
+        for (key, to) in &current.values {
+            let from = previous.and_then(|p| p.values.get(key)).copied().unwrap_or(0) as f32;
+            output.insert(key.clone(), from + (*to as f32 - from) * progress);
+        }
+        // Values only in the previous step fade out
+        if let Some(previous) = previous {
+            for (key, from) in &previous.values {
+                if !current.values.contains_key(key) && progress < 1.0 {
+                    output.insert(key.clone(), *from as f32 * (1.0 - progress));
+                }
+            }
+        }
+    }
+
+    pub fn status(&self) -> ChaserStatus {
+        ChaserStatus {
+            id: self.id,
+            name: self.name.clone(),
+            running: self.runtime.running,
+            step: self.runtime.step,
+            step_count: self.steps.len(),
+            bpm: self.bpm,
+        }
+    }
+}
+
+/// All chasers and their playback clock
+pub struct ChaserEngine {
+    epoch: Instant,
+    chasers: BTreeMap<u32, Chaser>,
+}
+
+impl ChaserEngine {
+    pub fn new() -> Self {
+        ChaserEngine {
+            epoch: Instant::now(),
+            chasers: BTreeMap::new(),
+        }
+    }
+
+    /// Seconds since the engine was created
+    pub fn now(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64()
+    }
+
+    pub fn chasers(&self) -> impl Iterator<Item = &Chaser> {
+        self.chasers.values()
+    }
+
+    /// Replace all chasers, e.g. when a show is loaded
+    pub fn load(&mut self, chasers: Vec<Chaser>) {
+        self.chasers = chasers.into_iter().map(|c| (c.id, c)).collect();
+    }
+
+    /// Replace the chasers (undo/redo). Chasers that still exist keep playing; ones that
+    /// come back start stopped.
+    pub fn restore(&mut self, chasers: Vec<Chaser>) {
+        let ids: HashSet<u32> = chasers.iter().map(|c| c.id).collect();
+        self.chasers.retain(|id, _| ids.contains(id));
+        for mut chaser in chasers {
+            if !self.chasers.contains_key(&chaser.id) {
+                chaser.runtime = ChaserRuntime::default();
+            }
+            self.store(chaser);
+        }
+    }
+
+    /// Store a chaser, keeping playback running if it replaces one that is playing
+    pub fn store(&mut self, mut chaser: Chaser) {
+        if let Some(existing) = self.chasers.get(&chaser.id) {
+            chaser.runtime = existing.runtime.clone();
+            if chaser.runtime.step >= chaser.steps.len() {
+                chaser.runtime.step = 0;
+                chaser.runtime.previous = None;
+            }
+        }
+        self.chasers.insert(chaser.id, chaser);
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.chasers.remove(&id).is_some()
+    }
+
+    fn get_mut(&mut self, id: u32) -> Result<&mut Chaser, String> {
+        self.chasers.get_mut(&id).ok_or_else(|| format!("Chaser {} not found", id))
+    }
+
+    pub fn start(&mut self, id: u32) -> Result<(), String> {
+        let t = self.now();
+        let chaser = self.get_mut(id)?;
+        if chaser.steps.is_empty() {
+            return Err(format!("Chaser {} has no steps", id));
+        }
+        chaser.start(t);
+        Ok(())
+    }
+
+    pub fn stop(&mut self, id: u32) -> Result<(), String> {
+        self.get_mut(id)?.runtime.running = false;
+        Ok(())
+    }
+
+    pub fn stop_all(&mut self) {
+        for chaser in self.chasers.values_mut() {
+            chaser.runtime.running = false;
+        }
+    }
+
+    pub fn set_bpm(&mut self, id: u32, bpm: f64) -> Result<(), String> {
+        if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+            return Err(format!("BPM {} out of range ({}-{})", bpm, MIN_BPM, MAX_BPM));
+        }
+        self.get_mut(id)?.bpm = bpm;
+        Ok(())
+    }
+
+    /// Tap tempo. Returns the chaser's BPM after the tap.
+    pub fn tap(&mut self, id: u32) -> Result<f64, String> {
+        let t = self.now();
+        let chaser = self.get_mut(id)?;
+        Ok(chaser.tap(t).unwrap_or(chaser.bpm))
+    }
+
+    /// Advance and render all running chasers. Later chasers win on shared channels.
+    pub fn render(&mut self, output: &mut HashMap<String, f32>) {
+        let t = self.now();
+        for chaser in self.chasers.values_mut() {
+            chaser.update(t);
+            chaser.render(t, output);
+        }
+    }
+}
+
+impl Default for ChaserEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chaser(steps: usize, direction: ChaseDirection) -> Chaser {
+        Chaser {
+            id: 1,
+            name: String::new(),
+            steps: (0..steps)
+                .map(|i| ChaseStep {
+                    name: String::new(),
+                    values: [(format!("f{}:0", i), 255)].into_iter().collect(),
+                    duration: None,
+                })
+                .collect(),
+            bpm: 60.0,
+            crossfade: 0.0,
+            direction,
+            runtime: ChaserRuntime::default(),
+        }
+    }
+
+    #[test]
+    fn test_steps_follow_bpm() {
+        let mut c = chaser(3, ChaseDirection::Forward);
+        c.start(0.0);
+        c.update(2.5);
+        assert_eq!(c.runtime.step, 2);
+        assert_eq!(c.runtime.step_started, 2.0);
+    }
+
+    #[test]
+    fn test_bounce_direction() {
+        let mut c = chaser(3, ChaseDirection::Bounce);
+        c.start(0.0);
+        let mut order = vec![c.runtime.step];
+        for i in 1..6 {
+            c.update(i as f64);
+            order.push(c.runtime.step);
+        }
+        assert_eq!(order, vec![0, 1, 2, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_tap_tempo() {
+        let mut c = chaser(2, ChaseDirection::Forward);
+        assert_eq!(c.tap(10.0), None);
+        c.tap(10.5);
+        assert_eq!(c.tap(11.0), Some(120.0));
+        // A long pause starts a new measurement
+        assert_eq!(c.tap(20.0), None);
+
+        // Taps at the same moment are ignored, taps too close together hit the limit
+        assert_eq!(c.tap(20.0), None);
+        assert_eq!(c.tap(20.000001), Some(MAX_BPM));
+        assert!(c.bpm.is_finite());
+    }
+
+    #[test]
+    fn test_crossfade_between_steps() {
+        let mut c = chaser(2, ChaseDirection::Forward);
+        c.crossfade = 50.0;
+        c.start(0.0);
+        c.update(1.25);
+
+        let mut out = HashMap::new();
+        c.render(1.25, &mut out);
+        assert_eq!(out["f1:0"], 127.5);
+        assert_eq!(out["f0:0"], 127.5);
+    }
+}
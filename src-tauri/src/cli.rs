@@ -0,0 +1,1440 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::actions;
+use crate::attributes::{channel_key, FeatureSet, PatchLookup};
+use crate::cue_engine::{CueTiming, RecordMode};
+use crate::fan::{self, FanMode, FanRequest};
+use crate::history::{self, Domain};
+use crate::presets::PresetId;
+use crate::selection::{self, patch_order, Group, SelectionCommand};
+use crate::{AppState, Fixture, FixtureChannel};
+use crate::ws_protocol::Event;
+
+/// Cue list used by "go", "cue N" and "record" when no executor is given
+pub const DEFAULT_CUE_LIST: u32 = 1;
+
+/// Fixture numbers as typed: "5" or "1 thru 10" (reversed ranges select in reverse order)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FixtureRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Which end of the selection a fan starts from (the "fan left/right/center/outside" modes)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FanDirection {
+    /// Values spread outward from the centre
+    #[default]
+    Center,
+    /// Values run from the first fixture to the last
+    Left,
+    /// Values run from the last fixture to the first
+    Right,
+    /// Both ends share the start value and meet in the centre
+    Outside,
+}
+
+/// Axis fixtures are ordered along before fanning, when every fixture has a stage position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FanAxis {
+    #[default]
+    X,
+    Y,
+}
+
+/// Parsed command line. Mirrors the command types produced by `src/utils/cliParser.js`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CliCommand {
+    Clear,
+    Blackout,
+    /// Selected fixtures to their home position at full
+    Locate,
+    Undo,
+    Redo,
+    Help,
+    /// Active selection at full over everything else; `None` toggles
+    Highlight { state: Option<bool> },
+    /// Hold the active selection at its current output
+    Park,
+    Unpark,
+    FeatureSet { feature_set: FeatureSet },
+    SelectFixtures { fixtures: Vec<FixtureRange> },
+    /// "[fixtures] [attribute] at value [thru value]". Without fixtures it applies to the
+    /// current selection; without an attribute it sets intensity; "thru" fans the value.
+    SetValue {
+        fixtures: Vec<FixtureRange>,
+        attribute: Option<String>,
+        value: u8,
+        fan_to: Option<u8>,
+    },
+    /// Fade time used when recording cues from the CLI
+    FadeTime { seconds: f64 },
+    CueTime { cue: f64, seconds: f64 },
+    /// Fade for every cue played on an executor, replacing the cues' own fades
+    ExecutorTime { executor: u32, seconds: f64 },
+    Fan { direction: FanDirection, axis: FanAxis },
+    /// Set the Nth attribute of the current feature set on the selection
+    Encoder { encoder: u32, value: u8 },
+    Go { executor: Option<u32>, cue: Option<f64> },
+    RecallCue { cue: f64 },
+    /// `feature_set` is the current feature set when not typed ("preset 5")
+    RecallPreset {
+        feature_set: Option<FeatureSet>,
+        number: u32,
+        group: Option<u32>,
+    },
+    SelectGroup { group: u32 },
+    /// Record (or update, which requires the cue to exist) a cue; without a number the
+    /// next whole cue number is used
+    RecordCue {
+        cue: Option<f64>,
+        name: Option<String>,
+        update: bool,
+    },
+    RecordPreset {
+        feature_set: Option<FeatureSet>,
+        number: u32,
+        name: Option<String>,
+        update: bool,
+    },
+    RecordGroup { group: u32, name: Option<String> },
+    /// Window, video, clock and conditional commands handled by the desktop UI
+    Desktop { command: String },
+}
+
+impl CliCommand {
+    /// Parts of the show the command changes, for the undo history
+    pub fn domains(&self) -> &'static [Domain] {
+        match self {
+            // Blackout also releases playback; undo brings the programmer back
+            CliCommand::Clear
+            | CliCommand::Blackout
+            | CliCommand::Locate
+            | CliCommand::SetValue { .. }
+            | CliCommand::Encoder { .. }
+            | CliCommand::RecallPreset { .. } => &[Domain::Programmer],
+            CliCommand::CueTime { .. } | CliCommand::ExecutorTime { .. } | CliCommand::RecordCue { .. } => &[Domain::Cues],
+            CliCommand::RecordPreset { .. } => &[Domain::Presets, Domain::Cues],
+            CliCommand::RecordGroup { .. } => &[Domain::Groups],
+            _ => &[],
+        }
+    }
+}
+
+/// Parse failure with the byte offset of the offending token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.position + 1)
+    }
+}
+
+struct Token {
+    text: String,
+    keyword: String,
+    position: usize,
+    quoted: bool,
+}
+
+/// Map aliases onto the keyword they stand for
+fn keyword(word: &str) -> String {
+    let word = word.to_lowercase();
+    match word.as_str() {
+        "clk" | "tod" | "tc" | "timecode" => "clock",
+        "exec" => "executor",
+        "through" => "thru",
+        "bo" => "blackout",
+        "c" => "clear",
+        "loc" => "locate",
+        "u" => "undo",
+        "r" => "redo",
+        "hi" | "hilight" => "highlight",
+        "enc" | "wheel" => "encoder",
+        "w" | "open" => "window",
+        "colour" => "color",
+        "fix" => "fixture",
+        _ => return word,
+    }
+    .to_string()
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '+' {
+            chars.next();
+            tokens.push(Token { text: "+".to_string(), keyword: "+".to_string(), position, quoted: false });
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => text.push(c),
+                    None => return Err(ParseError { message: "Unterminated quote".to_string(), position }),
+                }
+            }
+            tokens.push(Token { keyword: text.to_lowercase(), text, position, quoted: true });
+        } else {
+            let mut text = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || c == '+' || c == '"' {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+            tokens.push(Token { keyword: keyword(&text), text, position, quoted: false });
+        }
+    }
+    Ok(tokens)
+}
+
+const DESKTOP_KEYWORDS: &[&str] = &[
+    "window", "close", "play", "pause", "stop", "restart", "loop", "speed", "resume", "video", "ndi", "clock",
+    "start", "reset", "videosource", "videooutput",
+];
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    index: usize,
+    /// Highest fixture number a "thru" range may reach, when parsing against a patch
+    max_fixture: Option<u32>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.index).map(|t| t.keyword.as_str())
+    }
+
+    fn peek_is(&self, keyword: &str) -> bool {
+        self.tokens.get(self.index).is_some_and(|t| !t.quoted && t.keyword == keyword)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.index).map(|t| t.position).unwrap_or(self.input.len())
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { message: message.into(), position: self.position() })
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.index);
+        self.index += 1;
+        token
+    }
+
+    fn eat(&mut self, keyword: &str) -> bool {
+        if self.peek_is(keyword) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.index >= self.tokens.len()
+    }
+
+    fn end(&self) -> Result<(), ParseError> {
+        match self.tokens.get(self.index) {
+            Some(token) => self.error(format!("Unexpected '{}'", token.text)),
+            None => Ok(()),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, ParseError> {
+        match self.peek().and_then(|t| t.parse().ok()) {
+            Some(value) => {
+                self.index += 1;
+                Ok(value)
+            }
+            None => self.error(format!("Expected {}", what)),
+        }
+    }
+
+    /// Positive whole number such as a fixture, group or preset number
+    fn id(&mut self, what: &str) -> Result<u32, ParseError> {
+        let position = self.position();
+        let value: u32 = self.number(what)?;
+        if value == 0 {
+            return Err(ParseError { message: format!("{} numbers start at 1", what), position });
+        }
+        Ok(value)
+    }
+
+    /// DMX value 0-255, or "full"/"out"
+    fn value(&mut self) -> Result<u8, ParseError> {
+        match self.peek() {
+            Some("full") => {
+                self.index += 1;
+                Ok(255)
+            }
+            Some("out") => {
+                self.index += 1;
+                Ok(0)
+            }
+            Some(text) if text.parse::<u32>().is_ok() => match text.parse::<u8>() {
+                Ok(value) => {
+                    self.index += 1;
+                    Ok(value)
+                }
+                Err(_) => self.error("Values must be between 0 and 255"),
+            },
+            _ => self.error("Expected a value (0-255 or full)"),
+        }
+    }
+
+    /// Everything left on the line, for names
+    fn rest(&mut self) -> Option<String> {
+        if self.at_end() {
+            return None;
+        }
+        let name = self.tokens[self.index..].iter().map(|t| t.text.as_str()).collect::<Vec<_>>().join(" ");
+        self.index = self.tokens.len();
+        Some(name)
+    }
+
+    fn dot_notation(&self) -> Option<(u32, u32)> {
+        let (set, number) = self.peek()?.split_once('.')?;
+        Some((set.parse().ok()?, number.parse().ok()?))
+    }
+
+    /// "3.5" as a preset address
+    fn preset(&mut self) -> Result<(FeatureSet, u32), ParseError> {
+        let Some((set, number)) = self.dot_notation() else {
+            return self.error("Expected a preset like 3.5");
+        };
+        if set == 7 || set == 8 {
+            return self.error("Video presets are handled by the desktop UI");
+        }
+        let Some(feature_set) = FeatureSet::from_number(set) else {
+            return self.error(format!("Unknown feature set {}", set));
+        };
+        if number == 0 {
+            return self.error("Preset numbers start at 1");
+        }
+        self.index += 1;
+        Ok((feature_set, number))
+    }
+
+    fn fixture_ranges(&mut self) -> Result<Vec<FixtureRange>, ParseError> {
+        let mut ranges = Vec::new();
+        loop {
+            let start_position = self.position();
+            let start = self.id("Fixture")?;
+            let end = if self.eat("thru") {
+                let end_position = self.position();
+                let end = self.id("Fixture")?;
+                self.check_range_end(start, start_position)?;
+                self.check_range_end(end, end_position)?;
+                end
+            } else {
+                start
+            };
+            ranges.push(FixtureRange { start, end });
+            if !self.eat("+") {
+                return Ok(ranges);
+            }
+        }
+    }
+
+    fn check_range_end(&self, number: u32, position: usize) -> Result<(), ParseError> {
+        match self.max_fixture {
+            Some(max) if number > max => Err(ParseError {
+                message: format!("Fixture {} is beyond the patch (highest is {})", number, max),
+                position,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// "at value [thru value]" after the fixtures/attribute
+    fn set_value(&mut self, fixtures: Vec<FixtureRange>, attribute: Option<String>) -> Result<CliCommand, ParseError> {
+        let value = self.value()?;
+        let fan_to = if self.eat("thru") { Some(self.value()?) } else { None };
+        self.end()?;
+        Ok(CliCommand::SetValue { fixtures, attribute, value, fan_to })
+    }
+
+    fn parse(&mut self) -> Result<CliCommand, ParseError> {
+        if self.tokens.iter().any(|t| !t.quoted && t.keyword == "if") {
+            return Ok(CliCommand::Desktop { command: self.input.trim().to_string() });
+        }
+        let Some(first) = self.peek().map(str::to_string) else {
+            return self.error("Empty command");
+        };
+        if DESKTOP_KEYWORDS.contains(&first.as_str()) || first.contains('/') {
+            return Ok(CliCommand::Desktop { command: self.input.trim().to_string() });
+        }
+        if let Some(feature_set) = FeatureSet::from_name(&first).filter(|_| first != "dimmer") {
+            return self.feature_set(feature_set);
+        }
+
+        let simple = match first.as_str() {
+            "clear" => Some(CliCommand::Clear),
+            "blackout" => Some(CliCommand::Blackout),
+            "locate" => Some(CliCommand::Locate),
+            "undo" => Some(CliCommand::Undo),
+            "redo" => Some(CliCommand::Redo),
+            "park" => Some(CliCommand::Park),
+            "unpark" => Some(CliCommand::Unpark),
+            _ => None,
+        };
+        if let Some(command) = simple {
+            self.index += 1;
+            self.end()?;
+            return Ok(command);
+        }
+
+        match first.as_str() {
+            "help" => Ok(CliCommand::Help),
+            "highlight" => {
+                self.index += 1;
+                let state = match self.peek() {
+                    Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    _ => None,
+                };
+                if state.is_some() {
+                    self.index += 1;
+                }
+                self.end()?;
+                Ok(CliCommand::Highlight { state })
+            }
+            "time" => self.time(),
+            "fan" => self.fan(),
+            "encoder" => {
+                self.index += 1;
+                let encoder = self.id("Encoder")?;
+                let value = self.value()?;
+                self.end()?;
+                Ok(CliCommand::Encoder { encoder, value })
+            }
+            "go" => self.go(),
+            "record" | "update" => self.record(),
+            "group" => self.group(),
+            "cue" => {
+                self.index += 1;
+                let cue = self.number("a cue number")?;
+                self.end()?;
+                Ok(CliCommand::RecallCue { cue })
+            }
+            "preset" => {
+                self.index += 1;
+                let (feature_set, number) = match self.dot_notation() {
+                    Some(_) => self.preset().map(|(f, n)| (Some(f), n))?,
+                    None => (None, self.id("Preset")?),
+                };
+                self.end()?;
+                Ok(CliCommand::RecallPreset { feature_set, number, group: None })
+            }
+            "at" => {
+                self.index += 1;
+                self.set_value(Vec::new(), None)
+            }
+            _ if self.dot_notation().is_some() => {
+                let (feature_set, number) = self.preset()?;
+                self.end()?;
+                Ok(CliCommand::RecallPreset { feature_set: Some(feature_set), number, group: None })
+            }
+            "fixture" => {
+                self.index += 1;
+                self.fixtures()
+            }
+            _ if first.parse::<u32>().is_ok() => self.fixtures(),
+            _ => self.attribute(),
+        }
+    }
+
+    fn feature_set(&mut self, feature_set: FeatureSet) -> Result<CliCommand, ParseError> {
+        self.index += 1;
+        if self.at_end() {
+            return Ok(CliCommand::FeatureSet { feature_set });
+        }
+        if self.eat("at") {
+            // "intensity at 50"; other feature names aren't attributes
+            return match feature_set {
+                FeatureSet::Intensity => self.set_value(Vec::new(), None),
+                _ => self.error("Expected a preset number"),
+            };
+        }
+        let number = self.id("Preset")?;
+        self.end()?;
+        Ok(CliCommand::RecallPreset { feature_set: Some(feature_set), number, group: None })
+    }
+
+    fn fixtures(&mut self) -> Result<CliCommand, ParseError> {
+        let start = self.index;
+        let fixtures = self.fixture_ranges()?;
+        if self.at_end() {
+            return Ok(CliCommand::SelectFixtures { fixtures });
+        }
+        if self.eat("at") {
+            return self.set_value(fixtures, None);
+        }
+        // "4 10" and "9 color" route windows on the desktop
+        if self.index == start + 1 && self.index + 1 == self.tokens.len() {
+            return Ok(CliCommand::Desktop { command: self.input.trim().to_string() });
+        }
+        self.error(format!("Unexpected '{}'", self.tokens[self.index].text))
+    }
+
+    /// "red at 255", "pan at 0 thru 255" or the "red 255" shorthand
+    fn attribute(&mut self) -> Result<CliCommand, ParseError> {
+        let attribute = self.next().map(|t| t.text.clone());
+        if self.at_end() {
+            self.index -= 1;
+            return self.error(format!("Unknown command '{}'", attribute.unwrap_or_default()));
+        }
+        self.eat("at");
+        self.set_value(Vec::new(), attribute)
+    }
+
+    fn time(&mut self) -> Result<CliCommand, ParseError> {
+        self.index += 1;
+        let position = self.position();
+        let seconds: f64 = self.number("a time in seconds")?;
+        if !(0.0..=3600.0).contains(&seconds) {
+            return Err(ParseError { message: "Time must be between 0 and 3600 seconds".to_string(), position });
+        }
+        if self.eat("cue") {
+            let cue = self.number("a cue number")?;
+            self.end()?;
+            return Ok(CliCommand::CueTime { cue, seconds });
+        }
+        if self.eat("executor") {
+            let executor = self.number("an executor number")?;
+            self.end()?;
+            return Ok(CliCommand::ExecutorTime { executor, seconds });
+        }
+        self.end()?;
+        Ok(CliCommand::FadeTime { seconds })
+    }
+
+    fn fan(&mut self) -> Result<CliCommand, ParseError> {
+        self.index += 1;
+        let direction = match self.peek() {
+            Some("center") | Some("centre") => Some(FanDirection::Center),
+            Some("left") => Some(FanDirection::Left),
+            Some("right") => Some(FanDirection::Right),
+            Some("outside") => Some(FanDirection::Outside),
+            _ => None,
+        };
+        if direction.is_some() {
+            self.index += 1;
+        }
+        let axis = match self.peek() {
+            Some("x") => Some(FanAxis::X),
+            Some("y") => Some(FanAxis::Y),
+            _ => None,
+        };
+        if axis.is_some() {
+            self.index += 1;
+        }
+        self.end()?;
+        Ok(CliCommand::Fan { direction: direction.unwrap_or_default(), axis: axis.unwrap_or_default() })
+    }
+
+    fn go(&mut self) -> Result<CliCommand, ParseError> {
+        self.index += 1;
+        let command = if self.eat("cue") {
+            CliCommand::Go { executor: None, cue: Some(self.number("a cue number")?) }
+        } else if self.eat("executor") {
+            // "go exec 2.5" fires cue 5 on executor 2
+            match self.dot_notation() {
+                Some((executor, cue)) => {
+                    self.index += 1;
+                    CliCommand::Go { executor: Some(executor), cue: Some(cue as f64) }
+                }
+                None => CliCommand::Go { executor: Some(self.id("Executor")?), cue: None },
+            }
+        } else {
+            CliCommand::Go { executor: None, cue: None }
+        };
+        self.end()?;
+        Ok(command)
+    }
+
+    fn record(&mut self) -> Result<CliCommand, ParseError> {
+        let update = self.next().is_some_and(|t| t.keyword == "update");
+        if self.dot_notation().is_some() {
+            let (feature_set, number) = self.preset()?;
+            let name = self.rest();
+            return Ok(CliCommand::RecordPreset { feature_set: Some(feature_set), number, name, update });
+        }
+
+        let target = self.peek().map(str::to_string).filter(|_| !self.tokens[self.index].quoted);
+        match target.as_deref() {
+            Some("cue") => {
+                self.index += 1;
+                let cue = self.number("a cue number")?;
+                Ok(CliCommand::RecordCue { cue: Some(cue), name: self.rest(), update })
+            }
+            Some("preset") => {
+                self.index += 1;
+                let number = self.id("Preset")?;
+                Ok(CliCommand::RecordPreset { feature_set: None, number, name: self.rest(), update })
+            }
+            Some("group") => {
+                self.index += 1;
+                let group = self.id("Group")?;
+                if self.peek_is("executor") {
+                    return Ok(CliCommand::Desktop { command: self.input.trim().to_string() });
+                }
+                Ok(CliCommand::RecordGroup { group, name: self.rest() })
+            }
+            Some("executor") | Some("videosource") | Some("videooutput") => {
+                Ok(CliCommand::Desktop { command: self.input.trim().to_string() })
+            }
+            Some(word) if FeatureSet::from_name(word).is_some() && word != "dimmer" => {
+                let feature_set = FeatureSet::from_name(word);
+                self.index += 1;
+                let number = self.id("Preset")?;
+                Ok(CliCommand::RecordPreset { feature_set, number, name: self.rest(), update })
+            }
+            _ if update => {
+                if self.at_end() {
+                    self.error("Expected cue, preset or a preset like 3.5")
+                } else {
+                    self.error(format!("Cannot update '{}'", self.tokens[self.index].text))
+                }
+            }
+            _ => Ok(CliCommand::RecordCue { cue: None, name: self.rest(), update }),
+        }
+    }
+
+    fn group(&mut self) -> Result<CliCommand, ParseError> {
+        self.index += 1;
+        let group = self.id("Group")?;
+        if self.eat("preset") {
+            let (feature_set, number) = self.preset()?;
+            self.end()?;
+            return Ok(CliCommand::RecallPreset { feature_set: Some(feature_set), number, group: Some(group) });
+        }
+        // Group handle modes, priorities and masters live in the desktop UI
+        if matches!(self.peek(), Some("mode") | Some("priority") | Some("intensity")) {
+            return Ok(CliCommand::Desktop { command: self.input.trim().to_string() });
+        }
+        self.end()?;
+        Ok(CliCommand::SelectGroup { group })
+    }
+}
+
+/// Parse a command line
+pub fn parse(input: &str) -> Result<CliCommand, ParseError> {
+    let tokens = tokenize(input)?;
+    Parser { input, tokens, index: 0, max_fixture: None }.parse()
+}
+
+/// Parse a command line to run against `fixtures`. "thru" ranges can't go past the
+/// highest fixture number, so a typo can't ask for billions of fixtures.
+pub fn parse_for_patch(input: &str, fixtures: &HashMap<String, Fixture>) -> Result<CliCommand, ParseError> {
+    let tokens = tokenize(input)?;
+    Parser { input, tokens, index: 0, max_fixture: Some(highest_fixture_number(fixtures)) }.parse()
+}
+
+/// Typed numbers are fixture ids when the patch has numbered fixtures, otherwise
+/// positions in patch order. The whole patch uses one rule so a range never mixes them.
+fn numbers_are_ids(fixtures: &HashMap<String, Fixture>) -> bool {
+    fixtures.keys().any(|id| id.parse::<u32>().is_ok())
+}
+
+/// Highest number that can match a fixture
+fn highest_fixture_number(fixtures: &HashMap<String, Fixture>) -> u32 {
+    if numbers_are_ids(fixtures) {
+        fixtures.keys().filter_map(|id| id.parse::<u32>().ok()).max().unwrap_or(0)
+    } else {
+        fixtures.len().try_into().unwrap_or(u32::MAX)
+    }
+}
+
+/// CLI modes that carry over between command lines
+#[derive(Debug, Clone, Default)]
+pub struct CliSession {
+    pub feature_set: Option<FeatureSet>,
+    pub fade_time: Option<f64>,
+    pub fan_direction: FanDirection,
+    pub fan_axis: FanAxis,
+}
+
+const HELP: &str = "\
+Selection:   fixture 1 thru 10 | 1+5+10 | group 2 | clear
+Values:      at 50 | 1 thru 4 at full | red at 255 | red 255 | pan at 0 thru 255 (fan)
+             locate | highlight [on|off] | park | unpark | encoder 3 128
+Fan:         fan [center|left|right|outside] [x|y]
+Presets:     color | 3.5 | color 5 | preset 5 | group 1 preset 3.5
+             record 3.5 [name] | record color 5 [name] | update 3.5
+Cues:        go | go cue 5 | go exec 2 | go exec 2.5 | cue 5
+             record [name] | record cue 5 [name] | update cue 5 | record group 3 [name]
+Timing:      time 3 (fade for recorded cues) | time 3 cue 5 | time 3 exec 2
+Other:       blackout | help";
+
+/// Programmer values of one fixture, by attribute name, for printed feedback
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProgrammerFixture {
+    pub fixture_id: String,
+    pub name: String,
+    pub values: Vec<(String, u8)>,
+}
+
+/// Programmer contents in patch order
+pub fn programmer_summary(state: &AppState) -> Result<Vec<ProgrammerFixture>, String> {
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+    let programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+
+    let mut summary = Vec::new();
+    for fixture_id in patch_order(&fixtures) {
+        let values: Vec<(String, u8)> = lookup
+            .channels(&fixture_id)
+            .iter()
+            .filter_map(|c| programmer.get(&channel_key(&fixture_id, c.offset)).map(|v| (c.name.clone(), *v)))
+            .collect();
+        if !values.is_empty() {
+            let name = fixtures.get(&fixture_id).map(|f| f.name.clone()).unwrap_or_default();
+            summary.push(ProgrammerFixture { fixture_id, name, values });
+        }
+    }
+    Ok(summary)
+}
+
+/// One programmer value to set by attribute, from the web API
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProgrammerValue {
+    pub fixture_id: String,
+    /// Attribute name or channel type ("Dimmer", "Pan", "red"...)
+    pub attribute: String,
+    pub value: u8,
+}
+
+/// Set programmer values by attribute. Nothing is set unless every value resolves.
+pub fn set_programmer_values(state: &AppState, source: &str, values: &[ProgrammerValue]) -> Result<String, String> {
+    let label = format!("Set {} programmer values", values.len());
+    history::journal_from(state, source, label, &[Domain::Programmer], || {
+        let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        let lookup = PatchLookup::new(&fixtures, &library);
+
+        let mut keys = Vec::new();
+        for value in values {
+            if !fixtures.contains_key(&value.fixture_id) {
+                return Err(format!("Fixture {} not found", value.fixture_id));
+            }
+            let channel = find_attribute(&lookup, &value.fixture_id, Some(&value.attribute))
+                .ok_or_else(|| format!("Fixture {} has no {}", value.fixture_id, value.attribute))?;
+            keys.push((channel_key(&value.fixture_id, channel.offset), value.value));
+        }
+        state.programmer.lock().map_err(|e| e.to_string())?.extend(keys);
+        Ok(format!("Set {} programmer values", values.len()))
+    })
+}
+
+/// Words offered by tab completion, besides fixture names
+pub const KEYWORDS: &[&str] = &[
+    "at", "blackout", "beam", "clear", "color", "cue", "encoder", "executor", "fan", "fixture", "focus", "full",
+    "go", "gobo", "group", "help", "highlight", "intensity", "locate", "off", "on", "out", "park", "position",
+    "preset", "record", "redo", "thru", "time", "undo", "unpark", "update",
+];
+
+/// Resolve typed fixture numbers (see `numbers_are_ids`). Each fixture is listed once,
+/// where it was first typed.
+pub fn resolve_fixtures(fixtures: &HashMap<String, Fixture>, ranges: &[FixtureRange]) -> Result<Vec<String>, String> {
+    let order = patch_order(fixtures);
+    let by_id = numbers_are_ids(fixtures);
+    let highest = highest_fixture_number(fixtures);
+    let mut ids = Vec::new();
+    let mut seen = HashSet::new();
+    for range in ranges {
+        // Numbers past the patch can't match anything
+        let numbers: Box<dyn Iterator<Item = u32>> = if range.end >= range.start {
+            Box::new(range.start..=range.end.min(highest))
+        } else {
+            Box::new((range.end..=range.start.min(highest)).rev())
+        };
+        let mut matched = 0;
+        for number in numbers {
+            if matched == order.len() {
+                break;
+            }
+            let id = if by_id {
+                Some(number.to_string()).filter(|id| fixtures.contains_key(id))
+            } else {
+                (number as usize).checked_sub(1).and_then(|index| order.get(index).cloned())
+            };
+            match id {
+                Some(id) if seen.contains(&id) => {}
+                Some(id) => {
+                    seen.insert(id.clone());
+                    ids.push(id);
+                    matched += 1;
+                }
+                None if range.start == range.end => return Err(format!("Fixture {} not found", number)),
+                None => {}
+            }
+        }
+    }
+    if ids.is_empty() {
+        return Err("No fixtures found".to_string());
+    }
+    Ok(ids)
+}
+
+/// Group id for a typed group number: a group with that id, otherwise the Nth group
+fn resolve_group(groups: &[Group], number: u32) -> Result<Group, String> {
+    groups
+        .iter()
+        .find(|g| g.id == number.to_string())
+        .or_else(|| groups.get((number as usize).checked_sub(1)?))
+        .cloned()
+        .ok_or_else(|| format!("Group {} not found", number))
+}
+
+/// Channel for an attribute on a fixture: intensity when none is given, else an exact
+/// name/type match, else the first channel whose name contains it ("red" finds "Red 1")
+fn find_attribute<'a>(lookup: &PatchLookup<'a>, fixture_id: &str, attribute: Option<&str>) -> Option<&'a FixtureChannel> {
+    let channels = lookup.channels(fixture_id);
+    match attribute {
+        None => channels
+            .iter()
+            .find(|c| FeatureSet::from_channel_type(&c.channel_type) == FeatureSet::Intensity),
+        Some(attribute) => {
+            let wanted = attribute.to_lowercase().replace('_', " ");
+            channels
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(&wanted) || c.channel_type.eq_ignore_ascii_case(attribute))
+                .or_else(|| channels.iter().find(|c| c.name.to_lowercase().contains(&wanted)))
+        }
+    }
+}
+
+/// Order fixtures along a stage axis. Selection order is kept unless every fixture has a position.
+fn order_by_axis(fixture_ids: &[String], fixtures: &HashMap<String, Fixture>, axis: FanAxis) -> Vec<String> {
+    let positions: Option<Vec<f32>> = fixture_ids
+        .iter()
+        .map(|id| {
+            let position = fixtures.get(id)?.position.as_ref()?;
+            // Left to right, top to bottom (stage y points up)
+            Some(match axis {
+                FanAxis::X => position.x,
+                FanAxis::Y => -position.y,
+            })
+        })
+        .collect();
+    let mut ordered: Vec<(f32, String)> = match positions {
+        Some(positions) => positions.into_iter().zip(fixture_ids.iter().cloned()).collect(),
+        None => return fixture_ids.to_vec(),
+    };
+    ordered.sort_by(|a, b| a.0.total_cmp(&b.0));
+    ordered.into_iter().map(|(_, id)| id).collect()
+}
+
+fn active_selection(state: &AppState) -> Result<Vec<String>, String> {
+    let active = state.selection.lock().map_err(|e| e.to_string())?.active();
+    if active.is_empty() {
+        return Err("No fixtures selected".to_string());
+    }
+    Ok(active)
+}
+
+fn session(state: &AppState) -> Result<CliSession, String> {
+    Ok(state.cli.lock().map_err(|e| e.to_string())?.clone())
+}
+
+fn select(state: &AppState, ranges: &[FixtureRange]) -> Result<usize, String> {
+    let fixture_ids = {
+        let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        resolve_fixtures(&fixtures, ranges)?
+    };
+    let status = selection::run_command(&state.fixtures, &state.selection, SelectionCommand::Select { fixture_ids })?;
+    Ok(status.fixture_ids.len())
+}
+
+fn set_value(state: &AppState, attribute: Option<&str>, value: u8, fan_to: Option<u8>) -> Result<String, String> {
+    let fixture_ids = active_selection(state)?;
+    let session = session(state)?;
+    let label = attribute.unwrap_or("intensity");
+
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+    let mut programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+
+    if let Some(end) = fan_to {
+        let Some(channel) = fixture_ids.iter().find_map(|id| find_attribute(&lookup, id, attribute)) else {
+            return Err(format!("No selected fixture has {}", label));
+        };
+        let mut ordered = order_by_axis(&fixture_ids, &fixtures, session.fan_axis);
+        let (mut start, mut end_value) = (value as f32, end as f32);
+        let mode = match session.fan_direction {
+            FanDirection::Center => FanMode::CenterOut,
+            FanDirection::Left => FanMode::Linear,
+            FanDirection::Right => {
+                ordered.reverse();
+                FanMode::Linear
+            }
+            // Outside in: the outermost fixtures get the first value
+            FanDirection::Outside => {
+                std::mem::swap(&mut start, &mut end_value);
+                FanMode::CenterOut
+            }
+        };
+        let request = FanRequest {
+            fixture_ids: ordered,
+            attribute: channel.name.clone(),
+            start,
+            end: end_value,
+            mode,
+            group_size: None,
+        };
+        let result = fan::apply_fan(&request, &lookup, &mut programmer)?;
+        return Ok(format!("Fanned {} {} thru {} over {} fixtures", channel.name, value, end, result.values.len()));
+    }
+
+    let mut count = 0;
+    for fixture_id in &fixture_ids {
+        if let Some(channel) = find_attribute(&lookup, fixture_id, attribute) {
+            programmer.insert(channel_key(fixture_id, channel.offset), value);
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return Err(format!("No selected fixture has {}", label));
+    }
+    Ok(format!("Set {} to {} on {} fixtures", label, value, count))
+}
+
+/// Selected fixtures' channels to their defaults with intensity at full
+fn locate(state: &AppState) -> Result<String, String> {
+    let fixture_ids = active_selection(state)?;
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+    let mut programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+    for fixture_id in &fixture_ids {
+        for channel in lookup.channels(fixture_id) {
+            let value = match FeatureSet::from_channel_type(&channel.channel_type) {
+                FeatureSet::Intensity => 255,
+                _ => channel.default_value,
+            };
+            programmer.insert(channel_key(fixture_id, channel.offset), value);
+        }
+    }
+    Ok(format!("Located {} fixtures", fixture_ids.len()))
+}
+
+/// Hold every channel of the selection at what is being output right now
+fn park(state: &AppState) -> Result<String, String> {
+    let fixture_ids = active_selection(state)?;
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let lookup = PatchLookup::new(&fixtures, &library);
+    let engine = state.dmx_engine.lock().map_err(|e| e.to_string())?;
+
+    let mut parked = HashMap::new();
+    for fixture_id in &fixture_ids {
+        let Some(fixture) = fixtures.get(fixture_id) else { continue };
+        for offset in 0..fixture.channel_count {
+            let key = channel_key(fixture_id, offset);
+            if let Some((universe, channel)) = lookup.address(&key) {
+                let value = engine.universes.get(&universe).map(|u| u[channel as usize - 1]).unwrap_or(0);
+                parked.insert(key, value);
+            }
+        }
+    }
+    let count = parked.len();
+    state.overrides.lock().map_err(|e| e.to_string())?.parked.extend(parked);
+    Ok(format!("Parked {} channels", count))
+}
+
+/// Release parked channels of the selection, or everything when nothing is selected
+fn unpark(state: &AppState) -> Result<String, String> {
+    let selected = state.selection.lock().map_err(|e| e.to_string())?.active();
+    let mut overrides = state.overrides.lock().map_err(|e| e.to_string())?;
+    let before = overrides.parked.len();
+    if selected.is_empty() {
+        overrides.parked.clear();
+    } else {
+        overrides.parked.retain(|key, _| {
+            crate::attributes::parse_channel_key(key)
+                .map(|(fixture_id, _)| !selected.iter().any(|s| s == fixture_id))
+                .unwrap_or(true)
+        });
+    }
+    Ok(format!("Unparked {} channels", before - overrides.parked.len()))
+}
+
+fn encoder(state: &AppState, encoder: u32, value: u8) -> Result<String, String> {
+    let fixture_ids = active_selection(state)?;
+    let feature_set = session(state)?.feature_set.unwrap_or(FeatureSet::Intensity);
+    let attribute = {
+        let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        let lookup = PatchLookup::new(&fixtures, &library);
+        let mut channels: Vec<&FixtureChannel> = lookup
+            .channels(&fixture_ids[0])
+            .iter()
+            .filter(|c| FeatureSet::from_channel_type(&c.channel_type) == feature_set)
+            .collect();
+        channels.sort_by_key(|c| c.offset);
+        channels
+            .get(encoder as usize - 1)
+            .map(|c| c.name.clone())
+            .ok_or_else(|| format!("Encoder {} has no {:?} attribute", encoder, feature_set))?
+    };
+    set_value(state, Some(&attribute), value, None)
+}
+
+fn preset_id(state: &AppState, feature_set: Option<FeatureSet>, number: u32) -> Result<PresetId, String> {
+    let feature_set = match feature_set {
+        Some(feature_set) => feature_set,
+        None => session(state)?.feature_set.ok_or("Choose a feature set first (e.g. color)")?,
+    };
+    PresetId::new(feature_set, number)
+}
+
+/// Cue list an executor plays; executors without an entry play the list with their number
+fn executor_list(state: &AppState, executor: u32) -> Result<u32, String> {
+    let show = state.show.lock().map_err(|e| e.to_string())?;
+    Ok(show
+        .executors
+        .iter()
+        .find(|e| e.number == executor)
+        .map(|e| e.cue_list)
+        .unwrap_or(executor))
+}
+
+/// Cue addressed by a command
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CueRef {
+    pub cue_list: u32,
+    pub cue: f64,
+}
+
+/// What a command did, for remote tools
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub message: String,
+    /// Fixtures the command selected or changed
+    pub fixtures: Vec<String>,
+    pub cues: Vec<CueRef>,
+}
+
+/// Result of running a command line: the parsed command, and either what it did or
+/// why it failed. `error` is set when the line didn't parse.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommandResult {
+    pub success: bool,
+    pub message: String,
+    pub command: Option<CliCommand>,
+    pub error: Option<ParseError>,
+    #[serde(default)]
+    pub fixtures: Vec<String>,
+    #[serde(default)]
+    pub cues: Vec<CueRef>,
+}
+
+/// Parse and execute a command line, reporting the parsed command and what it affected.
+/// Web clients are told about every command that ran, whoever sent it. `source` is who
+/// sent it, for the undo history.
+pub fn run_structured(state: &AppState, source: &str, input: &str) -> CommandResult {
+    let result = run_line(state, source, input);
+    let _ = state.events.send(Event::CommandExecuted {
+        command: input.trim().to_string(),
+        success: result.success,
+        message: result.message.clone(),
+    });
+    result
+}
+
+fn run_line(state: &AppState, source: &str, input: &str) -> CommandResult {
+    let parsed = match state.fixtures.lock() {
+        Ok(fixtures) => parse_for_patch(input, &fixtures),
+        Err(_) => parse(input),
+    };
+    let command = match parsed {
+        Ok(command) => command,
+        Err(error) => {
+            return CommandResult {
+                success: false,
+                message: error.to_string(),
+                command: None,
+                error: Some(error),
+                fixtures: Vec::new(),
+                cues: Vec::new(),
+            }
+        }
+    };
+    let result = history::journal_from(state, source, input.trim(), command.domains(), || execute(state, &command));
+    let (success, outcome) = match result {
+        Ok(outcome) => (true, outcome),
+        Err(message) => (false, Outcome { message, ..Outcome::default() }),
+    };
+    CommandResult {
+        success,
+        message: outcome.message,
+        command: Some(command),
+        error: None,
+        fixtures: outcome.fixtures,
+        cues: outcome.cues,
+    }
+}
+
+/// Next whole cue number after the last cue of the default list
+fn next_cue_number(state: &AppState) -> Result<f64, String> {
+    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let last = cue_engine.list(DEFAULT_CUE_LIST).ok().and_then(|l| l.cues.last()).map(|c| c.number);
+    Ok(last.map(|n| n.floor() + 1.0).unwrap_or(1.0))
+}
+
+fn current_cue(state: &AppState, cue_list: u32) -> Result<Option<CueRef>, String> {
+    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let current = cue_engine.list(cue_list).ok().and_then(|l| l.status(cue_engine.now()).current_cue);
+    Ok(current.map(|cue| CueRef { cue_list, cue }))
+}
+
+fn programmer_fixtures(state: &AppState) -> Result<Vec<String>, String> {
+    let programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+    let mut fixture_ids: Vec<String> = programmer
+        .keys()
+        .filter_map(|key| crate::attributes::parse_channel_key(key).map(|(fixture_id, _)| fixture_id.to_string()))
+        .collect();
+    fixture_ids.sort();
+    fixture_ids.dedup();
+    Ok(fixture_ids)
+}
+
+/// Execute a parsed command against the backend
+pub fn execute(state: &AppState, command: &CliCommand) -> Result<Outcome, String> {
+    // Targets that have to be worked out before the command changes them
+    let cleared = match command {
+        CliCommand::Clear => programmer_fixtures(state)?,
+        _ => Vec::new(),
+    };
+    let recorded_cue = match command {
+        CliCommand::RecordCue { cue, .. } => Some(cue.map(Ok).unwrap_or_else(|| next_cue_number(state))?),
+        _ => None,
+    };
+
+    let message = execute_command(state, command)?;
+    let mut outcome = Outcome { message, ..Outcome::default() };
+
+    match command {
+        CliCommand::Clear => outcome.fixtures = cleared,
+        CliCommand::RecallPreset { group: Some(group), .. } => {
+            let selection = state.selection.lock().map_err(|e| e.to_string())?;
+            outcome.fixtures = resolve_group(selection.groups(), *group)?.fixture_ids;
+        }
+        CliCommand::Locate
+        | CliCommand::Highlight { .. }
+        | CliCommand::Park
+        | CliCommand::Unpark
+        | CliCommand::SelectFixtures { .. }
+        | CliCommand::SetValue { .. }
+        | CliCommand::Encoder { .. }
+        | CliCommand::RecallPreset { group: None, .. }
+        | CliCommand::SelectGroup { .. }
+        | CliCommand::RecordGroup { .. } => {
+            outcome.fixtures = state.selection.lock().map_err(|e| e.to_string())?.active();
+        }
+        CliCommand::Go { executor, .. } => {
+            let cue_list = match executor {
+                Some(executor) => executor_list(state, *executor)?,
+                None => DEFAULT_CUE_LIST,
+            };
+            outcome.cues.extend(current_cue(state, cue_list)?);
+        }
+        CliCommand::RecallCue { cue } | CliCommand::CueTime { cue, .. } => {
+            outcome.cues.push(CueRef { cue_list: DEFAULT_CUE_LIST, cue: *cue });
+        }
+        CliCommand::RecordCue { .. } => {
+            outcome.cues.extend(recorded_cue.map(|cue| CueRef { cue_list: DEFAULT_CUE_LIST, cue }));
+        }
+        _ => {}
+    }
+    Ok(outcome)
+}
+
+/// Record (or update, which requires the cue to exist) a cue in the default list
+fn record_cue(state: &AppState, number: f64, name: &Option<String>, update: bool) -> Result<String, String> {
+    if update {
+        let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+        let exists = cue_engine.list(DEFAULT_CUE_LIST).ok().and_then(|l| l.find(number)).is_some();
+        if !exists {
+            return Err(format!("Cue {} not found", number));
+        }
+    }
+    let timing = session(state)?.fade_time.map(|fade| CueTiming { fade, ..CueTiming::default() });
+    actions::record_cue(state, DEFAULT_CUE_LIST, number, name.clone(), timing, RecordMode::Tracking, false)
+}
+
+fn execute_command(state: &AppState, command: &CliCommand) -> Result<String, String> {
+    match command {
+        CliCommand::Clear => {
+            state.programmer.lock().map_err(|e| e.to_string())?.clear();
+            state.overrides.lock().map_err(|e| e.to_string())?.highlight = false;
+            Ok("Programmer cleared".to_string())
+        }
+        CliCommand::Blackout => actions::blackout(state),
+        CliCommand::Locate => locate(state),
+        CliCommand::Undo => history::undo(state),
+        CliCommand::Redo => history::redo(state),
+        CliCommand::Help => Ok(HELP.to_string()),
+        CliCommand::Highlight { state: wanted } => {
+            let mut overrides = state.overrides.lock().map_err(|e| e.to_string())?;
+            overrides.highlight = wanted.unwrap_or(!overrides.highlight);
+            Ok(format!("Highlight {}", if overrides.highlight { "ON" } else { "OFF" }))
+        }
+        CliCommand::Park => park(state),
+        CliCommand::Unpark => unpark(state),
+        CliCommand::FeatureSet { feature_set } => {
+            state.cli.lock().map_err(|e| e.to_string())?.feature_set = Some(*feature_set);
+            Ok(format!("Feature set {:?}", feature_set))
+        }
+        CliCommand::SelectFixtures { fixtures } => {
+            let count = select(state, fixtures)?;
+            Ok(format!("Selected {} fixtures", count))
+        }
+        CliCommand::SetValue { fixtures, attribute, value, fan_to } => {
+            if !fixtures.is_empty() {
+                select(state, fixtures)?;
+            }
+            set_value(state, attribute.as_deref(), *value, *fan_to)
+        }
+        CliCommand::FadeTime { seconds } => {
+            state.cli.lock().map_err(|e| e.to_string())?.fade_time = Some(*seconds);
+            Ok(format!("Fade time {}s", seconds))
+        }
+        CliCommand::CueTime { cue, seconds } => {
+            let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+            let list = cue_engine.list(DEFAULT_CUE_LIST)?;
+            let index = list.find(*cue).ok_or_else(|| format!("Cue {} not found", cue))?;
+            let timing = CueTiming { fade: *seconds, ..list.cues[index].timing.clone() };
+            cue_engine.set_timing(DEFAULT_CUE_LIST, *cue, timing)?;
+            Ok(format!("Cue {} fade {}s", cue, seconds))
+        }
+        CliCommand::ExecutorTime { executor, seconds } => {
+            let cue_list = executor_list(state, *executor)?;
+            state.cue_engine.lock().map_err(|e| e.to_string())?.set_executor_time(cue_list, Some(*seconds))?;
+            Ok(format!("Executor {} fade {}s", executor, seconds))
+        }
+        CliCommand::Fan { direction, axis } => {
+            let mut session = state.cli.lock().map_err(|e| e.to_string())?;
+            session.fan_direction = *direction;
+            session.fan_axis = *axis;
+            Ok(format!("Fan {:?} on {:?} axis", direction, axis).to_lowercase())
+        }
+        CliCommand::Encoder { encoder, value } => self::encoder(state, *encoder, *value),
+        CliCommand::Go { executor, cue } => {
+            let cue_list = match executor {
+                Some(executor) => executor_list(state, *executor)?,
+                None => DEFAULT_CUE_LIST,
+            };
+            match cue {
+                Some(cue) => actions::goto(state, cue_list, *cue),
+                None => actions::go(state, cue_list),
+            }
+        }
+        CliCommand::RecallCue { cue } => actions::goto(state, DEFAULT_CUE_LIST, *cue),
+        CliCommand::RecallPreset { feature_set, number, group } => {
+            let id = preset_id(state, *feature_set, *number)?;
+            let fixture_ids = match group {
+                Some(group) => {
+                    let selection = state.selection.lock().map_err(|e| e.to_string())?;
+                    Some(resolve_group(selection.groups(), *group)?.fixture_ids)
+                }
+                None => None,
+            };
+            actions::apply_preset(state, id, fixture_ids)
+        }
+        CliCommand::SelectGroup { group } => {
+            let group_id = resolve_group(state.selection.lock().map_err(|e| e.to_string())?.groups(), *group)?.id;
+            let status = selection::run_command(&state.fixtures, &state.selection, SelectionCommand::SelectGroup { group_id })?;
+            Ok(format!("Selected group {} ({} fixtures)", group, status.fixture_ids.len()))
+        }
+        CliCommand::RecordCue { cue, name, update } => {
+            let number = match cue {
+                Some(cue) => *cue,
+                None => next_cue_number(state)?,
+            };
+            record_cue(state, number, name, *update)
+        }
+        CliCommand::RecordPreset { feature_set, number, name, update } => {
+            let id = preset_id(state, *feature_set, *number)?;
+            actions::record_preset(state, id, name.clone(), false, *update)
+        }
+        CliCommand::RecordGroup { group, name } => {
+            let mut selection = state.selection.lock().map_err(|e| e.to_string())?;
+            let fixture_ids = selection.selected().to_vec();
+            if fixture_ids.is_empty() {
+                return Err("Group needs at least one fixture".to_string());
+            }
+            let count = fixture_ids.len();
+            selection.store_group(Group {
+                id: group.to_string(),
+                name: name.clone().unwrap_or_else(|| format!("Group {}", group)),
+                fixture_ids,
+                color: None,
+            });
+            Ok(format!("Recorded group {} with {} fixtures", group, count))
+        }
+        CliCommand::Desktop { command } => Err(format!("'{}' is only available in the desktop CLI", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_selection_and_values() {
+        assert_eq!(
+            parse("fixture 1 thru 4 + 7 at full").unwrap(),
+            CliCommand::SetValue {
+                fixtures: vec![FixtureRange { start: 1, end: 4 }, FixtureRange { start: 7, end: 7 }],
+                attribute: None,
+                value: 255,
+                fan_to: None,
+            }
+        );
+        assert_eq!(
+            parse("1+5+10").unwrap(),
+            CliCommand::SelectFixtures {
+                fixtures: vec![
+                    FixtureRange { start: 1, end: 1 },
+                    FixtureRange { start: 5, end: 5 },
+                    FixtureRange { start: 10, end: 10 },
+                ],
+            }
+        );
+        assert_eq!(
+            parse("Pan at 0 THROUGH 255").unwrap(),
+            CliCommand::SetValue { fixtures: Vec::new(), attribute: Some("Pan".to_string()), value: 0, fan_to: Some(255) }
+        );
+        assert_eq!(
+            parse("red 128").unwrap(),
+            CliCommand::SetValue { fixtures: Vec::new(), attribute: Some("red".to_string()), value: 128, fan_to: None }
+        );
+    }
+
+    #[test]
+    fn test_presets_records_and_playback() {
+        assert_eq!(
+            parse("3.5").unwrap(),
+            CliCommand::RecallPreset { feature_set: Some(FeatureSet::Color), number: 5, group: None }
+        );
+        assert_eq!(
+            parse("group 1 preset 2.3").unwrap(),
+            CliCommand::RecallPreset { feature_set: Some(FeatureSet::Position), number: 3, group: Some(1) }
+        );
+        assert_eq!(
+            parse("record color 5 \"Deep Red\"").unwrap(),
+            CliCommand::RecordPreset {
+                feature_set: Some(FeatureSet::Color),
+                number: 5,
+                name: Some("Deep Red".to_string()),
+                update: false,
+            }
+        );
+        assert_eq!(parse("record").unwrap(), CliCommand::RecordCue { cue: None, name: None, update: false });
+        assert_eq!(parse("update cue 2.5").unwrap(), CliCommand::RecordCue { cue: Some(2.5), name: None, update: true });
+        assert_eq!(parse("go exec 2.5").unwrap(), CliCommand::Go { executor: Some(2), cue: Some(5.0) });
+        assert_eq!(parse("time 2.5").unwrap(), CliCommand::FadeTime { seconds: 2.5 });
+        assert_eq!(parse("time 3 exec 2").unwrap(), CliCommand::ExecutorTime { executor: 2, seconds: 3.0 });
+        assert!(matches!(parse("4/1 10").unwrap(), CliCommand::Desktop { .. }));
+        assert!(matches!(parse("1 thru 10 if odd").unwrap(), CliCommand::Desktop { .. }));
+        assert_eq!(
+            parse("record cue 5 \"what if\"").unwrap(),
+            CliCommand::RecordCue { cue: Some(5.0), name: Some("what if".to_string()), update: false }
+        );
+        assert_eq!(parse("bo").unwrap().domains(), &[Domain::Programmer]);
+    }
+
+    #[test]
+    fn test_command_tree_is_tagged() {
+        let tree = serde_json::to_value(parse("go exec 3").unwrap()).unwrap();
+        assert_eq!(tree["type"], "go");
+        assert_eq!(tree["executor"], 3);
+        let error = serde_json::to_value(parse("record cue x").unwrap_err()).unwrap();
+        assert_eq!(error["position"], 11);
+    }
+
+    #[test]
+    fn test_errors_point_at_the_token() {
+        let error = parse("fixture 1 at 300").unwrap_err();
+        assert_eq!(error.position, 13);
+        let error = parse("1 thru").unwrap_err();
+        assert_eq!(error.position, 6);
+        assert_eq!(parse("frobnicate").unwrap_err().message, "Unknown command 'frobnicate'");
+    }
+
+    #[test]
+    fn test_huge_ranges_stay_within_the_patch() {
+        let fixtures: HashMap<String, Fixture> = ["1", "2", "40"]
+            .iter()
+            .map(|id| {
+                let fixture: Fixture = serde_json::from_value(serde_json::json!({
+                    "id": id, "name": id, "fixture_type": "Dimmer", "dmx_address": id.parse::<u16>().unwrap(),
+                    "universe": 0, "channel_count": 1,
+                }))
+                .unwrap();
+                (id.to_string(), fixture)
+            })
+            .collect();
+
+        let error = parse_for_patch("1 thru 4294967295 at full", &fixtures).unwrap_err();
+        assert_eq!((error.message.as_str(), error.position), ("Fixture 4294967295 is beyond the patch (highest is 40)", 7));
+        assert!(parse_for_patch("1 thru 40 at full", &fixtures).is_ok());
+
+        let everything = [FixtureRange { start: 1, end: u32::MAX }];
+        assert_eq!(resolve_fixtures(&fixtures, &everything).unwrap(), vec!["1", "2", "40"]);
+        let backwards = [FixtureRange { start: u32::MAX, end: 39 }];
+        assert_eq!(resolve_fixtures(&fixtures, &backwards).unwrap(), vec!["40"]);
+    }
+
+    #[test]
+    fn test_numbers_use_one_rule_per_patch() {
+        let patch = |ids: &[&str]| -> HashMap<String, Fixture> {
+            ids.iter()
+                .enumerate()
+                .map(|(index, id)| {
+                    let fixture: Fixture = serde_json::from_value(serde_json::json!({
+                        "id": id, "name": id, "fixture_type": "Dimmer", "dmx_address": index + 1,
+                        "universe": 0, "channel_count": 1,
+                    }))
+                    .unwrap();
+                    (id.to_string(), fixture)
+                })
+                .collect()
+        };
+        let range = |start, end| FixtureRange { start, end };
+
+        // Numbered patch: numbers are ids, never positions
+        let mixed = patch(&["2", "x"]);
+        assert_eq!(resolve_fixtures(&mixed, &[range(1, 2)]).unwrap(), vec!["2"]);
+        assert!(resolve_fixtures(&mixed, &[range(1, 1)]).is_err());
+        assert_eq!(resolve_fixtures(&mixed, &[range(2, 2), range(1, 2)]).unwrap(), vec!["2"]);
+
+        // No numbered fixtures: numbers are positions
+        let named = patch(&["par_a", "par_b", "par_c"]);
+        assert_eq!(resolve_fixtures(&named, &[range(3, 1), range(2, 3)]).unwrap(), vec!["par_c", "par_b", "par_a"]);
+    }
+}
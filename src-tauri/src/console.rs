@@ -0,0 +1,306 @@
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cli::{self, CommandResult, ProgrammerFixture};
+use crate::history;
+use crate::selection::patch_order;
+use crate::web_api::{ApiError, API_PREFIX};
+use crate::web_auth::LoginReply;
+use crate::web_server::SessionStatus;
+use crate::web_settings::{self, WebSettings};
+use crate::{AppState, Fixture};
+
+const PROMPT: &str = "rocontrol> ";
+
+/// Where commands run: inside this process (headless) or in a running instance over its web API
+pub enum Backend {
+    InProcess(AppState),
+    Remote { address: String, token: Option<String> },
+}
+
+impl Backend {
+    /// Accepts "host:port" or "http://host:port/"
+    pub fn remote(url: &str) -> Self {
+        let address = url.trim_start_matches("http://").trim_end_matches('/');
+        Backend::Remote { address: address.to_string(), token: None }
+    }
+
+    /// The instance on this machine, found through its saved web settings
+    pub fn local() -> Result<Self, String> {
+        let settings = WebSettings::load(&web_settings::settings_path()).unwrap_or_default();
+        if settings.server.tls {
+            return Err("The web remote here uses HTTPS, which the console doesn't speak. \
+                        Use --headless --console on this machine, or --url for a plain HTTP instance."
+                .to_string());
+        }
+        Ok(Backend::remote(&settings.server.local_address()))
+    }
+
+    /// Log in to a remote instance that requires it
+    fn log_in(&mut self, editor: &mut Editor<ConsoleHelper, DefaultHistory>) -> Result<(), String> {
+        let Backend::Remote { address, token } = self else {
+            return Ok(());
+        };
+        let session: SessionStatus =
+            serde_json::from_str(&request(address, "GET", "/session", None, None)?).map_err(|e| e.to_string())?;
+        if !session.login_required {
+            return Ok(());
+        }
+        let secret = editor.readline("PIN or password: ").map_err(|e| e.to_string())?;
+        let body = serde_json::json!({ "secret": secret.trim() }).to_string();
+        let reply: LoginReply = serde_json::from_str(&request(address, "POST", "/login", Some(&body), None)?)
+            .map_err(|e| e.to_string())?;
+        println!("Logged in as {} ({})", reply.user, reply.role);
+        *token = Some(reply.token);
+        Ok(())
+    }
+
+    fn run(&self, line: &str) -> Result<CommandResult, String> {
+        match self {
+            Backend::InProcess(state) => Ok(cli::run_structured(state, history::LOCAL, line)),
+            Backend::Remote { address, token } => {
+                let body = serde_json::json!({ "command": line }).to_string();
+                let response = request(address, "POST", "/command", Some(&body), token.as_deref())?;
+                serde_json::from_str(&response).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn programmer(&self) -> Result<Vec<ProgrammerFixture>, String> {
+        match self {
+            Backend::InProcess(state) => cli::programmer_summary(state),
+            Backend::Remote { address, token } => {
+                let response = request(address, "GET", "/programmer", None, token.as_deref())?;
+                serde_json::from_str(&response).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn fixtures(&self) -> Result<Vec<Fixture>, String> {
+        match self {
+            Backend::InProcess(state) => {
+                let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+                Ok(patch_order(&fixtures).iter().filter_map(|id| fixtures.get(id).cloned()).collect())
+            }
+            Backend::Remote { address, token } => {
+                let response = request(address, "GET", "/fixtures", None, token.as_deref())?;
+                serde_json::from_str(&response).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Minimal HTTP/1.1 request to the local web API; `path` is below the API prefix. Returns
+/// the response body of a 2xx reply.
+fn request(address: &str, method: &str, path: &str, body: Option<&str>, token: Option<&str>) -> Result<String, String> {
+    let mut stream =
+        TcpStream::connect(address).map_err(|e| format!("Can't reach RoControl at {}: {}", address, e))?;
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let body = body.unwrap_or("");
+    let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+    let head = format!(
+        "{} {}{} HTTP/1.1\r\nHost: {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method,
+        API_PREFIX,
+        path,
+        address,
+        authorization,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(body.as_bytes())).map_err(|e| e.to_string())?;
+
+    // Bytes until the body is complete: chunks can end inside a multibyte character
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).map_err(|e| e.to_string())?;
+    let split = find(&response, b"\r\n\r\n").ok_or("Malformed response from RoControl")?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    let body = if head.to_lowercase().contains("transfer-encoding: chunked") {
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+    let body = String::from_utf8(body).map_err(|_| "Response from RoControl isn't valid UTF-8")?;
+    if !status.starts_with('2') {
+        let message = match serde_json::from_str::<ApiError>(&body) {
+            Ok(error) => error.message,
+            Err(_) => body.trim().to_string(),
+        };
+        return Err(format!("HTTP {}: {}", status, message));
+    }
+    Ok(body)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = find(body, b"\r\n").ok_or("Malformed chunked response")?;
+        let size = std::str::from_utf8(&body[..line_end]).map_err(|_| "Malformed chunk size")?;
+        let size = usize::from_str_radix(size.trim(), 16).map_err(|_| "Malformed chunk size")?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let rest = &body[line_end + 2..];
+        decoded.extend_from_slice(rest.get(..size).ok_or("Truncated chunk")?);
+        body = rest.get(size..).unwrap_or_default();
+        body = body.strip_prefix(b"\r\n").unwrap_or(body);
+    }
+}
+
+/// Completes CLI keywords and fixture names
+struct ConsoleHelper {
+    words: Vec<String>,
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(|c: char| c.is_whitespace() || c == '+').map(|i| i + 1).unwrap_or(0);
+        let word = line[start..pos].to_lowercase();
+        if word.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+        let candidates = self.words.iter().filter(|w| w.to_lowercase().starts_with(&word)).cloned().collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+fn completion_words(backend: &Backend) -> Vec<String> {
+    let mut words: Vec<String> = cli::KEYWORDS.iter().map(|w| w.to_string()).collect();
+    for fixture in backend.fixtures().unwrap_or_default() {
+        // Names with spaces can't be typed as one word
+        if !fixture.name.contains(char::is_whitespace) {
+            words.push(fixture.name);
+        }
+        words.push(fixture.id);
+    }
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// One line per fixture: "  1 Spot 1: Dimmer 255, Pan 128"
+pub fn format_programmer(programmer: &[ProgrammerFixture]) -> String {
+    if programmer.is_empty() {
+        return "Programmer empty".to_string();
+    }
+    programmer
+        .iter()
+        .map(|fixture| {
+            let values: Vec<String> = fixture.values.iter().map(|(name, value)| format!("{} {}", name, value)).collect();
+            format!("  {} {}: {}", fixture.fixture_id, fixture.name, values.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn history_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("RoControl")
+        .join("console_history.txt")
+}
+
+/// Read-eval-print loop over the CLI grammar. Returns when the user types exit or presses Ctrl+D.
+pub fn run(mut backend: Backend) -> Result<(), String> {
+    let mut editor: Editor<ConsoleHelper, DefaultHistory> = Editor::new().map_err(|e| e.to_string())?;
+    backend.log_in(&mut editor)?;
+    editor.set_helper(Some(ConsoleHelper { words: completion_words(&backend) }));
+    let history = history_path();
+    let _ = editor.load_history(&history);
+
+    match &backend {
+        Backend::InProcess(_) => println!("RoControl console - type help for commands, exit to quit"),
+        Backend::Remote { address, .. } => println!("RoControl console on {} - type help for commands, exit to quit", address),
+    }
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            // Ctrl+C clears the line like a shell
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if matches!(line, "exit" | "quit") {
+            break;
+        }
+
+        match backend.run(line) {
+            Ok(result) if result.success => println!("{}", result.message),
+            Ok(result) => {
+                // Point at the token that didn't parse; the line was echoed after the prompt
+                if let Some(error) = &result.error {
+                    let column = PROMPT.len() + indent + line[..error.position.min(line.len())].chars().count();
+                    println!("{}^", " ".repeat(column));
+                }
+                println!("Error: {}", result.message);
+            }
+            Err(message) => println!("Error: {}", message),
+        }
+        match backend.programmer() {
+            Ok(programmer) => println!("{}", format_programmer(&programmer)),
+            Err(e) => println!("Programmer unavailable: {}", e),
+        }
+        if let Some(helper) = editor.helper_mut() {
+            helper.words = completion_words(&backend);
+        }
+    }
+
+    if let Some(parent) = history.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = editor.save_history(&history);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunked_body_and_feedback() {
+        assert_eq!(decode_chunked(b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n").unwrap(), b"hello world");
+        // "é" is two bytes, split across the chunks
+        let split = b"4\r\nCaf\xc3\r\n3\r\n\xa9 1\r\n0\r\n\r\n";
+        assert_eq!(String::from_utf8(decode_chunked(split).unwrap()).unwrap(), "Café 1");
+        let programmer = vec![ProgrammerFixture {
+            fixture_id: "1".to_string(),
+            name: "Spot".to_string(),
+            values: vec![("Dimmer".to_string(), 255), ("Pan".to_string(), 128)],
+        }];
+        assert_eq!(format_programmer(&programmer), "  1 Spot: Dimmer 255, Pan 128");
+        assert!(matches!(Backend::remote("http://10.0.0.5:8080/"), Backend::Remote { address, .. } if address == "10.0.0.5:8080"));
+    }
+}
@@ -0,0 +1,766 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
+
+use crate::attributes::FeatureSet;
+use crate::presets::PresetId;
+
+/// Default crossfade time in seconds (matches `preferences.default_fade_time` in show files)
+pub const DEFAULT_FADE_TIME: f64 = 2.0;
+
+fn default_fade() -> f64 {
+    DEFAULT_FADE_TIME
+}
+
+/// Fade/delay override for a single feature set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttributeTiming {
+    #[serde(default)]
+    pub fade: Option<f64>,
+    #[serde(default)]
+    pub delay: Option<f64>,
+}
+
+/// What happens after a cue has been triggered
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FollowMode {
+    /// Wait for the next GO
+    #[default]
+    Manual,
+    /// Fire the next cue as soon as every fade in this cue has completed
+    Follow,
+    /// Fire the next cue a fixed time after this cue was triggered
+    Wait { seconds: f64 },
+}
+
+/// Timing for a cue. Intensity up/down and per-feature-set values override the base fade/delay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CueTiming {
+    #[serde(default = "default_fade")]
+    pub fade: f64,
+    #[serde(default)]
+    pub delay: f64,
+    #[serde(default)]
+    pub intensity_up: Option<f64>,
+    #[serde(default)]
+    pub intensity_down: Option<f64>,
+    #[serde(default)]
+    pub attributes: HashMap<FeatureSet, AttributeTiming>,
+    #[serde(default)]
+    pub follow: FollowMode,
+}
+
+impl Default for CueTiming {
+    fn default() -> Self {
+        CueTiming {
+            fade: DEFAULT_FADE_TIME,
+            delay: 0.0,
+            intensity_up: None,
+            intensity_down: None,
+            attributes: HashMap::new(),
+            follow: FollowMode::Manual,
+        }
+    }
+}
+
+impl CueTiming {
+    /// Resolve (delay, fade) for a channel moving from `from` to `to`
+    pub fn resolve(&self, feature_set: FeatureSet, from: f32, to: f32) -> (f64, f64) {
+        let group = self.attributes.get(&feature_set);
+        let delay = group.and_then(|g| g.delay).unwrap_or(self.delay);
+        let mut fade = group.and_then(|g| g.fade).unwrap_or(self.fade);
+
+        if feature_set == FeatureSet::Intensity {
+            let directional = if to >= from { self.intensity_up } else { self.intensity_down };
+            if let Some(t) = directional {
+                fade = t;
+            }
+        }
+
+        (delay.max(0.0), fade.max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cue {
+    pub number: f64,
+    #[serde(default)]
+    pub name: String,
+    /// Values that change in this cue (fixture_id:channel -> value, same keys as
+    /// the programmer). Everything else tracks in from earlier cues.
+    #[serde(default)]
+    pub values: HashMap<String, u8>,
+    #[serde(default)]
+    pub timing: CueTiming,
+    /// Block cues re-state every value, so nothing tracks into them
+    #[serde(default)]
+    pub block: bool,
+    /// Effects that run while this cue is the active cue of its list
+    #[serde(default)]
+    pub effects: Vec<u32>,
+    /// Values that came from a preset (key -> preset). Editing the preset rewrites them.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub presets: HashMap<String, PresetId>,
+}
+
+/// How a record merges the programmer into a cue list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordMode {
+    /// Store changes in this cue and let them track forward
+    #[default]
+    Tracking,
+    /// Store changes in this cue only; the next cue keeps its previous look
+    CueOnly,
+}
+
+/// Where a channel's value in a cue comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSource {
+    /// Hard value changed in this cue
+    Move,
+    /// Unchanged value carried forward from an earlier cue
+    Track,
+    /// Hard value re-stated by a block cue
+    Block,
+    /// Channel not used by the cue list yet
+    None,
+}
+
+/// One row of a channel's tracking sheet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelTrackEntry {
+    pub cue_number: f64,
+    pub value: Option<u8>,
+    pub source: TrackSource,
+    pub last_changed: Option<f64>,
+}
+
+/// A single channel crossfading between two values
+#[derive(Debug, Clone)]
+struct ChannelFade {
+    from: f32,
+    to: f32,
+    delay: f64,
+    fade: f64,
+    feature_set: FeatureSet,
+}
+
+impl ChannelFade {
+    fn value_at(&self, elapsed: f64) -> f32 {
+        let t = elapsed - self.delay;
+        if self.fade <= 0.0 {
+            return if t >= 0.0 { self.to } else { self.from };
+        }
+        if t <= 0.0 {
+            return self.from;
+        }
+        if t >= self.fade {
+            return self.to;
+        }
+        let progress = (t / self.fade) as f32;
+        self.from + (self.to - self.from) * progress
+    }
+
+    fn end(&self) -> f64 {
+        self.delay + self.fade
+    }
+}
+
+/// Playback state of a cue list: the cue being faded to and when it was triggered
+#[derive(Debug, Clone)]
+struct ActiveCue {
+    cue_index: usize,
+    started: f64,
+    channels: HashMap<String, ChannelFade>,
+    follow_at: Option<f64>,
+}
+
+impl ActiveCue {
+    fn values_at(&self, t: f64) -> HashMap<String, f32> {
+        let elapsed = t - self.started;
+        self.channels
+            .iter()
+            .map(|(key, fade)| (key.clone(), fade.value_at(elapsed)))
+            .collect()
+    }
+
+    fn duration(&self) -> f64 {
+        self.channels.values().map(ChannelFade::end).fold(0.0, f64::max)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CueList {
+    pub number: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub cues: Vec<Cue>,
+    /// Executor time: replaces the fade of every cue played on this list. Delays are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor_time: Option<f64>,
+    #[serde(skip)]
+    active: Option<ActiveCue>,
+}
+
+/// Summary of a cue list for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CueListStatus {
+    pub number: u32,
+    pub name: String,
+    pub cue_count: usize,
+    pub current_cue: Option<f64>,
+    pub fade_progress: Option<f64>,
+}
+
+impl CueList {
+    pub fn new(number: u32, name: String) -> Self {
+        CueList {
+            number,
+            name,
+            cues: Vec::new(),
+            executor_time: None,
+            active: None,
+        }
+    }
+
+    /// Insert or replace a cue, keeping the list ordered by cue number
+    pub fn store(&mut self, cue: Cue) {
+        match self.cues.iter().position(|c| c.number == cue.number) {
+            Some(index) => self.cues[index] = cue,
+            None => {
+                let index = self.cues.iter().position(|c| c.number > cue.number).unwrap_or(self.cues.len());
+                // Keep the playhead on the same cue if it moved
+                if let Some(active) = self.active.as_mut() {
+                    if active.cue_index >= index {
+                        active.cue_index += 1;
+                    }
+                }
+                self.cues.insert(index, cue);
+            }
+        }
+    }
+
+    pub fn find(&self, number: f64) -> Option<usize> {
+        self.cues.iter().position(|c| c.number == number)
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.active.as_ref().map(|a| a.cue_index)
+    }
+
+    pub fn status(&self, t: f64) -> CueListStatus {
+        CueListStatus {
+            number: self.number,
+            name: self.name.clone(),
+            cue_count: self.cues.len(),
+            current_cue: self.active.as_ref().and_then(|a| self.cues.get(a.cue_index)).map(|c| c.number),
+            fade_progress: self.active.as_ref().map(|a| {
+                let duration = a.duration();
+                if duration <= 0.0 {
+                    1.0
+                } else {
+                    ((t - a.started) / duration).clamp(0.0, 1.0)
+                }
+            }),
+        }
+    }
+
+    /// Replace the effects referenced by a cue
+    pub fn set_effects(&mut self, number: f64, effects: Vec<u32>) -> Result<(), String> {
+        let index = self.find(number).ok_or_else(|| format!("Cue {} not found", number))?;
+        self.cues[index].effects = effects;
+        Ok(())
+    }
+
+    /// Reference presets for recorded channels that came from them
+    pub fn link_presets(&mut self, number: f64, refs: &HashMap<String, PresetId>) -> Result<(), String> {
+        let index = self.find(number).ok_or_else(|| format!("Cue {} not found", number))?;
+        let cue = &mut self.cues[index];
+        for (key, id) in refs {
+            if cue.values.contains_key(key) {
+                cue.presets.insert(key.clone(), *id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite values that reference a preset after it changed. Channels the preset no
+    /// longer covers keep their last value as a hard value. Returns how many cues changed.
+    pub fn update_preset_refs(&mut self, id: PresetId, value_for: &dyn Fn(&str) -> Option<u8>) -> usize {
+        let mut changed = 0;
+        for cue in &mut self.cues {
+            let keys: Vec<String> = cue.presets.iter().filter(|(_, p)| **p == id).map(|(k, _)| k.clone()).collect();
+            for key in &keys {
+                match value_for(key) {
+                    Some(value) => {
+                        cue.values.insert(key.clone(), value);
+                    }
+                    None => {
+                        cue.presets.remove(key);
+                    }
+                }
+            }
+            if !keys.is_empty() {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Current output of this list
+    pub fn values_at(&self, t: f64) -> HashMap<String, f32> {
+        self.active.as_ref().map(|a| a.values_at(t)).unwrap_or_default()
+    }
+
+    /// Full look of the cue at `index`: values tracked forward from earlier cues,
+    /// restarting at the most recent block cue
+    pub fn tracked_state(&self, index: usize) -> HashMap<String, u8> {
+        let start = self.cues[..=index].iter().rposition(|c| c.block).unwrap_or(0);
+        let mut state = HashMap::new();
+        for cue in &self.cues[start..=index] {
+            state.extend(cue.values.iter().map(|(k, v)| (k.clone(), *v)));
+        }
+        state
+    }
+
+    /// Record programmer values into a cue. In tracking mode only values that differ
+    /// from the tracked state before this cue are stored; block cues store everything.
+    pub fn record(
+        &mut self,
+        number: f64,
+        name: String,
+        programmer: &HashMap<String, u8>,
+        timing: Option<CueTiming>,
+        mode: RecordMode,
+        block: bool,
+    ) {
+        let existing = self.find(number);
+        let position = existing
+            .unwrap_or_else(|| self.cues.iter().position(|c| c.number > number).unwrap_or(self.cues.len()));
+
+        let previous_state = if position > 0 { self.tracked_state(position - 1) } else { HashMap::new() };
+        let old_state = match existing {
+            Some(index) => self.tracked_state(index),
+            None => previous_state.clone(),
+        };
+
+        let mut values = existing.map(|i| self.cues[i].values.clone()).unwrap_or_default();
+        values.extend(programmer.iter().map(|(k, v)| (k.clone(), *v)));
+        if block {
+            let mut full = old_state.clone();
+            full.extend(values);
+            values = full;
+        } else {
+            values.retain(|key, value| previous_state.get(key) != Some(value));
+        }
+
+        let timing = timing.unwrap_or_else(|| existing.map(|i| self.cues[i].timing.clone()).unwrap_or_default());
+        let effects = existing.map(|i| self.cues[i].effects.clone()).unwrap_or_default();
+        // Re-recorded channels are hard values until linked again
+        let mut presets = existing.map(|i| self.cues[i].presets.clone()).unwrap_or_default();
+        presets.retain(|key, _| !programmer.contains_key(key) && values.contains_key(key));
+        self.store(Cue { number, name, values, timing, block, effects, presets });
+
+        if mode == RecordMode::CueOnly {
+            let index = self.find(number).unwrap_or(position);
+            if let Some(next) = self.cues.get_mut(index + 1) {
+                if !next.block {
+                    for (key, value) in programmer {
+                        if old_state.get(key) != Some(value) && !next.values.contains_key(key) {
+                            next.values.insert(key.clone(), old_state.get(key).copied().unwrap_or(0));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Value of one channel in every cue, with where it last changed
+    pub fn channel_tracking(&self, key: &str) -> Vec<ChannelTrackEntry> {
+        let mut value = None;
+        let mut last_changed = None;
+
+        self.cues
+            .iter()
+            .map(|cue| {
+                let source = match cue.values.get(key) {
+                    Some(v) => {
+                        let moved = value != Some(*v);
+                        value = Some(*v);
+                        if cue.block && !moved {
+                            TrackSource::Block
+                        } else {
+                            last_changed = Some(cue.number);
+                            TrackSource::Move
+                        }
+                    }
+                    None if cue.block => {
+                        value = None;
+                        last_changed = None;
+                        TrackSource::None
+                    }
+                    None if value.is_some() => TrackSource::Track,
+                    None => TrackSource::None,
+                };
+                ChannelTrackEntry {
+                    cue_number: cue.number,
+                    value,
+                    source,
+                    last_changed,
+                }
+            })
+            .collect()
+    }
+
+    /// Start fading to the cue at `index`, beginning exactly at time `t`
+    fn trigger(&mut self, index: usize, t: f64, classify: &dyn Fn(&str) -> FeatureSet) {
+        let current = self.values_at(t);
+        let target = self.tracked_state(index);
+        let timing = &self.cues[index].timing;
+
+        let mut channels = HashMap::new();
+        for (key, to) in &target {
+            let to = *to as f32;
+            let from = current.get(key).copied().unwrap_or(0.0);
+            let feature_set = classify(key);
+            let (delay, fade) = timing.resolve(feature_set, from, to);
+            let fade = self.executor_time.unwrap_or(fade);
+            channels.insert(key.clone(), ChannelFade { from, to, delay, fade, feature_set });
+        }
+        // Channels that aren't part of the new cue's tracked state fade out
+        for (key, from) in current {
+            if from <= 0.0 || channels.contains_key(&key) {
+                continue;
+            }
+            let feature_set = classify(&key);
+            let (delay, fade) = timing.resolve(feature_set, from, 0.0);
+            let fade = self.executor_time.unwrap_or(fade);
+            channels.insert(key, ChannelFade { from, to: 0.0, delay, fade, feature_set });
+        }
+
+        let mut active = ActiveCue {
+            cue_index: index,
+            started: t,
+            channels,
+            follow_at: None,
+        };
+        active.follow_at = match timing.follow {
+            FollowMode::Manual => None,
+            FollowMode::Follow => Some(t + active.duration()),
+            FollowMode::Wait { seconds } => Some(t + seconds.max(0.0)),
+        };
+        self.active = Some(active);
+    }
+}
+
+/// Cue lists and their playback state. Timing is evaluated against a continuous
+/// clock so fades and follows are independent of the output frame rate.
+pub struct CueEngine {
+    epoch: Instant,
+    lists: BTreeMap<u32, CueList>,
+}
+
+impl CueEngine {
+    pub fn new() -> Self {
+        CueEngine {
+            epoch: Instant::now(),
+            lists: BTreeMap::new(),
+        }
+    }
+
+    /// Seconds since the engine was created
+    pub fn now(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64()
+    }
+
+    pub fn lists(&self) -> impl Iterator<Item = &CueList> {
+        self.lists.values()
+    }
+
+    /// Replace all cue lists, e.g. when a show is loaded. Playback stops.
+    pub fn load(&mut self, lists: Vec<CueList>) {
+        self.lists = lists.into_iter().map(|l| (l.number, l)).collect();
+    }
+
+    /// Replace the cue lists with an earlier copy (undo/redo). Lists keep playing from
+    /// the same cue number if it still exists.
+    pub fn restore(&mut self, lists: Vec<CueList>) {
+        let mut previous = std::mem::take(&mut self.lists);
+        self.lists = lists
+            .into_iter()
+            .map(|mut list| {
+                list.active = previous.remove(&list.number).and_then(|old| {
+                    let active = old.active?;
+                    let number = old.cues.get(active.cue_index)?.number;
+                    let cue_index = list.cues.iter().position(|c| c.number == number)?;
+                    Some(ActiveCue { cue_index, ..active })
+                });
+                (list.number, list)
+            })
+            .collect();
+    }
+
+    pub fn list_mut(&mut self, number: u32) -> &mut CueList {
+        self.lists
+            .entry(number)
+            .or_insert_with(|| CueList::new(number, format!("Cue List {}", number)))
+    }
+
+    pub fn list(&self, number: u32) -> Result<&CueList, String> {
+        self.lists.get(&number).ok_or_else(|| format!("Cue list {} not found", number))
+    }
+
+    /// Follow a preset change in every cue list. Returns how many cues changed.
+    pub fn update_preset_refs(&mut self, id: PresetId, value_for: &dyn Fn(&str) -> Option<u8>) -> usize {
+        self.lists.values_mut().map(|l| l.update_preset_refs(id, value_for)).sum()
+    }
+
+    pub fn set_timing(&mut self, list: u32, number: f64, timing: CueTiming) -> Result<(), String> {
+        let cue_list = self.lists.get_mut(&list).ok_or_else(|| format!("Cue list {} not found", list))?;
+        let index = cue_list.find(number).ok_or_else(|| format!("Cue {} not found in list {}", number, list))?;
+        cue_list.cues[index].timing = timing;
+        Ok(())
+    }
+
+    pub fn set_executor_time(&mut self, list: u32, seconds: Option<f64>) -> Result<(), String> {
+        let cue_list = self.lists.get_mut(&list).ok_or_else(|| format!("Cue list {} not found", list))?;
+        cue_list.executor_time = seconds;
+        Ok(())
+    }
+
+    /// Advance a cue list to its next cue. Returns the cue number that was fired.
+    pub fn go(&mut self, list: u32, t: f64, classify: &dyn Fn(&str) -> FeatureSet) -> Result<f64, String> {
+        let cue_list = self.lists.get_mut(&list).ok_or_else(|| format!("Cue list {} not found", list))?;
+        if cue_list.cues.is_empty() {
+            return Err(format!("Cue list {} is empty", list));
+        }
+        let next = match cue_list.current_index() {
+            Some(index) if index + 1 < cue_list.cues.len() => index + 1,
+            Some(_) => return Err(format!("Cue list {} is on its last cue", list)),
+            None => 0,
+        };
+        cue_list.trigger(next, t, classify);
+        Ok(cue_list.cues[next].number)
+    }
+
+    /// Jump to a specific cue using that cue's timing
+    pub fn goto(&mut self, list: u32, number: f64, t: f64, classify: &dyn Fn(&str) -> FeatureSet) -> Result<(), String> {
+        let cue_list = self.lists.get_mut(&list).ok_or_else(|| format!("Cue list {} not found", list))?;
+        let index = cue_list.find(number).ok_or_else(|| format!("Cue {} not found in list {}", number, list))?;
+        cue_list.trigger(index, t, classify);
+        Ok(())
+    }
+
+    /// Stop a cue list and release its output
+    pub fn release(&mut self, list: u32) {
+        if let Some(cue_list) = self.lists.get_mut(&list) {
+            cue_list.active = None;
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        for cue_list in self.lists.values_mut() {
+            cue_list.active = None;
+        }
+    }
+
+    /// Fire any follow/wait cues that became due up to time `t`. Each follow starts
+    /// at its exact scheduled time rather than at the frame that noticed it.
+    pub fn update(&mut self, t: f64, classify: &dyn Fn(&str) -> FeatureSet) {
+        for cue_list in self.lists.values_mut() {
+            while let Some(active) = &cue_list.active {
+                let Some(due) = active.follow_at else { break };
+                if due > t {
+                    break;
+                }
+                let next = active.cue_index + 1;
+                if next >= cue_list.cues.len() {
+                    if let Some(active) = cue_list.active.as_mut() {
+                        active.follow_at = None;
+                    }
+                    break;
+                }
+                cue_list.trigger(next, due, classify);
+            }
+        }
+    }
+
+    /// Effects referenced by the active cue of every running list
+    pub fn active_effects(&self) -> HashSet<u32> {
+        self.lists
+            .values()
+            .filter_map(|list| list.active.as_ref().and_then(|a| list.cues.get(a.cue_index)))
+            .flat_map(|cue| cue.effects.iter().copied())
+            .collect()
+    }
+
+    /// Combined output of all cue lists: intensity is highest-takes-precedence,
+    /// every other attribute comes from the most recently triggered list.
+    pub fn render(&self, t: f64) -> HashMap<String, f32> {
+        let mut output: HashMap<String, f32> = HashMap::new();
+        let mut latest: HashMap<String, f64> = HashMap::new();
+
+        for cue_list in self.lists.values() {
+            let Some(active) = &cue_list.active else { continue };
+            let elapsed = t - active.started;
+            for (key, fade) in &active.channels {
+                let value = fade.value_at(elapsed);
+                if fade.feature_set == FeatureSet::Intensity {
+                    let entry = output.entry(key.clone()).or_insert(0.0);
+                    *entry = entry.max(value);
+                } else if latest.get(key).map(|started| active.started >= *started).unwrap_or(true) {
+                    latest.insert(key.clone(), active.started);
+                    output.insert(key.clone(), value);
+                }
+            }
+        }
+
+        output
+    }
+}
+
+impl Default for CueEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(key: &str) -> FeatureSet {
+        if key.ends_with(":0") {
+            FeatureSet::Intensity
+        } else {
+            FeatureSet::Position
+        }
+    }
+
+    fn cue(number: f64, values: &[(&str, u8)], timing: CueTiming) -> Cue {
+        Cue {
+            number,
+            name: String::new(),
+            values: values.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            timing,
+            block: false,
+            effects: Vec::new(),
+            presets: HashMap::new(),
+        }
+    }
+
+    fn programmer(values: &[(&str, u8)]) -> HashMap<String, u8> {
+        values.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_crossfade_is_linear() {
+        let mut engine = CueEngine::new();
+        engine.list_mut(1).store(cue(1.0, &[("spot1:0", 200)], CueTiming { fade: 2.0, ..CueTiming::default() }));
+        engine.go(1, 10.0, &classify).unwrap();
+
+        assert_eq!(engine.render(10.0)["spot1:0"], 0.0);
+        assert_eq!(engine.render(11.0)["spot1:0"], 100.0);
+        assert_eq!(engine.render(12.5)["spot1:0"], 200.0);
+
+        // Executor time replaces the cue's fade
+        engine.list_mut(1).store(cue(2.0, &[("spot1:0", 0)], CueTiming { fade: 2.0, ..CueTiming::default() }));
+        engine.set_executor_time(1, Some(4.0)).unwrap();
+        engine.go(1, 20.0, &classify).unwrap();
+        assert_eq!(engine.render(22.0)["spot1:0"], 100.0);
+        assert!(engine.set_executor_time(9, Some(1.0)).is_err());
+    }
+
+    #[test]
+    fn test_intensity_up_down_and_attribute_delay() {
+        let mut timing = CueTiming {
+            fade: 4.0,
+            intensity_up: Some(1.0),
+            intensity_down: Some(8.0),
+            ..CueTiming::default()
+        };
+        timing.attributes.insert(FeatureSet::Position, AttributeTiming { fade: Some(2.0), delay: Some(1.0) });
+
+        let mut engine = CueEngine::new();
+        engine.list_mut(1).store(cue(1.0, &[("a:0", 255), ("b:0", 0), ("a:1", 100)], CueTiming { fade: 0.0, ..CueTiming::default() }));
+        engine.list_mut(1).store(cue(2.0, &[("a:0", 0), ("b:0", 255), ("a:1", 200)], timing));
+        engine.go(1, 0.0, &classify).unwrap();
+        engine.go(1, 0.0, &classify).unwrap();
+
+        let out = engine.render(0.5);
+        assert!((out["b:0"] - 127.5).abs() < 0.01); // up in 1s
+        assert!((out["a:0"] - 239.0625).abs() < 0.01); // down in 8s
+        assert_eq!(out["a:1"], 100.0); // position still delayed
+        assert_eq!(engine.render(2.0)["a:1"], 150.0);
+    }
+
+    #[test]
+    fn test_follow_fires_at_exact_time() {
+        let mut engine = CueEngine::new();
+        engine.list_mut(1).store(cue(1.0, &[("a:0", 100)], CueTiming { fade: 1.0, follow: FollowMode::Follow, ..CueTiming::default() }));
+        engine.list_mut(1).store(cue(2.0, &[("a:0", 200)], CueTiming { fade: 1.0, ..CueTiming::default() }));
+        engine.go(1, 0.0, &classify).unwrap();
+
+        // Frame lands 0.25s after the follow was due
+        engine.update(1.25, &classify);
+        assert_eq!(engine.lists().next().unwrap().status(1.25).current_cue, Some(2.0));
+        assert_eq!(engine.render(1.25)["a:0"], 125.0);
+    }
+
+    #[test]
+    fn test_wait_follow() {
+        let mut engine = CueEngine::new();
+        engine.list_mut(1).store(cue(1.0, &[("a:0", 100)], CueTiming { fade: 10.0, follow: FollowMode::Wait { seconds: 3.0 }, ..CueTiming::default() }));
+        engine.list_mut(1).store(cue(2.0, &[("a:0", 0)], CueTiming { fade: 0.0, ..CueTiming::default() }));
+        engine.go(1, 0.0, &classify).unwrap();
+
+        engine.update(2.9, &classify);
+        assert_eq!(engine.lists().next().unwrap().status(2.9).current_cue, Some(1.0));
+        engine.update(3.0, &classify);
+        assert_eq!(engine.render(3.0)["a:0"], 0.0);
+    }
+
+    #[test]
+    fn test_tracking_record_stores_only_changes() {
+        let mut list = CueList::new(1, String::new());
+        list.record(1.0, String::new(), &programmer(&[("a:0", 255), ("a:1", 10)]), None, RecordMode::Tracking, false);
+        list.record(2.0, String::new(), &programmer(&[("a:0", 255), ("a:1", 50)]), None, RecordMode::Tracking, false);
+
+        assert_eq!(list.cues[1].values, programmer(&[("a:1", 50)]));
+        assert_eq!(list.tracked_state(1), programmer(&[("a:0", 255), ("a:1", 50)]));
+    }
+
+    #[test]
+    fn test_cue_only_restores_next_cue() {
+        let mut list = CueList::new(1, String::new());
+        list.record(1.0, String::new(), &programmer(&[("a:0", 100)]), None, RecordMode::Tracking, false);
+        list.record(2.0, String::new(), &programmer(&[("b:0", 100)]), None, RecordMode::Tracking, false);
+        list.record(3.0, String::new(), &programmer(&[("c:0", 100)]), None, RecordMode::Tracking, false);
+
+        list.record(2.0, String::new(), &programmer(&[("a:0", 200)]), None, RecordMode::CueOnly, false);
+
+        assert_eq!(list.tracked_state(1)["a:0"], 200);
+        assert_eq!(list.tracked_state(2)["a:0"], 100);
+    }
+
+    #[test]
+    fn test_block_cue_stops_tracking() {
+        let mut list = CueList::new(1, String::new());
+        list.record(1.0, String::new(), &programmer(&[("a:0", 100)]), None, RecordMode::Tracking, false);
+        list.record(2.0, String::new(), &programmer(&[("b:0", 100)]), None, RecordMode::Tracking, true);
+        assert_eq!(list.cues[1].values, programmer(&[("a:0", 100), ("b:0", 100)]));
+
+        // A change in cue 1 no longer tracks through the block
+        list.record(1.0, String::new(), &programmer(&[("a:0", 50)]), None, RecordMode::Tracking, false);
+        assert_eq!(list.tracked_state(1)["a:0"], 100);
+
+        let sheet = list.channel_tracking("a:0");
+        assert_eq!(sheet[0].source, TrackSource::Move);
+        assert_eq!(sheet[1].source, TrackSource::Move);
+        assert_eq!(sheet[1].last_changed, Some(2.0));
+    }
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f64::consts::TAU;
+
+use crate::attributes::{channel_key, PatchLookup};
+
+/// Waveform generators available to effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectForm {
+    Sine,
+    Square,
+    Ramp,
+    /// Pan/tilt circle (sine on pan, cosine on tilt)
+    Circle,
+    /// One fixture per wing on at a time
+    Chase,
+    /// Hue rotation across the Red/Green/Blue channels
+    Rainbow,
+}
+
+fn default_attribute() -> String {
+    "Dimmer".to_string()
+}
+
+fn default_rate() -> f64 {
+    1.0
+}
+
+fn default_size() -> f32 {
+    255.0
+}
+
+fn default_wings() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Effect {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    pub form: EffectForm,
+    /// Attribute the waveform drives ("Dimmer", "Pan", ...). Ignored by circle and rainbow.
+    #[serde(default = "default_attribute")]
+    pub attribute: String,
+    /// Ordered fixture selection
+    #[serde(default)]
+    pub fixtures: Vec<String>,
+    /// Cycles per second
+    #[serde(default = "default_rate")]
+    pub rate: f64,
+    /// Peak-to-peak size in DMX steps
+    #[serde(default = "default_size")]
+    pub size: f32,
+    /// Centre value. When unset the effect runs around the attribute's current value.
+    #[serde(default)]
+    pub offset: Option<f32>,
+    /// Phase difference in degrees between the first and last fixture of a wing
+    #[serde(default)]
+    pub phase_spread: f64,
+    /// Number of mirrored wings the selection is split into
+    #[serde(default = "default_wings")]
+    pub wings: u32,
+}
+
+impl Effect {
+    /// Phase offset (0-1) for the fixture at `index` in the selection, mirrored per wing
+    fn phase_for(&self, index: usize) -> f64 {
+        let (position, wing_size) = self.wing_position(index);
+        if wing_size <= 1 {
+            return 0.0;
+        }
+        (self.phase_spread / 360.0) * position as f64 / wing_size as f64
+    }
+
+    /// Position of a fixture inside its wing and the wing size
+    fn wing_position(&self, index: usize) -> (usize, usize) {
+        let count = self.fixtures.len().max(1);
+        let wings = (self.wings.max(1) as usize).min(count);
+        let wing_size = count.div_ceil(wings);
+        let wing = index / wing_size;
+        let mut position = index % wing_size;
+        if wing % 2 == 1 {
+            position = wing_size - 1 - position;
+        }
+        (position, wing_size)
+    }
+
+    /// Waveform value in the range -1..1 at cycle position `p` (0-1)
+    fn wave(&self, p: f64, index: usize) -> f64 {
+        match self.form {
+            EffectForm::Sine | EffectForm::Circle => (TAU * p).sin(),
+            EffectForm::Square => {
+                if p < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            EffectForm::Ramp => 2.0 * p - 1.0,
+            EffectForm::Chase => {
+                let (_, wing_size) = self.wing_position(index);
+                if p < 1.0 / wing_size as f64 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            EffectForm::Rainbow => 0.0,
+        }
+    }
+
+    /// Apply this effect to the frame's values at time `t`
+    fn apply(&self, t: f64, values: &mut HashMap<String, f32>, lookup: &PatchLookup) {
+        for (index, fixture_id) in self.fixtures.iter().enumerate() {
+            let mut p = (t * self.rate + self.phase_for(index)).fract();
+            if p < 0.0 {
+                p += 1.0;
+            }
+
+            match self.form {
+                EffectForm::Circle => {
+                    let radius = self.size as f64 / 2.0;
+                    let pan = radius * (TAU * p).sin();
+                    let tilt = radius * (TAU * p).cos();
+                    self.modulate(fixture_id, "Pan", pan, values, lookup);
+                    self.modulate(fixture_id, "Tilt", tilt, values, lookup);
+                }
+                EffectForm::Rainbow => {
+                    let (r, g, b) = hue_to_rgb(p);
+                    let scale = self.size.clamp(0.0, 255.0);
+                    for (attribute, level) in [("Red", r), ("Green", g), ("Blue", b)] {
+                        if let Some(offset) = lookup.find_channel(fixture_id, attribute) {
+                            values.insert(channel_key(fixture_id, offset), level * scale);
+                        }
+                    }
+                }
+                _ => {
+                    let amount = self.wave(p, index) * self.size as f64 / 2.0;
+                    self.modulate(fixture_id, &self.attribute, amount, values, lookup);
+                }
+            }
+        }
+    }
+
+    fn modulate(
+        &self,
+        fixture_id: &str,
+        attribute: &str,
+        amount: f64,
+        values: &mut HashMap<String, f32>,
+        lookup: &PatchLookup,
+    ) {
+        let Some(offset) = lookup.find_channel(fixture_id, attribute) else {
+            return;
+        };
+        let key = channel_key(fixture_id, offset);
+        let centre = match self.offset {
+            Some(centre) => centre,
+            None => values.get(&key).copied().unwrap_or(self.size / 2.0),
+        };
+        values.insert(key, (centre + amount as f32).clamp(0.0, 255.0));
+    }
+}
+
+/// Fully saturated RGB (0-1) for a hue position (0-1)
+fn hue_to_rgb(hue: f64) -> (f32, f32, f32) {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let x = (1.0 - ((h % 2.0) - 1.0).abs()) as f32;
+    match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    }
+}
+
+/// Stored effects and which of them are running from the programmer
+pub struct EffectsEngine {
+    effects: BTreeMap<u32, Effect>,
+    running: HashSet<u32>,
+}
+
+impl EffectsEngine {
+    pub fn new() -> Self {
+        EffectsEngine {
+            effects: BTreeMap::new(),
+            running: HashSet::new(),
+        }
+    }
+
+    pub fn effects(&self) -> impl Iterator<Item = &Effect> {
+        self.effects.values()
+    }
+
+    /// Replace all effects, e.g. when a show is loaded
+    pub fn load(&mut self, effects: Vec<Effect>) {
+        self.running.clear();
+        self.effects = effects.into_iter().map(|e| (e.id, e)).collect();
+    }
+
+    /// Replace the effect definitions (undo/redo), keeping running effects that still exist
+    pub fn restore(&mut self, effects: Vec<Effect>) {
+        self.effects = effects.into_iter().map(|e| (e.id, e)).collect();
+        let effects = &self.effects;
+        self.running.retain(|id| effects.contains_key(id));
+    }
+
+    pub fn store(&mut self, effect: Effect) {
+        self.effects.insert(effect.id, effect);
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.running.remove(&id);
+        self.effects.remove(&id).is_some()
+    }
+
+    pub fn start(&mut self, id: u32) -> Result<(), String> {
+        if !self.effects.contains_key(&id) {
+            return Err(format!("Effect {} not found", id));
+        }
+        self.running.insert(id);
+        Ok(())
+    }
+
+    pub fn stop(&mut self, id: u32) {
+        self.running.remove(&id);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.running.clear();
+    }
+
+    /// Effects started from the programmer (these get stored when recording a cue)
+    pub fn running(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.running.iter().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Apply programmer effects plus any effects referenced by active cues
+    pub fn render(&self, t: f64, cue_effects: &HashSet<u32>, values: &mut HashMap<String, f32>, lookup: &PatchLookup) {
+        for (id, effect) in &self.effects {
+            if self.running.contains(id) || cue_effects.contains(id) {
+                effect.apply(t, values, lookup);
+            }
+        }
+    }
+}
+
+impl Default for EffectsEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(form: EffectForm, fixtures: usize, phase_spread: f64, wings: u32) -> Effect {
+        Effect {
+            id: 1,
+            name: String::new(),
+            form,
+            attribute: default_attribute(),
+            fixtures: (0..fixtures).map(|i| format!("f{}", i)).collect(),
+            rate: 1.0,
+            size: 255.0,
+            offset: None,
+            phase_spread,
+            wings,
+        }
+    }
+
+    #[test]
+    fn test_phase_spread_and_wings() {
+        let fx = effect(EffectForm::Sine, 4, 360.0, 1);
+        assert_eq!(fx.phase_for(0), 0.0);
+        assert_eq!(fx.phase_for(2), 0.5);
+
+        // Two wings mirror each other
+        let fx = effect(EffectForm::Sine, 4, 360.0, 2);
+        assert_eq!(fx.phase_for(0), fx.phase_for(3));
+        assert_eq!(fx.phase_for(1), fx.phase_for(2));
+    }
+
+    #[test]
+    fn test_waveforms() {
+        let fx = effect(EffectForm::Square, 1, 0.0, 1);
+        assert_eq!(fx.wave(0.25, 0), 1.0);
+        assert_eq!(fx.wave(0.75, 0), -1.0);
+
+        let fx = effect(EffectForm::Ramp, 1, 0.0, 1);
+        assert_eq!(fx.wave(0.5, 0), 0.0);
+
+        let fx = effect(EffectForm::Chase, 4, 360.0, 1);
+        assert_eq!(fx.wave(0.1, 0), 1.0);
+        assert_eq!(fx.wave(0.3, 0), -1.0);
+    }
+
+    #[test]
+    fn test_hue_to_rgb() {
+        assert_eq!(hue_to_rgb(0.0), (1.0, 0.0, 0.0));
+        assert_eq!(hue_to_rgb(0.5), (0.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::attributes::{channel_key, PatchLookup};
+
+/// How values are distributed across an ordered selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FanMode {
+    /// First fixture gets `start`, last gets `end`
+    #[default]
+    Linear,
+    /// Centre fixture(s) get `start`, the outermost fixtures get `end`
+    CenterOut,
+    /// Mirrored around the centre, which gets `start`: the last fixture gets `end` and
+    /// the first the same distance the other way (pan -x..0..+x)
+    Symmetric,
+    /// Selection is split into blocks of `group_size`; each block shares one value
+    Grouped,
+}
+
+/// A fan operation as sent by the CLI, web remote or Stream Deck
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanRequest {
+    pub fixture_ids: Vec<String>,
+    pub attribute: String,
+    pub start: f32,
+    pub end: f32,
+    #[serde(default)]
+    pub mode: FanMode,
+    #[serde(default)]
+    pub group_size: Option<usize>,
+}
+
+/// Result of applying a fan to the programmer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanResult {
+    pub values: Vec<(String, u8)>,
+    /// Fixtures in the selection that don't have the attribute
+    pub skipped: Vec<String>,
+}
+
+/// Distribute `start`..`end` over `count` positions
+pub fn fan_values(count: usize, start: f32, end: f32, mode: FanMode, group_size: usize) -> Vec<f32> {
+    if count == 0 {
+        return Vec::new();
+    }
+    if count == 1 {
+        return vec![start];
+    }
+
+    let lerp = |fraction: f32| start + (end - start) * fraction;
+    let last = (count - 1) as f32;
+    let centre = last / 2.0;
+
+    (0..count)
+        .map(|i| {
+            let i = i as f32;
+            match mode {
+                FanMode::Linear => lerp(i / last),
+                FanMode::CenterOut => lerp((i - centre).abs() / centre),
+                FanMode::Symmetric => lerp((i - centre) / centre),
+                FanMode::Grouped => {
+                    let group_size = group_size.max(1);
+                    let groups = count.div_ceil(group_size);
+                    if groups <= 1 {
+                        start
+                    } else {
+                        lerp((i as usize / group_size) as f32 / (groups - 1) as f32)
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Write a fan into the programmer. Fixtures without the attribute are skipped and
+/// don't take up a position in the distribution.
+pub fn apply_fan(
+    request: &FanRequest,
+    lookup: &PatchLookup,
+    programmer: &mut HashMap<String, u8>,
+) -> Result<FanResult, String> {
+    if request.fixture_ids.is_empty() {
+        return Err("No fixtures selected".to_string());
+    }
+
+    let mut targets = Vec::new();
+    let mut skipped = Vec::new();
+    for fixture_id in &request.fixture_ids {
+        match lookup.find_channel(fixture_id, &request.attribute) {
+            Some(offset) => targets.push(channel_key(fixture_id, offset)),
+            None => skipped.push(fixture_id.clone()),
+        }
+    }
+    if targets.is_empty() {
+        return Err(format!("No selected fixture has attribute '{}'", request.attribute));
+    }
+
+    let distribution = fan_values(
+        targets.len(),
+        request.start,
+        request.end,
+        request.mode,
+        request.group_size.unwrap_or(1),
+    );
+
+    let values: Vec<(String, u8)> = targets
+        .into_iter()
+        .zip(distribution)
+        .map(|(key, value)| (key, value.round().clamp(0.0, 255.0) as u8))
+        .collect();
+
+    for (key, value) in &values {
+        programmer.insert(key.clone(), *value);
+    }
+
+    Ok(FanResult { values, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_fan() {
+        assert_eq!(fan_values(5, 100.0, 200.0, FanMode::Linear, 1), vec![100.0, 125.0, 150.0, 175.0, 200.0]);
+    }
+
+    #[test]
+    fn test_center_out_and_symmetric() {
+        assert_eq!(fan_values(5, 200.0, 100.0, FanMode::CenterOut, 1), vec![100.0, 150.0, 200.0, 150.0, 100.0]);
+        assert_eq!(fan_values(5, 128.0, 228.0, FanMode::Symmetric, 1), vec![28.0, 78.0, 128.0, 178.0, 228.0]);
+        assert_eq!(fan_values(4, 0.0, 30.0, FanMode::Symmetric, 1), vec![-30.0, -10.0, 10.0, 30.0]);
+        // Even counts share the centre value
+        let even = fan_values(4, 0.0, 90.0, FanMode::CenterOut, 1);
+        assert_eq!(even[1], even[2]);
+    }
+
+    #[test]
+    fn test_grouped_fan() {
+        assert_eq!(fan_values(6, 0.0, 200.0, FanMode::Grouped, 2), vec![0.0, 0.0, 100.0, 100.0, 200.0, 200.0]);
+    }
+}
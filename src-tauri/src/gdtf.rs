@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+use zip::ZipArchive;
+
+use crate::{FixtureChannel, GdtfFixtureType};
+
+/// DMX mode of a GDTF fixture type with its channel layout
+#[derive(Debug, Clone)]
+pub struct GdtfMode {
+    pub name: String,
+    pub channels: Vec<FixtureChannel>,
+}
+
+/// The parts of a GDTF description.xml the console uses
+#[derive(Debug, Clone)]
+pub struct GdtfDescription {
+    pub name: String,
+    pub manufacturer: String,
+    pub modes: Vec<GdtfMode>,
+}
+
+impl GdtfDescription {
+    /// Library entry name for a mode. Every mode gets its own entry since channel layouts differ.
+    pub fn type_name(&self, mode: &str) -> String {
+        format!("{} ({})", self.name, mode)
+    }
+
+    /// Library entry for `mode`, or the first mode when it isn't given or not found
+    pub fn fixture_type(&self, mode: Option<&str>) -> Option<GdtfFixtureType> {
+        let selected = mode
+            .and_then(|m| self.modes.iter().find(|x| x.name.eq_ignore_ascii_case(m)))
+            .or(self.modes.first())?;
+        Some(GdtfFixtureType {
+            name: self.type_name(&selected.name),
+            manufacturer: self.manufacturer.clone(),
+            channels: selected.channels.clone(),
+            modes: vec![selected.name.clone()],
+        })
+    }
+
+    /// Library entries for every mode
+    pub fn fixture_types(&self) -> Vec<GdtfFixtureType> {
+        self.modes
+            .iter()
+            .filter_map(|m| self.fixture_type(Some(&m.name)))
+            .collect()
+    }
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+/// Default DMX value of the coarse byte from "value/bytes" (e.g. "32768/2")
+fn coarse_default(default: Option<&str>) -> u8 {
+    let Some((value, bytes)) = default.and_then(|d| d.split_once('/')) else {
+        return 0;
+    };
+    match (value.parse::<u64>(), bytes.parse::<u32>()) {
+        (Ok(value), Ok(bytes @ 1..=4)) => (value >> (8 * (bytes - 1))) as u8,
+        _ => 0,
+    }
+}
+
+struct PendingChannel {
+    offsets: Vec<u16>,
+    default_value: u8,
+    geometry: String,
+    attribute: Option<String>,
+}
+
+/// Parse a GDTF description.xml
+pub fn parse_description(xml: &str) -> Result<GdtfDescription, String> {
+    let mut description = GdtfDescription {
+        name: String::new(),
+        manufacturer: String::new(),
+        modes: Vec::new(),
+    };
+    let mut channel: Option<PendingChannel> = None;
+    let mut used_names: HashSet<String> = HashSet::new();
+
+    for event in EventReader::new(xml.as_bytes()) {
+        match event.map_err(|e| format!("Invalid GDTF description: {}", e))? {
+            XmlEvent::StartElement { name, attributes, .. } => match name.local_name.as_str() {
+                "FixtureType" => {
+                    description.name = attribute(&attributes, "Name").unwrap_or("Unnamed").to_string();
+                    description.manufacturer = attribute(&attributes, "Manufacturer").unwrap_or("Unknown").to_string();
+                }
+                "DMXMode" => {
+                    used_names.clear();
+                    description.modes.push(GdtfMode {
+                        name: attribute(&attributes, "Name").unwrap_or("Default").to_string(),
+                        channels: Vec::new(),
+                    });
+                }
+                "DMXChannel" => {
+                    // Only the first DMX break is patched; virtual channels have no offset
+                    let first_break = attribute(&attributes, "DMXBreak").map(|b| b == "1").unwrap_or(true);
+                    let offsets: Vec<u16> = attribute(&attributes, "Offset")
+                        .unwrap_or("")
+                        .split(',')
+                        .filter_map(|o| o.trim().parse().ok())
+                        .filter(|o| *o > 0)
+                        .collect();
+                    channel = (first_break && !offsets.is_empty()).then(|| PendingChannel {
+                        offsets,
+                        default_value: coarse_default(attribute(&attributes, "Default")),
+                        geometry: attribute(&attributes, "Geometry").unwrap_or("").to_string(),
+                        attribute: None,
+                    });
+                }
+                "LogicalChannel" => {
+                    if let Some(channel) = channel.as_mut() {
+                        if channel.attribute.is_none() {
+                            channel.attribute = attribute(&attributes, "Attribute").map(str::to_string);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            XmlEvent::EndElement { name } if name.local_name == "DMXChannel" => {
+                let (Some(pending), Some(mode)) = (channel.take(), description.modes.last_mut()) else {
+                    continue;
+                };
+                let channel_type = pending.attribute.unwrap_or_else(|| "Other".to_string());
+                // Multi-instance fixtures repeat attributes per geometry
+                let mut base = channel_type.clone();
+                if !used_names.insert(base.to_lowercase()) {
+                    base = format!("{} {}", channel_type, pending.geometry);
+                    used_names.insert(base.to_lowercase());
+                }
+                for (byte, offset) in pending.offsets.iter().enumerate() {
+                    mode.channels.push(FixtureChannel {
+                        name: if byte == 0 { base.clone() } else { format!("{} fine", base) },
+                        offset: offset - 1,
+                        default_value: if byte == 0 { pending.default_value } else { 0 },
+                        channel_type: channel_type.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if description.name.is_empty() {
+        return Err("GDTF description has no FixtureType".to_string());
+    }
+    for mode in &mut description.modes {
+        mode.channels.sort_by_key(|c| c.offset);
+    }
+    Ok(description)
+}
+
+/// Read the description.xml out of a GDTF archive
+pub fn read_gdtf<R: Read + Seek>(reader: R) -> Result<GdtfDescription, String> {
+    let mut archive = ZipArchive::new(reader).map_err(|e| e.to_string())?;
+    let mut xml = String::new();
+    archive
+        .by_name("description.xml")
+        .map_err(|e| format!("GDTF has no description.xml: {}", e))?
+        .read_to_string(&mut xml)
+        .map_err(|e| e.to_string())?;
+    parse_description(&xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_description() {
+        let xml = r#"<?xml version="1.0"?>
+<GDTF DataVersion="1.1">
+  <FixtureType Name="Spot" Manufacturer="Acme">
+    <DMXModes>
+      <DMXMode Name="Extended" Geometry="Base">
+        <DMXChannels>
+          <DMXChannel DMXBreak="1" Offset="1,2" Default="32768/2" Geometry="Yoke">
+            <LogicalChannel Attribute="Pan"/>
+          </DMXChannel>
+          <DMXChannel DMXBreak="1" Offset="3" Default="0/1" Geometry="Head">
+            <LogicalChannel Attribute="Dimmer"/>
+          </DMXChannel>
+          <DMXChannel DMXBreak="1" Offset="" Geometry="Head">
+            <LogicalChannel Attribute="Virtual"/>
+          </DMXChannel>
+        </DMXChannels>
+      </DMXMode>
+    </DMXModes>
+  </FixtureType>
+</GDTF>"#;
+        let description = parse_description(xml).unwrap();
+        let fixture_type = description.fixture_type(None).unwrap();
+        assert_eq!(fixture_type.name, "Spot (Extended)");
+        let names: Vec<&str> = fixture_type.channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Pan", "Pan fine", "Dimmer"]);
+        assert_eq!(fixture_type.channels[0].default_value, 128);
+    }
+}
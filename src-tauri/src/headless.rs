@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use crate::show_file;
+use crate::{autosave, console, web_server, AppState};
+
+/// Command line options
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    /// Run as a background service: DMX output, playback and the web remote, no window
+    pub headless: bool,
+    /// Show file to load at startup
+    pub show: Option<PathBuf>,
+    /// Terminal console: in-process with --headless, otherwise against a running instance
+    pub console: bool,
+    /// Web API of the instance the console talks to
+    pub url: Option<String>,
+}
+
+pub const USAGE: &str = "Usage: rocontrol [--headless] [--show <file>] [--console [--url <host:port>]]";
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--console" => options.console = true,
+                "--show" => {
+                    let path = args.next().ok_or("--show needs a show file")?;
+                    options.show = Some(PathBuf::from(path));
+                }
+                "--url" => options.url = Some(args.next().ok_or("--url needs an address")?),
+                _ => match arg.strip_prefix("--show=") {
+                    Some(path) => options.show = Some(PathBuf::from(path)),
+                    // macOS passes -psn_* when launched from Finder; Tauri adds its own flags in dev
+                    None if !arg.starts_with("--") => {}
+                    None => return Err(format!("Unknown option '{}'\n{}", arg, USAGE)),
+                },
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Load the show given on the command line. Without one, a headless service picks up
+/// the autosave of a crashed session since there is nobody to ask about it.
+pub fn load_startup_show(state: &AppState, options: &Options) {
+    if let Some(path) = &options.show {
+        match show_file::load_show_file(state, path) {
+            Ok(loaded) => {
+                println!("[Show] Loaded '{}' from {}", loaded.info.name, path.display());
+                if let Some(report) = loaded.migration {
+                    println!("[Show] Upgraded from format {} to {}", report.from_version, report.to_version);
+                }
+            }
+            Err(e) => eprintln!("[Show] Failed to load {}: {}", path.display(), e),
+        }
+        // An explicit show wins over any autosave left behind
+        if let Ok(mut recovery) = state.recovery.lock() {
+            *recovery = None;
+        }
+    } else if options.headless && state.recovery.lock().map(|r| r.is_some()).unwrap_or(false) {
+        match autosave::restore(state) {
+            Ok(loaded) => println!("[Autosave] Restored '{}' from the previous session", loaded.info.name),
+            Err(e) => eprintln!("[Autosave] Failed to restore autosave: {}", e),
+        }
+    }
+}
+
+/// Run the web remote until SIGTERM or Ctrl+C, or until the console is closed when
+/// `console` is set. The output loop and autosave must already be running.
+pub fn run(state: AppState, autosave_dir: &Path, console: bool) {
+    println!("[Headless] RoControl {} running without a window", env!("CARGO_PKG_VERSION"));
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("[Headless] Failed to start async runtime: {}", e);
+            return;
+        }
+    };
+    runtime.block_on(async move {
+        let console_closed = console.then(|| {
+            let (closed_tx, closed_rx) = tokio::sync::oneshot::channel::<()>();
+            let backend = console::Backend::InProcess(state.clone());
+            std::thread::spawn(move || {
+                if let Err(e) = console::run(backend) {
+                    eprintln!("[Console] {}", e);
+                }
+                let _ = closed_tx.send(());
+            });
+            closed_rx
+        });
+        tokio::spawn(async move {
+            if let Err(e) = web_server::start_server(state).await {
+                eprintln!("Web server error: {}", e);
+            }
+        });
+
+        tokio::select! {
+            signal = shutdown_signal() => println!("[Headless] {} received, shutting down", signal),
+            Some(_) = async { console_closed?.await.ok() } => println!("[Headless] Console closed, shutting down"),
+        }
+    });
+    // Dropping the runtime stops the web server
+    runtime.shutdown_timeout(std::time::Duration::from_secs(2));
+
+    autosave::end_session(autosave_dir);
+    println!("[Headless] Stopped");
+}
+
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                eprintln!("[Headless] Can't listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(Options::parse(args(&[])).unwrap(), Options::default());
+        let options = Options::parse(args(&["--headless", "--show", "rack.json"])).unwrap();
+        assert!(options.headless);
+        assert_eq!(options.show, Some(PathBuf::from("rack.json")));
+        assert_eq!(Options::parse(args(&["--show=a.json"])).unwrap().show, Some(PathBuf::from("a.json")));
+        assert!(Options::parse(args(&["--show"])).is_err());
+        let options = Options::parse(args(&["--console", "--url", "deck.local:8080"])).unwrap();
+        assert!(options.console && !options.headless);
+        assert_eq!(options.url.as_deref(), Some("deck.local:8080"));
+        assert!(Options::parse(args(&["--verbose"])).is_err());
+    }
+}
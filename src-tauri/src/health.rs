@@ -0,0 +1,180 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Most recent failure in a background task (output, autosave, web server)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorReport {
+    /// Part of the console that failed, e.g. "output" or "autosave"
+    pub source: String,
+    pub message: String,
+    /// Unix time in seconds
+    pub time: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UniverseRate {
+    /// Art-Net numbering, 0-based
+    pub universe: u8,
+    pub packets_per_second: f64,
+}
+
+/// Packets sent on one universe, averaged over windows of about a second
+#[derive(Debug, Clone)]
+struct PacketRate {
+    window_start: Instant,
+    count: u32,
+    rate: f64,
+}
+
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+impl PacketRate {
+    fn new(now: Instant) -> Self {
+        PacketRate { window_start: now, count: 0, rate: 0.0 }
+    }
+
+    fn record(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= RATE_WINDOW {
+            self.rate = self.count as f64 / elapsed.as_secs_f64();
+            self.window_start = now;
+            self.count = 0;
+        }
+        self.count += 1;
+    }
+
+    /// Rate of the last full window. A universe that stops sending decays towards zero
+    /// instead of reporting its old rate forever.
+    fn rate(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= RATE_WINDOW {
+            self.count as f64 / elapsed.as_secs_f64()
+        } else {
+            self.rate
+        }
+    }
+}
+
+/// Runtime counters behind the status endpoint
+pub struct Health {
+    started: Instant,
+    last_error: Mutex<Option<ErrorReport>>,
+    packets: Mutex<HashMap<u8, PacketRate>>,
+    websocket_clients: AtomicUsize,
+}
+
+/// Counts a WebSocket client until dropped
+pub struct ClientGuard<'a>(&'a Health);
+
+impl Drop for ClientGuard<'_> {
+    fn drop(&mut self) {
+        self.0.websocket_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Health {
+            started: Instant::now(),
+            last_error: Mutex::new(None),
+            packets: Mutex::new(HashMap::new()),
+            websocket_clients: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Remember a failure for the status endpoint. Callers still log it themselves.
+    pub fn report_error(&self, source: &str, message: impl ToString) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = Some(ErrorReport { source: source.to_string(), message: message.to_string(), time });
+        }
+    }
+
+    pub fn last_error(&self) -> Option<ErrorReport> {
+        self.last_error.lock().ok().and_then(|last| last.clone())
+    }
+
+    pub fn packet_sent(&self, universe: u8) {
+        let now = Instant::now();
+        if let Ok(mut packets) = self.packets.lock() {
+            packets.entry(universe).or_insert_with(|| PacketRate::new(now)).record(now);
+        }
+    }
+
+    /// Packet rate of every universe that has been transmitted, in universe order
+    pub fn packet_rates(&self) -> Vec<UniverseRate> {
+        let now = Instant::now();
+        let mut rates: Vec<UniverseRate> = match self.packets.lock() {
+            Ok(packets) => packets
+                .iter()
+                .map(|(universe, rate)| UniverseRate { universe: *universe, packets_per_second: rate.rate(now) })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        rates.sort_by_key(|rate| rate.universe);
+        rates
+    }
+
+    pub fn client_connected(&self) -> ClientGuard<'_> {
+        self.websocket_clients.fetch_add(1, Ordering::Relaxed);
+        ClientGuard(self)
+    }
+
+    pub fn websocket_clients(&self) -> usize {
+        self.websocket_clients.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_rate_windows_and_decay() {
+        let start = Instant::now();
+        let mut rate = PacketRate::new(start);
+        for frame in 0..44 {
+            rate.record(start + Duration::from_millis(frame * 1000 / 44));
+        }
+        assert_eq!(rate.rate(start + Duration::from_millis(990)), 0.0);
+
+        rate.record(start + Duration::from_secs(1));
+        assert_eq!(rate.rate(start + Duration::from_millis(1500)), 44.0);
+
+        // Stopped sending: the open window counts against the elapsed time
+        assert_eq!(rate.rate(start + Duration::from_secs(11)), 0.1);
+    }
+
+    #[test]
+    fn test_client_count_and_last_error() {
+        let health = Health::new();
+        let first = health.client_connected();
+        {
+            let _second = health.client_connected();
+            assert_eq!(health.websocket_clients(), 2);
+        }
+        assert_eq!(health.websocket_clients(), 1);
+        drop(first);
+        assert_eq!(health.websocket_clients(), 0);
+
+        assert!(health.last_error().is_none());
+        health.report_error("output", "Network unreachable");
+        health.report_error("autosave", "Disk full");
+        let last = health.last_error().unwrap();
+        assert_eq!((last.source.as_str(), last.message.as_str()), ("autosave", "Disk full"));
+    }
+}
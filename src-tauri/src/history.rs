@@ -0,0 +1,290 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::chaser::Chaser;
+use crate::cue_engine::CueList;
+use crate::effects_engine::Effect;
+use crate::presets::Preset;
+use crate::selection::Group;
+use crate::{AppState, Fixture, GdtfFixtureType};
+
+/// Repeats of the same edit within this window merge into one step, so dragging a
+/// fader is undone in one go
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Source of edits made on this machine: the desktop UI and the headless console
+pub const LOCAL: &str = "local";
+
+/// Part of the show an operation changes. Only these parts are snapshotted for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Domain {
+    /// Fixtures and the fixture library
+    Patch,
+    Programmer,
+    Cues,
+    Presets,
+    Groups,
+    Effects,
+    Chasers,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Snapshot {
+    patch: Option<(HashMap<String, Fixture>, HashMap<String, GdtfFixtureType>)>,
+    programmer: Option<HashMap<String, u8>>,
+    cue_lists: Option<Vec<CueList>>,
+    presets: Option<Vec<Preset>>,
+    groups: Option<Vec<Group>>,
+    effects: Option<Vec<Effect>>,
+    chasers: Option<Vec<Chaser>>,
+}
+
+impl Snapshot {
+    fn capture(state: &AppState, domains: &[Domain]) -> Result<Self, String> {
+        let mut snapshot = Snapshot::default();
+        for domain in domains {
+            match domain {
+                Domain::Patch => {
+                    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?.clone();
+                    let library = state.fixture_library.lock().map_err(|e| e.to_string())?.clone();
+                    snapshot.patch = Some((fixtures, library));
+                }
+                Domain::Programmer => {
+                    snapshot.programmer = Some(state.programmer.lock().map_err(|e| e.to_string())?.clone());
+                }
+                Domain::Cues => {
+                    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+                    snapshot.cue_lists = Some(cue_engine.lists().cloned().collect());
+                }
+                Domain::Presets => {
+                    let presets = state.presets.lock().map_err(|e| e.to_string())?;
+                    snapshot.presets = Some(presets.presets().cloned().collect());
+                }
+                Domain::Groups => {
+                    snapshot.groups = Some(state.selection.lock().map_err(|e| e.to_string())?.groups().to_vec());
+                }
+                Domain::Effects => {
+                    let effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+                    snapshot.effects = Some(effects.effects().cloned().collect());
+                }
+                Domain::Chasers => {
+                    let chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+                    snapshot.chasers = Some(chasers.chasers().cloned().collect());
+                }
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Put the captured parts back. Playback keeps running.
+    fn restore(&self, state: &AppState) -> Result<(), String> {
+        if let Some((fixtures, library)) = &self.patch {
+            *state.fixture_library.lock().map_err(|e| e.to_string())? = library.clone();
+            *state.fixtures.lock().map_err(|e| e.to_string())? = fixtures.clone();
+        }
+        if let Some(programmer) = &self.programmer {
+            *state.programmer.lock().map_err(|e| e.to_string())? = programmer.clone();
+        }
+        if let Some(cue_lists) = &self.cue_lists {
+            state.cue_engine.lock().map_err(|e| e.to_string())?.restore(cue_lists.clone());
+        }
+        if let Some(presets) = &self.presets {
+            state.presets.lock().map_err(|e| e.to_string())?.restore(presets.clone());
+        }
+        if let Some(groups) = &self.groups {
+            state.selection.lock().map_err(|e| e.to_string())?.restore_groups(groups.clone());
+        }
+        if let Some(effects) = &self.effects {
+            state.effects_engine.lock().map_err(|e| e.to_string())?.restore(effects.clone());
+        }
+        if let Some(chasers) = &self.chasers {
+            state.chaser_engine.lock().map_err(|e| e.to_string())?.restore(chasers.clone());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    label: String,
+    /// Who made the edit; only their own repeats merge into it
+    source: String,
+    at: Instant,
+    before: Snapshot,
+    after: Snapshot,
+}
+
+/// Undo and redo steps for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HistoryStatus {
+    /// Labels, most recent first
+    pub undo: Vec<String>,
+    pub redo: Vec<String>,
+}
+
+/// Journal of reversible edits. Every step keeps the affected parts of the show as they
+/// were before and after, so undo and redo work the same whichever client made the edit.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget all steps, e.g. when another show is loaded
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn status(&self) -> HistoryStatus {
+        HistoryStatus {
+            undo: self.undo.iter().rev().map(|e| e.label.clone()).collect(),
+            redo: self.redo.iter().rev().map(|e| e.label.clone()).collect(),
+        }
+    }
+
+    fn push(&mut self, entry: Entry, limit: usize) {
+        self.redo.clear();
+        match self.undo.back_mut() {
+            Some(last)
+                if last.label == entry.label
+                    && last.source == entry.source
+                    && last.at.elapsed() < COALESCE_WINDOW =>
+            {
+                last.after = entry.after;
+                last.at = entry.at;
+            }
+            _ => self.undo.push_back(entry),
+        }
+        while self.undo.len() > limit.max(1) {
+            self.undo.pop_front();
+        }
+    }
+}
+
+/// Run an edit made on this machine and journal it
+pub fn journal<T>(
+    state: &AppState,
+    label: impl Into<String>,
+    domains: &[Domain],
+    edit: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    journal_from(state, LOCAL, label, domains, edit)
+}
+
+/// Run an edit and journal it. `domains` must cover everything the edit changes; with
+/// none the edit runs without a step (dry runs). Failed edits aren't journaled.
+/// Journaled edits, undo and redo run one at a time, so a step never captures another
+/// client's change.
+pub fn journal_from<T>(
+    state: &AppState,
+    source: &str,
+    label: impl Into<String>,
+    domains: &[Domain],
+    edit: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    if domains.is_empty() {
+        return edit();
+    }
+    let _edits = state.edits.lock().map_err(|e| e.to_string())?;
+    let before = Snapshot::capture(state, domains)?;
+    let result = edit()?;
+    let after = Snapshot::capture(state, domains)?;
+
+    let limit = state.show.lock().map_err(|e| e.to_string())?.preferences.undo_levels;
+    let entry = Entry { label: label.into(), source: source.to_string(), at: Instant::now(), before, after };
+    state.history.lock().map_err(|e| e.to_string())?.push(entry, limit);
+    Ok(result)
+}
+
+pub fn undo(state: &AppState) -> Result<String, String> {
+    let _edits = state.edits.lock().map_err(|e| e.to_string())?;
+    let entry = state
+        .history
+        .lock()
+        .map_err(|e| e.to_string())?
+        .undo
+        .pop_back()
+        .ok_or("Nothing to undo")?;
+    entry.before.restore(state)?;
+    let message = format!("Undo: {}", entry.label);
+    state.history.lock().map_err(|e| e.to_string())?.redo.push(entry);
+    Ok(message)
+}
+
+pub fn redo(state: &AppState) -> Result<String, String> {
+    let _edits = state.edits.lock().map_err(|e| e.to_string())?;
+    let mut entry = state
+        .history
+        .lock()
+        .map_err(|e| e.to_string())?
+        .redo
+        .pop()
+        .ok_or("Nothing to redo")?;
+    entry.after.restore(state)?;
+    let message = format!("Redo: {}", entry.label);
+    // A redone step never merges with the next edit
+    entry.at = Instant::now().checked_sub(COALESCE_WINDOW).unwrap_or(entry.at);
+    state.history.lock().map_err(|e| e.to_string())?.undo.push_back(entry);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(label: &str, value: u8) -> Entry {
+        Entry {
+            label: label.to_string(),
+            source: LOCAL.to_string(),
+            at: Instant::now(),
+            before: Snapshot::default(),
+            after: Snapshot {
+                programmer: Some(HashMap::from([("1:0".to_string(), value)])),
+                ..Snapshot::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_repeated_edits_coalesce() {
+        let mut history = History::new();
+        history.push(entry("Fader 1", 10), 100);
+        history.push(entry("Fader 1", 20), 100);
+        history.push(entry("Record cue 1", 0), 100);
+        assert_eq!(history.status().undo, vec!["Record cue 1", "Fader 1"]);
+        assert_eq!(history.undo[0].after.programmer.as_ref().unwrap()["1:0"], 20);
+    }
+
+    #[test]
+    fn test_edits_from_other_clients_stay_separate() {
+        let mut history = History::new();
+        history.push(entry("Fader 1", 10), 100);
+        history.push(Entry { source: "10.0.0.7".to_string(), ..entry("Fader 1", 20) }, 100);
+        history.push(Entry { source: "10.0.0.7".to_string(), ..entry("Fader 1", 30) }, 100);
+        assert_eq!(history.undo.len(), 2);
+        assert_eq!(history.undo[0].after.programmer.as_ref().unwrap()["1:0"], 10);
+        assert_eq!(history.undo[1].after.programmer.as_ref().unwrap()["1:0"], 30);
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_new_edits_drop_redo() {
+        let mut history = History::new();
+        for i in 0..5 {
+            history.push(entry(&format!("Edit {}", i), i), 3);
+        }
+        assert_eq!(history.status().undo, vec!["Edit 4", "Edit 3", "Edit 2"]);
+
+        let undone = history.undo.pop_back().unwrap();
+        history.redo.push(undone);
+        history.push(entry("Edit 5", 5), 3);
+        assert!(history.status().redo.is_empty());
+    }
+}
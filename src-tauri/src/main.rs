@@ -4,9 +4,36 @@
 mod web_server;
 mod ndi_support;
 mod streamdeck_support;
+mod attributes;
+mod cue_engine;
+mod output_loop;
+mod effects_engine;
+mod fan;
+mod chaser;
+mod show_file;
+mod autosave;
+mod migration;
+mod show_import;
+mod gdtf;
+mod mvr;
+mod selection;
+mod patch;
+mod presets;
+mod actions;
+mod cli;
+mod headless;
+mod console;
+mod health;
+mod history;
+mod ws_protocol;
+mod web_api;
+mod web_auth;
+mod web_settings;
+mod video_upload;
 
 use artnet_protocol::*;
 use sacn::source::SacnSource;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::UdpSocket;
@@ -14,8 +41,24 @@ use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 use tauri::State;
 use std::fs;
-use std::io::Read;
-use zip::ZipArchive;
+use tokio::sync::broadcast;
+use attributes::PatchLookup;
+use attributes::channel_key;
+use cue_engine::{ChannelTrackEntry, CueEngine, CueList, CueListStatus, CueTiming, RecordMode};
+use effects_engine::{Effect, EffectsEngine};
+use fan::{FanRequest, FanResult};
+use chaser::{Chaser, ChaserEngine, ChaserStatus};
+use show_file::{LoadedShow, ShowDocument, ShowInfo};
+use autosave::{AutosaveSettings, RecoveryInfo};
+use show_import::ImportReport;
+use selection::{Group, SelectionCommand, SelectionEngine, SelectionStatus};
+use presets::{Preset, PresetEngine, PresetId};
+use cli::CliSession;
+use output_loop::OutputOverrides;
+use history::{Domain, History, HistoryStatus};
+use web_auth::Role;
+use web_settings::{ServerSettings, UploadSettings, WebAccessStatus, WebSettings};
+use web_server::WebServerStatus;
 
 // DMX Universe - 512 channels
 type DmxUniverse = [u8; 512];
@@ -27,7 +70,7 @@ struct NetworkInterface {
     is_loopback: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct Fixture {
     id: String,
     name: String,
@@ -40,6 +83,15 @@ struct Fixture {
     is_video: Option<bool>,           // True if this is a video fixture
     video_source_type: Option<String>, // "file" or "ndi"
     video_source_path: Option<String>, // File path or NDI stream name
+    #[serde(default)]
+    position: Option<FixturePosition>, // Stage position in meters
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+struct FixturePosition {
+    x: f32,
+    y: f32,
+    z: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,12 +125,31 @@ struct DmxEngine {
     selected_interface: Option<String>, // IP address of selected interface
 }
 
+#[derive(Clone)]
 struct AppState {
     dmx_engine: Arc<Mutex<DmxEngine>>,
     fixtures: Arc<Mutex<HashMap<String, Fixture>>>,
     fixture_library: Arc<Mutex<HashMap<String, GdtfFixtureType>>>,
     programmer: Arc<Mutex<HashMap<String, u8>>>, // fixture_id:channel -> value
-    streamdeck_manager: Arc<Mutex<streamdeck_support::StreamDeckManager>>,
+    streamdeck_manager: Arc<Mutex<Option<streamdeck_support::StreamDeckManager>>>, // None without HID access
+    cue_engine: Arc<Mutex<CueEngine>>,
+    effects_engine: Arc<Mutex<EffectsEngine>>,
+    chaser_engine: Arc<Mutex<ChaserEngine>>,
+    selection: Arc<Mutex<SelectionEngine>>, // Groups and the shared fixture selection
+    presets: Arc<Mutex<PresetEngine>>,
+    show: Arc<Mutex<ShowDocument>>, // Show sections not owned by an engine, plus the file path
+    recovery: Arc<Mutex<Option<RecoveryInfo>>>, // Autosave left by a session that crashed
+    cli: Arc<Mutex<CliSession>>, // Feature set, fade time and fan mode between command lines
+    overrides: Arc<Mutex<OutputOverrides>>, // Highlight and parked channels
+    history: Arc<Mutex<History>>, // Undo/redo journal shared by every client
+    edits: Arc<Mutex<()>>, // Held while a journaled edit, undo or redo runs
+    events: broadcast::Sender<ws_protocol::Event>, // Changes pushed to web clients
+    web_settings: Arc<Mutex<WebSettings>>, // Web remote logins, CORS origins and listen address of this machine
+    web_server: Arc<tokio::sync::Mutex<Option<web_server::WebServerControl>>>, // Running web server, for restarts
+    web_auth: web_auth::Auth, // Open web remote sessions
+    video_dir: PathBuf, // Video files served to and uploaded from the web remote
+    ndi_manager: Arc<ndi_support::NdiManager>, // NDI sources for video fixtures
+    health: Arc<health::Health>, // Uptime, packet rates and last error for the status endpoint
 }
 
 impl DmxEngine {
@@ -129,6 +200,14 @@ impl DmxEngine {
         }
     }
 
+    /// Whether the selected protocol has a socket to send on
+    fn output_ready(&self) -> bool {
+        match self.protocol {
+            DmxProtocol::ArtNet => self.artnet_socket.is_some(),
+            DmxProtocol::Sacn => self.sacn_source.is_some(),
+        }
+    }
+
     fn send_dmx(&self, universe: u8) -> std::result::Result<(), Box<dyn std::error::Error>> {
         match self.protocol {
             DmxProtocol::ArtNet => self.send_artnet(universe),
@@ -194,21 +273,136 @@ fn set_fixture_channel(
     channel_offset: u16,
     value: u8,
 ) -> std::result::Result<String, String> {
-    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
-    let fixture = fixtures
-        .get(&fixture_id)
-        .ok_or("Fixture not found")?;
+    history::journal(&state, format!("Set {} channel {}", fixture_id, channel_offset), &[Domain::Programmer], || {
+        let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let fixture = fixtures
+            .get(&fixture_id)
+            .ok_or("Fixture not found")?;
 
-    let mut engine = state.dmx_engine.lock().map_err(|e| e.to_string())?;
-    let absolute_channel = fixture.dmx_address + channel_offset;
-    engine.set_channel(fixture.universe, absolute_channel, value);
-    engine.send_dmx(fixture.universe).map_err(|e| e.to_string())?;
+        let mut engine = state.dmx_engine.lock().map_err(|e| e.to_string())?;
+        let absolute_channel = fixture.dmx_address + channel_offset;
+        engine.set_channel(fixture.universe, absolute_channel, value);
+        engine.send_dmx(fixture.universe).map_err(|e| e.to_string())?;
+
+        // Store in programmer
+        let mut programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+        programmer.insert(channel_key(&fixture_id, channel_offset), value);
+
+        Ok(format!("Set fixture {} channel {} to {}", fixture_id, channel_offset, value))
+    })
+}
+
+#[tauri::command]
+fn fan_attribute(
+    state: State<AppState>,
+    request: FanRequest,
+) -> std::result::Result<FanResult, String> {
+    history::journal(&state, format!("Fan {}", request.attribute), &[Domain::Programmer], || {
+        let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        let lookup = PatchLookup::new(&fixtures, &library);
+
+        let mut programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+        fan::apply_fan(&request, &lookup, &mut programmer)
+    })
+}
+
+// Selection Commands
+#[tauri::command]
+fn select_fixtures(
+    state: State<AppState>,
+    command: SelectionCommand,
+) -> std::result::Result<SelectionStatus, String> {
+    selection::run_command(&state.fixtures, &state.selection, command)
+}
+
+#[tauri::command]
+fn get_selection(state: State<AppState>) -> std::result::Result<SelectionStatus, String> {
+    let selection = state.selection.lock().map_err(|e| e.to_string())?;
+    Ok(selection.status())
+}
+
+#[tauri::command]
+fn get_groups(state: State<AppState>) -> std::result::Result<Vec<Group>, String> {
+    let selection = state.selection.lock().map_err(|e| e.to_string())?;
+    Ok(selection.groups().to_vec())
+}
+
+#[tauri::command]
+fn store_group(
+    state: State<AppState>,
+    id: String,
+    name: String,
+    fixture_ids: Option<Vec<String>>,
+    color: Option<String>,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Store group {}", id), &[Domain::Groups], || {
+        let mut selection = state.selection.lock().map_err(|e| e.to_string())?;
+        // Without an explicit list the group is stored from the current selection
+        let fixture_ids = fixture_ids.unwrap_or_else(|| selection.selected().to_vec());
+        if fixture_ids.is_empty() {
+            return Err("Group needs at least one fixture".to_string());
+        }
+        let count = fixture_ids.len();
+        selection.store_group(Group { id: id.clone(), name, fixture_ids, color });
+        Ok(format!("Stored group {} with {} fixtures", id, count))
+    })
+}
+
+#[tauri::command]
+fn delete_group(state: State<AppState>, id: String) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Delete group {}", id), &[Domain::Groups], || {
+        let mut selection = state.selection.lock().map_err(|e| e.to_string())?;
+        if selection.remove_group(&id) {
+            Ok(format!("Deleted group {}", id))
+        } else {
+            Err(format!("Group {} not found", id))
+        }
+    })
+}
+
+// Preset Commands
+#[tauri::command]
+fn record_preset(
+    state: State<AppState>,
+    id: PresetId,
+    name: Option<String>,
+    global: Option<bool>,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Record preset {}", id), &[Domain::Presets, Domain::Cues], || {
+        actions::record_preset(&state, id, name, global.unwrap_or(false), false)
+    })
+}
 
-    // Store in programmer
-    let mut programmer = state.programmer.lock().map_err(|e| e.to_string())?;
-    programmer.insert(format!("{}:{}", fixture_id, channel_offset), value);
+#[tauri::command]
+fn apply_preset(
+    state: State<AppState>,
+    id: PresetId,
+    fixture_ids: Option<Vec<String>>,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Apply preset {}", id), &[Domain::Programmer], || {
+        // Without an explicit list the preset goes to the active selection
+        actions::apply_preset(&state, id, fixture_ids)
+    })
+}
 
-    Ok(format!("Set fixture {} channel {} to {}", fixture_id, channel_offset, value))
+#[tauri::command]
+fn get_presets(state: State<AppState>) -> std::result::Result<Vec<Preset>, String> {
+    let presets = state.presets.lock().map_err(|e| e.to_string())?;
+    Ok(presets.presets().cloned().collect())
+}
+
+#[tauri::command]
+fn delete_preset(state: State<AppState>, id: PresetId) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Delete preset {}", id), &[Domain::Presets, Domain::Cues], || {
+        let mut presets = state.presets.lock().map_err(|e| e.to_string())?;
+        if !presets.remove(id) {
+            return Err(format!("Preset {} not found", id));
+        }
+        // Referencing cues keep their current values as hard values
+        state.cue_engine.lock().map_err(|e| e.to_string())?.update_preset_refs(id, &|_| None);
+        Ok(format!("Deleted preset {}", id))
+    })
 }
 
 #[tauri::command]
@@ -216,10 +410,28 @@ fn add_fixture(
     state: State<AppState>,
     fixture: Fixture,
 ) -> std::result::Result<String, String> {
-    let mut fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
-    let id = fixture.id.clone();
-    fixtures.insert(id.clone(), fixture);
-    Ok(format!("Added fixture {}", id))
+    history::journal(&state, format!("Add fixture {}", fixture.id), &[Domain::Patch], || {
+        let id = fixture.id.clone();
+        patch::add(&mut *state.fixtures.lock().map_err(|e| e.to_string())?, fixture)?;
+        let _ = state.events.send(ws_protocol::Event::PatchChanged { fixture_id: id.clone() });
+        Ok(format!("Added fixture {}", id))
+    })
+}
+
+/// Patch a batch of fixtures as one undo step. Nothing is added if any of them doesn't fit.
+#[tauri::command]
+fn add_fixtures(
+    state: State<AppState>,
+    fixtures: Vec<Fixture>,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Add {} fixtures", fixtures.len()), &[Domain::Patch], || {
+        let ids: Vec<String> = fixtures.iter().map(|f| f.id.clone()).collect();
+        patch::add_all(&mut *state.fixtures.lock().map_err(|e| e.to_string())?, fixtures)?;
+        for id in &ids {
+            let _ = state.events.send(ws_protocol::Event::PatchChanged { fixture_id: id.clone() });
+        }
+        Ok(format!("Added {} fixtures", ids.len()))
+    })
 }
 
 #[tauri::command]
@@ -230,12 +442,7 @@ fn get_fixtures(state: State<AppState>) -> std::result::Result<Vec<Fixture>, Str
 
 #[tauri::command]
 fn blackout(state: State<AppState>) -> std::result::Result<String, String> {
-    let mut engine = state.dmx_engine.lock().map_err(|e| e.to_string())?;
-    engine.blackout();
-    for universe in 0..=255 {
-        let _ = engine.send_dmx(universe);
-    }
-    Ok("Blackout activated".to_string())
+    history::journal(&state, "Blackout", &[Domain::Programmer], || actions::blackout(&state))
 }
 
 #[tauri::command]
@@ -258,40 +465,23 @@ fn parse_gdtf_file(
     state: State<AppState>,
     file_path: String,
 ) -> std::result::Result<GdtfFixtureType, String> {
-    // GDTF files are ZIP archives containing XML files
-    let file = fs::File::open(&file_path).map_err(|e| e.to_string())?;
-    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
-    
-    // Look for description.xml
-    let mut description_xml = String::new();
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
-        if file.name().ends_with("description.xml") {
-            file.read_to_string(&mut description_xml).map_err(|e| e.to_string())?;
-            break;
-        }
-    }
-
-    // Basic XML parsing (simplified for now)
-    // In production, use proper XML parser to extract DMX modes, channels, etc.
-    let fixture_type = GdtfFixtureType {
-        name: "Generic GDTF Fixture".to_string(),
-        manufacturer: "Unknown".to_string(),
-        channels: vec![
-            FixtureChannel {
-                name: "Dimmer".to_string(),
-                offset: 0,
-                default_value: 0,
-                channel_type: "Dimmer".to_string(),
-            },
-        ],
-        modes: vec!["Standard".to_string()],
-    };
+    history::journal(&state, format!("Import GDTF {}", file_path), &[Domain::Patch], || {
+        // GDTF files are ZIP archives with a description.xml; every DMX mode becomes a library entry
+        let file = fs::File::open(&file_path).map_err(|e| e.to_string())?;
+        let description = gdtf::read_gdtf(file)?;
+        let fixture_types = description.fixture_types();
+        let fixture_type = fixture_types
+            .first()
+            .cloned()
+            .ok_or_else(|| format!("{} has no DMX modes", description.name))?;
 
-    let mut library = state.fixture_library.lock().map_err(|e| e.to_string())?;
-    library.insert(fixture_type.name.clone(), fixture_type.clone());
+        let mut library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        for fixture_type in fixture_types {
+            library.insert(fixture_type.name.clone(), fixture_type);
+        }
 
-    Ok(fixture_type)
+        Ok(fixture_type)
+    })
 }
 
 #[tauri::command]
@@ -347,21 +537,517 @@ fn set_network_interface(
     }
 }
 
+/// Run a command line from the desktop CLI. Desktop-only commands (windows, video,
+/// conditions) come back unexecuted as `desktop` for the UI to handle.
 #[tauri::command]
-async fn execute_cli_command(
+fn execute_cli_command(
+    state: State<AppState>,
     command: String,
+) -> cli::CommandResult {
+    cli::run_structured(&state, history::LOCAL, &command)
+}
+
+// Cue Commands
+#[tauri::command]
+fn record_cue(
+    state: State<AppState>,
+    cue_list: u32,
+    cue_number: f64,
+    name: Option<String>,
+    timing: Option<CueTiming>,
+    mode: Option<RecordMode>,
+    block: Option<bool>,
 ) -> std::result::Result<String, String> {
-    // TODO: Integrate with actual CLI dispatcher
-    // For now, return a placeholder response
-    Ok(format!("CLI command received: {}", command))
+    history::journal(&state, format!("Record cue {}/{}", cue_list, cue_number), &[Domain::Cues], || {
+        actions::record_cue(
+            &state,
+            cue_list,
+            cue_number,
+            name,
+            timing,
+            mode.unwrap_or_default(),
+            block.unwrap_or(false),
+        )
+    })
+}
+
+#[tauri::command]
+fn get_tracked_state(
+    state: State<AppState>,
+    cue_list: u32,
+    cue_number: f64,
+) -> std::result::Result<HashMap<String, u8>, String> {
+    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let list = cue_engine.list(cue_list)?;
+    let index = list
+        .find(cue_number)
+        .ok_or_else(|| format!("Cue {} not found in list {}", cue_number, cue_list))?;
+    Ok(list.tracked_state(index))
+}
+
+#[tauri::command]
+fn get_channel_tracking(
+    state: State<AppState>,
+    cue_list: u32,
+    fixture_id: String,
+    channel_offset: u16,
+) -> std::result::Result<Vec<ChannelTrackEntry>, String> {
+    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let list = cue_engine.list(cue_list)?;
+    Ok(list.channel_tracking(&channel_key(&fixture_id, channel_offset)))
+}
+
+#[tauri::command]
+fn set_cue_timing(
+    state: State<AppState>,
+    cue_list: u32,
+    cue_number: f64,
+    timing: CueTiming,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Cue {}/{} timing", cue_list, cue_number), &[Domain::Cues], || {
+        let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+        cue_engine.set_timing(cue_list, cue_number, timing)?;
+        Ok(format!("Updated timing for cue {} in list {}", cue_number, cue_list))
+    })
+}
+
+#[tauri::command]
+fn go_cue(
+    state: State<AppState>,
+    cue_list: u32,
+) -> std::result::Result<String, String> {
+    actions::go(&state, cue_list)
+}
+
+#[tauri::command]
+fn goto_cue(
+    state: State<AppState>,
+    cue_list: u32,
+    cue_number: f64,
+) -> std::result::Result<String, String> {
+    actions::goto(&state, cue_list, cue_number)
+}
+
+#[tauri::command]
+fn release_cue_list(
+    state: State<AppState>,
+    cue_list: u32,
+) -> std::result::Result<String, String> {
+    let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    cue_engine.release(cue_list);
+    Ok(format!("Released cue list {}", cue_list))
+}
+
+#[tauri::command]
+fn get_cue_lists(state: State<AppState>) -> std::result::Result<Vec<CueList>, String> {
+    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    Ok(cue_engine.lists().cloned().collect())
+}
+
+#[tauri::command]
+fn get_playback_status(state: State<AppState>) -> std::result::Result<Vec<CueListStatus>, String> {
+    let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+    let t = cue_engine.now();
+    Ok(cue_engine.lists().map(|list| list.status(t)).collect())
+}
+
+// Effect Commands
+#[tauri::command]
+fn store_effect(
+    state: State<AppState>,
+    effect: Effect,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Store effect {}", effect.id), &[Domain::Effects], || {
+        let mut effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+        let id = effect.id;
+        effects.store(effect);
+        Ok(format!("Stored effect {}", id))
+    })
+}
+
+#[tauri::command]
+fn delete_effect(
+    state: State<AppState>,
+    effect_id: u32,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Delete effect {}", effect_id), &[Domain::Effects], || {
+        let mut effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+        if effects.remove(effect_id) {
+            Ok(format!("Deleted effect {}", effect_id))
+        } else {
+            Err(format!("Effect {} not found", effect_id))
+        }
+    })
+}
+
+#[tauri::command]
+fn start_effect(
+    state: State<AppState>,
+    effect_id: u32,
+) -> std::result::Result<String, String> {
+    let mut effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+    effects.start(effect_id)?;
+    Ok(format!("Started effect {}", effect_id))
+}
+
+#[tauri::command]
+fn stop_effect(
+    state: State<AppState>,
+    effect_id: u32,
+) -> std::result::Result<String, String> {
+    let mut effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+    effects.stop(effect_id);
+    Ok(format!("Stopped effect {}", effect_id))
+}
+
+#[tauri::command]
+fn get_effects(state: State<AppState>) -> std::result::Result<Vec<Effect>, String> {
+    let effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+    Ok(effects.effects().cloned().collect())
+}
+
+// Chaser Commands
+#[tauri::command]
+fn store_chaser(
+    state: State<AppState>,
+    chaser: Chaser,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Store chaser {}", chaser.id), &[Domain::Chasers], || {
+        let mut chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+        let id = chaser.id;
+        chasers.store(chaser);
+        Ok(format!("Stored chaser {}", id))
+    })
+}
+
+#[tauri::command]
+fn delete_chaser(
+    state: State<AppState>,
+    chaser_id: u32,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Delete chaser {}", chaser_id), &[Domain::Chasers], || {
+        let mut chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+        if chasers.remove(chaser_id) {
+            Ok(format!("Deleted chaser {}", chaser_id))
+        } else {
+            Err(format!("Chaser {} not found", chaser_id))
+        }
+    })
+}
+
+#[tauri::command]
+fn start_chaser(
+    state: State<AppState>,
+    chaser_id: u32,
+) -> std::result::Result<String, String> {
+    let mut chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+    chasers.start(chaser_id)?;
+    Ok(format!("Started chaser {}", chaser_id))
+}
+
+#[tauri::command]
+fn stop_chaser(
+    state: State<AppState>,
+    chaser_id: u32,
+) -> std::result::Result<String, String> {
+    let mut chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+    chasers.stop(chaser_id)?;
+    Ok(format!("Stopped chaser {}", chaser_id))
+}
+
+#[tauri::command]
+fn set_chaser_bpm(
+    state: State<AppState>,
+    chaser_id: u32,
+    bpm: f64,
+) -> std::result::Result<String, String> {
+    history::journal(&state, format!("Chaser {} BPM", chaser_id), &[Domain::Chasers], || {
+        let mut chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+        chasers.set_bpm(chaser_id, bpm)?;
+        Ok(format!("Chaser {} set to {} BPM", chaser_id, bpm))
+    })
+}
+
+#[tauri::command]
+fn tap_chaser_tempo(
+    state: State<AppState>,
+    chaser_id: u32,
+) -> std::result::Result<f64, String> {
+    let mut chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+    chasers.tap(chaser_id)
+}
+
+#[tauri::command]
+fn get_chasers(state: State<AppState>) -> std::result::Result<Vec<Chaser>, String> {
+    let chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+    Ok(chasers.chasers().cloned().collect())
+}
+
+#[tauri::command]
+fn get_chaser_status(state: State<AppState>) -> std::result::Result<Vec<ChaserStatus>, String> {
+    let chasers = state.chaser_engine.lock().map_err(|e| e.to_string())?;
+    Ok(chasers.chasers().map(Chaser::status).collect())
+}
+
+// Undo Commands
+#[tauri::command]
+fn undo(state: State<AppState>) -> std::result::Result<String, String> {
+    history::undo(&state)
+}
+
+#[tauri::command]
+fn redo(state: State<AppState>) -> std::result::Result<String, String> {
+    history::redo(&state)
+}
+
+#[tauri::command]
+fn get_history(state: State<AppState>) -> std::result::Result<HistoryStatus, String> {
+    let history = state.history.lock().map_err(|e| e.to_string())?;
+    Ok(history.status())
+}
+
+// Web Remote Access Commands
+#[tauri::command]
+fn get_web_access(state: State<AppState>) -> std::result::Result<WebAccessStatus, String> {
+    Ok(state.web_settings.lock().map_err(|e| e.to_string())?.status())
+}
+
+#[tauri::command]
+fn set_web_require_login(state: State<AppState>, require: bool) -> std::result::Result<WebAccessStatus, String> {
+    web_settings::update(&state.web_settings, |settings| settings.set_require_login(require))
+}
+
+#[tauri::command]
+fn save_web_user(
+    state: State<AppState>,
+    name: String,
+    role: Role,
+    secret: String,
+) -> std::result::Result<WebAccessStatus, String> {
+    web_settings::update(&state.web_settings, |settings| settings.save_user(&name, role, &secret))
+}
+
+#[tauri::command]
+fn remove_web_user(state: State<AppState>, name: String) -> std::result::Result<WebAccessStatus, String> {
+    web_settings::update(&state.web_settings, |settings| settings.remove_user(&name))
+}
+
+#[tauri::command]
+fn set_web_allowed_origins(
+    state: State<AppState>,
+    origins: Vec<String>,
+) -> std::result::Result<WebAccessStatus, String> {
+    web_settings::update(&state.web_settings, |settings| settings.set_allowed_origins(origins))
+}
+
+#[tauri::command]
+fn get_upload_settings(state: State<AppState>) -> std::result::Result<UploadSettings, String> {
+    Ok(state.web_settings.lock().map_err(|e| e.to_string())?.uploads.clone())
+}
+
+#[tauri::command]
+fn set_video_upload_limit(state: State<AppState>, max_size_mb: u64) -> std::result::Result<UploadSettings, String> {
+    web_settings::update(&state.web_settings, |settings| settings.uploads.set_max_video_size_mb(max_size_mb))?;
+    get_upload_settings(state)
+}
+
+#[tauri::command]
+async fn get_web_server(state: State<'_, AppState>) -> std::result::Result<Option<WebServerStatus>, String> {
+    Ok(state.web_server.lock().await.as_ref().map(|server| server.status()))
+}
+
+/// Listen on another address, port or with(out) HTTPS. Returns the URLs to reach it.
+#[tauri::command]
+async fn restart_web_server(
+    state: State<'_, AppState>,
+    settings: ServerSettings,
+) -> std::result::Result<WebServerStatus, String> {
+    web_server::restart(&state, settings).await
+}
+
+// Show File Commands
+#[tauri::command]
+fn save_show(
+    state: State<AppState>,
+    path: Option<String>,
+) -> std::result::Result<String, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => state
+            .show
+            .lock()
+            .map_err(|e| e.to_string())?
+            .path
+            .clone()
+            .ok_or("Show has not been saved yet - a path is required")?,
+    };
+    show_file::save_show_file(&state, &path).map_err(|e| e.to_string())?;
+    Ok(format!("Saved show to {}", path.display()))
+}
+
+#[tauri::command]
+fn load_show(
+    state: State<AppState>,
+    path: String,
+) -> std::result::Result<LoadedShow, String> {
+    show_file::load_show_file(&state, &PathBuf::from(path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_show_info(state: State<AppState>) -> std::result::Result<ShowInfo, String> {
+    let show = state.show.lock().map_err(|e| e.to_string())?;
+    Ok(show.info.clone())
+}
+
+#[tauri::command]
+fn get_recovery_info(state: State<AppState>) -> std::result::Result<Option<RecoveryInfo>, String> {
+    let recovery = state.recovery.lock().map_err(|e| e.to_string())?;
+    Ok(recovery.clone())
+}
+
+#[tauri::command]
+fn restore_autosave(state: State<AppState>) -> std::result::Result<LoadedShow, String> {
+    autosave::restore(&state).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_autosave_settings(state: State<AppState>) -> std::result::Result<AutosaveSettings, String> {
+    autosave::settings(&state)
+}
+
+#[tauri::command]
+fn set_autosave_settings(
+    state: State<AppState>,
+    settings: AutosaveSettings,
+) -> std::result::Result<AutosaveSettings, String> {
+    autosave::set_settings(&state, &settings)
+}
+
+#[tauri::command]
+fn discard_recovery(state: State<AppState>) -> std::result::Result<String, String> {
+    *state.recovery.lock().map_err(|e| e.to_string())? = None;
+    Ok("Autosave discarded".to_string())
+}
+
+// Import Commands
+#[tauri::command]
+fn import_csv_patch(
+    state: State<AppState>,
+    path: String,
+    dry_run: Option<bool>,
+) -> std::result::Result<ImportReport, String> {
+    history::journal(&state, format!("Import {}", path), if dry_run.unwrap_or(false) { &[] } else { &[Domain::Patch] }, || {
+        let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let mut report = ImportReport {
+            dry_run: dry_run.unwrap_or(false),
+            ..ImportReport::default()
+        };
+
+        let mut fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        let imported = show_import::import_csv_patch(&data, &library, &fixtures, &mut report)?;
+        if !report.dry_run {
+            for fixture in imported {
+                let id = fixture.id.clone();
+                patch::add(&mut fixtures, fixture)?;
+                let _ = state.events.send(ws_protocol::Event::PatchChanged { fixture_id: id });
+            }
+        }
+        Ok(report)
+    })
+}
+
+#[tauri::command]
+fn import_usitt_ascii(
+    state: State<AppState>,
+    path: String,
+    cue_list: u32,
+    dry_run: Option<bool>,
+) -> std::result::Result<ImportReport, String> {
+    history::journal(&state, format!("Import {}", path), if dry_run.unwrap_or(false) { &[] } else { &[Domain::Cues] }, || {
+        let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let mut report = ImportReport {
+            dry_run: dry_run.unwrap_or(false),
+            ..ImportReport::default()
+        };
+
+        let cues = {
+            let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+            show_import::import_usitt_ascii(&data, &fixtures, &mut report)?
+        };
+        if !report.dry_run {
+            let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+            let list = cue_engine.list_mut(cue_list);
+            for cue in cues {
+                list.store(cue);
+            }
+        }
+        Ok(report)
+    })
+}
+
+#[tauri::command]
+fn import_mvr(
+    state: State<AppState>,
+    path: String,
+    dry_run: Option<bool>,
+) -> std::result::Result<ImportReport, String> {
+    history::journal(&state, format!("Import {}", path), if dry_run.unwrap_or(false) { &[] } else { &[Domain::Patch] }, || {
+        let file = fs::File::open(&path).map_err(|e| e.to_string())?;
+        let mut report = ImportReport {
+            dry_run: dry_run.unwrap_or(false),
+            ..ImportReport::default()
+        };
+        // Embedded GDTFs are kept so the patch can be exported again
+        let gdtf_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("RoControl")
+            .join("GDTF");
+
+        let mut fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let mut library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        let imported = mvr::import_mvr(
+            file,
+            &library,
+            &fixtures,
+            (!report.dry_run).then_some(gdtf_dir.as_path()),
+            &mut report,
+        )?;
+        if !report.dry_run {
+            for fixture_type in imported.fixture_types {
+                library.insert(fixture_type.name.clone(), fixture_type);
+            }
+            for fixture in imported.fixtures {
+                let id = fixture.id.clone();
+                patch::add(&mut fixtures, fixture)?;
+                let _ = state.events.send(ws_protocol::Event::PatchChanged { fixture_id: id });
+            }
+        }
+        Ok(report)
+    })
+}
+
+#[tauri::command]
+fn export_mvr(
+    state: State<AppState>,
+    path: String,
+) -> std::result::Result<String, String> {
+    let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+    let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+    let file = fs::File::create(&path).map_err(|e| e.to_string())?;
+    let count = mvr::export_mvr(file, &fixtures, &library)?;
+    Ok(format!("Exported {} fixtures to {}", count, path))
 }
 
 // Stream Deck Commands
+const STREAMDECK_UNAVAILABLE: &str = "Stream Deck support isn't available (no HID access)";
+
 #[tauri::command]
 fn scan_streamdeck_devices(
     state: State<AppState>,
 ) -> std::result::Result<Vec<streamdeck_support::StreamDeckDevice>, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.scan_devices()
 }
 
@@ -371,6 +1057,7 @@ fn connect_streamdeck(
     serial: String,
 ) -> std::result::Result<String, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.connect_device(&serial)
 }
 
@@ -380,6 +1067,7 @@ fn disconnect_streamdeck(
     serial: String,
 ) -> std::result::Result<String, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.disconnect_device(&serial)
 }
 
@@ -390,6 +1078,7 @@ fn set_streamdeck_brightness(
     brightness: u8,
 ) -> std::result::Result<String, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.set_brightness(&serial, brightness)
 }
 
@@ -399,6 +1088,7 @@ fn read_streamdeck_buttons(
     serial: String,
 ) -> std::result::Result<Vec<bool>, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.read_buttons(&serial)
 }
 
@@ -408,6 +1098,7 @@ fn reset_streamdeck(
     serial: String,
 ) -> std::result::Result<String, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.reset_device(&serial)
 }
 
@@ -417,6 +1108,7 @@ fn clear_streamdeck_buttons(
     serial: String,
 ) -> std::result::Result<String, String> {
     let manager = state.streamdeck_manager.lock().map_err(|e| e.to_string())?;
+    let manager = manager.as_ref().ok_or(STREAMDECK_UNAVAILABLE)?;
     manager.clear_all_buttons(&serial)
 }
 
@@ -424,19 +1116,46 @@ fn clear_streamdeck_buttons(
 // The browser Gamepad API works natively with Steam Input
 
 fn main() {
+    let options = match headless::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Console client for an instance that is already running - no engines of our own
+    if options.console && !options.headless {
+        let backend = match &options.url {
+            Some(url) => Ok(console::Backend::remote(url)),
+            None => console::Backend::local(),
+        };
+        if let Err(e) = backend.and_then(console::run) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let dmx_engine = Arc::new(Mutex::new(DmxEngine::new("2.255.255.255".to_string())));
     let fixtures = Arc::new(Mutex::new(HashMap::new()));
     let fixture_library = Arc::new(Mutex::new(HashMap::new()));
     let programmer = Arc::new(Mutex::new(HashMap::new()));
+    let cue_engine = Arc::new(Mutex::new(CueEngine::new()));
+    let effects_engine = Arc::new(Mutex::new(EffectsEngine::new()));
+    let chaser_engine = Arc::new(Mutex::new(ChaserEngine::new()));
+    let selection = Arc::new(Mutex::new(SelectionEngine::new()));
 
-    // Initialize Stream Deck manager
-    let streamdeck_manager = Arc::new(Mutex::new(
+    // Initialize Stream Deck manager. Headless servers don't use one, and the desktop
+    // keeps running without it when HID isn't available.
+    let streamdeck_manager = if options.headless {
+        None
+    } else {
         streamdeck_support::StreamDeckManager::new()
-            .unwrap_or_else(|e| {
-                eprintln!("Failed to initialize Stream Deck manager: {}", e);
-                panic!("Stream Deck initialization failed");
-            })
-    ));
+            .map_err(|e| eprintln!("Failed to initialize Stream Deck manager, continuing without it: {}", e))
+            .ok()
+    };
+    let streamdeck_manager = Arc::new(Mutex::new(streamdeck_manager));
 
     // Setup video directory for web remote
     let video_dir = dirs::video_dir()
@@ -446,27 +1165,81 @@ fn main() {
 
     // Create video directory if it doesn't exist
     let _ = std::fs::create_dir_all(&video_dir);
+    video_upload::remove_partial_uploads(&video_dir);
+
+    // A session marker left behind means the last run didn't shut down cleanly
+    let autosave_dir = autosave::autosave_dir();
+    let recovery = autosave::check_recovery(&autosave_dir);
+    if let Some(info) = &recovery {
+        println!("[Autosave] Previous session ended unexpectedly - autosave available at {}", info.autosave_path.display());
+    }
+    if let Err(e) = autosave::begin_session(&autosave_dir) {
+        eprintln!("[Autosave] Failed to write session marker: {}", e);
+    }
+
+    let web_settings = Arc::new(Mutex::new(web_settings::load_startup_settings()));
+    let app_state = AppState {
+        dmx_engine,
+        fixtures,
+        fixture_library,
+        programmer,
+        streamdeck_manager,
+        cue_engine,
+        effects_engine,
+        chaser_engine,
+        selection,
+        presets: Arc::new(Mutex::new(PresetEngine::new())),
+        show: Arc::new(Mutex::new(ShowDocument::default())),
+        recovery: Arc::new(Mutex::new(recovery)),
+        cli: Arc::new(Mutex::new(CliSession::default())),
+        overrides: Arc::new(Mutex::new(OutputOverrides::default())),
+        history: Arc::new(Mutex::new(History::new())),
+        edits: Arc::default(),
+        events: broadcast::channel(256).0,
+        web_settings: web_settings.clone(),
+        web_server: Arc::default(),
+        web_auth: web_auth::Auth::new(web_settings),
+        video_dir,
+        ndi_manager: Arc::new(ndi_support::NdiManager::new()),
+        health: Arc::new(health::Health::new()),
+    };
+
+    headless::load_startup_show(&app_state, &options);
+
+    // Cue playback and DMX transmission run in the backend, independent of the UI
+    output_loop::spawn(app_state.clone());
+    autosave::spawn(app_state.clone(), autosave_dir.clone());
+
+    if options.headless {
+        headless::run(app_state, &autosave_dir, options.console);
+        return;
+    }
 
     // Start web server in background using Tauri's async runtime
-    let web_video_dir = video_dir.clone();
+    let web_state = app_state.clone();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = web_server::start_server(web_video_dir).await {
+        if let Err(e) = web_server::start_server(web_state).await {
             eprintln!("Web server error: {}", e);
         }
     });
 
     tauri::Builder::default()
-        .manage(AppState {
-            dmx_engine,
-            fixtures,
-            fixture_library,
-            programmer,
-            streamdeck_manager,
-        })
+        .manage(app_state)
         .invoke_handler(tauri::generate_handler![
             set_dmx_channel,
             set_fixture_channel,
+            fan_attribute,
+            select_fixtures,
+            get_selection,
+            get_groups,
+            store_group,
+            delete_group,
+            record_preset,
+            apply_preset,
+            get_presets,
+            delete_preset,
             add_fixture,
+            add_fixtures,
             get_fixtures,
             blackout,
             set_protocol,
@@ -476,6 +1249,52 @@ fn main() {
             get_network_interfaces,
             set_network_interface,
             execute_cli_command,
+            record_cue,
+            get_tracked_state,
+            get_channel_tracking,
+            set_cue_timing,
+            go_cue,
+            goto_cue,
+            release_cue_list,
+            get_cue_lists,
+            get_playback_status,
+            store_effect,
+            delete_effect,
+            start_effect,
+            stop_effect,
+            get_effects,
+            store_chaser,
+            delete_chaser,
+            start_chaser,
+            stop_chaser,
+            set_chaser_bpm,
+            tap_chaser_tempo,
+            get_chasers,
+            get_chaser_status,
+            undo,
+            redo,
+            get_history,
+            get_web_access,
+            set_web_require_login,
+            save_web_user,
+            remove_web_user,
+            set_web_allowed_origins,
+            get_web_server,
+            restart_web_server,
+            get_upload_settings,
+            set_video_upload_limit,
+            save_show,
+            load_show,
+            get_show_info,
+            get_recovery_info,
+            restore_autosave,
+            discard_recovery,
+            get_autosave_settings,
+            set_autosave_settings,
+            import_csv_patch,
+            import_usitt_ascii,
+            import_mvr,
+            export_mvr,
             scan_streamdeck_devices,
             connect_streamdeck,
             disconnect_streamdeck,
@@ -484,6 +1303,11 @@ fn main() {
             reset_streamdeck,
             clear_streamdeck_buttons,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(move |_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                autosave::end_session(&autosave_dir);
+            }
+        });
 }
@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::attributes::{channel_key, FeatureSet};
+use crate::show_file::SHOW_FORMAT_VERSION;
+
+/// One upgrade step: rewrites a document of version `from` into version `from + 1`
+struct Step {
+    from: u32,
+    description: &'static str,
+    apply: fn(&mut Map<String, Value>, &mut Vec<String>),
+}
+
+/// Ordered upgrade steps. Add a step here whenever SHOW_FORMAT_VERSION is bumped.
+const STEPS: &[Step] = &[
+    Step {
+        from: 0,
+        description: "convert demo-era cues and chase effects to cue lists, executors and chasers",
+        apply: cue_model_v1,
+    },
+    Step {
+        from: 1,
+        description: "rename fixture address to dmx_address and number universes from 1",
+        apply: universe_numbering_v2,
+    },
+    Step {
+        from: 2,
+        description: "turn palettes into numbered presets",
+        apply: presets_v3,
+    },
+];
+
+/// What was changed while upgrading a show file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// RoControl version that wrote the file (`show_info.version`)
+    pub written_by: Option<String>,
+    pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+    /// Plain text report written next to the migrated show
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Show file migrated from format {} to {}\n",
+            self.from_version, self.to_version
+        );
+        if let Some(version) = &self.written_by {
+            text.push_str(&format!("Written by RoControl {}\n", version));
+        }
+        text.push('\n');
+        for change in &self.changes {
+            text.push_str(&format!("- {}\n", change));
+        }
+        text
+    }
+}
+
+/// Format version of a show document. `show_info.version` is the app version that wrote
+/// the file, not the layout, so files without `format_version` predate versioning (0).
+pub fn detect_version(doc: &Value) -> u32 {
+    doc.get("format_version")
+        .and_then(Value::as_u64)
+        .map(|v| v as u32)
+        .unwrap_or(0)
+}
+
+/// Upgrade a show document in place to the current format. Returns None when it
+/// already was current.
+pub fn migrate(doc: &mut Value) -> Result<Option<MigrationReport>, String> {
+    let from_version = detect_version(doc);
+    if from_version > SHOW_FORMAT_VERSION {
+        return Err(format!(
+            "Show file format {} is newer than this version of RoControl supports ({})",
+            from_version, SHOW_FORMAT_VERSION
+        ));
+    }
+    if from_version == SHOW_FORMAT_VERSION {
+        return Ok(None);
+    }
+
+    let root = doc.as_object_mut().ok_or("Show file must be a JSON object")?;
+    let written_by = root
+        .get("show_info")
+        .and_then(|i| i.get("version"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    let mut changes = Vec::new();
+    for step in STEPS.iter().filter(|s| s.from >= from_version) {
+        changes.push(format!("Format {} -> {}: {}", step.from, step.from + 1, step.description));
+        (step.apply)(root, &mut changes);
+    }
+    root.insert("format_version".to_string(), json!(SHOW_FORMAT_VERSION));
+
+    Ok(Some(MigrationReport {
+        from_version,
+        to_version: SHOW_FORMAT_VERSION,
+        written_by,
+        changes,
+    }))
+}
+
+fn array_mut<'a>(root: &'a mut Map<String, Value>, key: &str) -> &'a mut Vec<Value> {
+    let entry = root.entry(key).or_insert_with(|| json!([]));
+    if !entry.is_array() {
+        *entry = json!([]);
+    }
+    entry.as_array_mut().expect("just made an array")
+}
+
+/// Attribute name (lowercase) -> channel offset, from each fixture's inline channel map
+fn attribute_offsets(root: &Map<String, Value>) -> HashMap<String, HashMap<String, u16>> {
+    let mut offsets = HashMap::new();
+    for fixture in root.get("fixtures").and_then(Value::as_array).into_iter().flatten() {
+        let Some(id) = fixture.get("id").and_then(Value::as_str) else { continue };
+        let channels = fixture
+            .get("channels")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(number, name)| {
+                let number: u16 = number.parse().ok()?;
+                Some((name.as_str()?.to_lowercase(), number.checked_sub(1)?))
+            })
+            .collect();
+        offsets.insert(id.to_string(), channels);
+    }
+    offsets
+}
+
+/// Turn `{fixture: {attribute: value}}` into channel-keyed values, noting anything dropped
+fn convert_values(
+    values: &Map<String, Value>,
+    offsets: &HashMap<String, HashMap<String, u16>>,
+    path: &str,
+    changes: &mut Vec<String>,
+) -> Map<String, Value> {
+    let mut result = Map::new();
+    for (fixture_id, attributes) in values {
+        for (attribute, value) in attributes.as_object().into_iter().flatten() {
+            let offset = offsets.get(fixture_id).and_then(|o| o.get(&attribute.to_lowercase()));
+            match offset {
+                Some(offset) => {
+                    result.insert(channel_key(fixture_id, *offset), value.clone());
+                }
+                None => changes.push(format!(
+                    "{}.{}.{}: dropped, fixture has no '{}' channel",
+                    path, fixture_id, attribute, attribute
+                )),
+            }
+        }
+    }
+    result
+}
+
+/// Format 0 -> 1. demo-show.json stored one cue per executor with values by attribute
+/// name, and chases in `effects`.
+fn cue_model_v1(root: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    let offsets = attribute_offsets(root);
+
+    let legacy_cues = match root.remove("cues") {
+        Some(Value::Array(cues)) => cues,
+        _ => Vec::new(),
+    };
+    for (i, legacy) in legacy_cues.iter().enumerate() {
+        let name = legacy.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+        let Some(executor) = legacy.get("executor").and_then(Value::as_u64) else {
+            changes.push(format!("cues[{}] ('{}'): dropped, no executor", i, name));
+            continue;
+        };
+        let fade = legacy.get("fade_time").and_then(Value::as_f64).unwrap_or(crate::cue_engine::DEFAULT_FADE_TIME);
+        let values = legacy
+            .get("values")
+            .and_then(Value::as_object)
+            .map(|v| convert_values(v, &offsets, &format!("cues[{}].values", i), changes))
+            .unwrap_or_default();
+
+        let cue_lists = array_mut(root, "cue_lists");
+        let index = match cue_lists.iter().position(|l| l["number"].as_u64() == Some(executor)) {
+            Some(index) => index,
+            None => {
+                cue_lists.push(json!({ "number": executor, "name": name, "cues": [] }));
+                cue_lists.len() - 1
+            }
+        };
+        if !cue_lists[index]["cues"].is_array() {
+            cue_lists[index]["cues"] = json!([]);
+        }
+        let cues = cue_lists[index]["cues"].as_array_mut().expect("just made an array");
+        let number = cues.len() + 1;
+        cues.push(json!({ "number": number as f64, "name": name, "values": values, "timing": { "fade": fade } }));
+        changes.push(format!("cues[{}] ('{}'): now cue {} in cue list {}", i, name, number, executor));
+
+        let executors = array_mut(root, "executors");
+        if !executors.iter().any(|e| e["number"].as_u64() == Some(executor)) {
+            executors.push(json!({ "number": executor, "cue_list": executor, "name": name }));
+            changes.push(format!("executor {}: plays cue list {}", executor, executor));
+        }
+    }
+
+    let effects = match root.remove("effects") {
+        Some(Value::Array(effects)) => effects,
+        _ => Vec::new(),
+    };
+    let mut kept = Vec::new();
+    for (i, effect) in effects.into_iter().enumerate() {
+        if effect.get("form").is_some() {
+            kept.push(effect);
+            continue;
+        }
+        let name = effect.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+        let Some(steps) = effect.get("steps").and_then(Value::as_array) else {
+            changes.push(format!("effects[{}] ('{}'): dropped, unsupported effect", i, name));
+            continue;
+        };
+
+        // Chase steps apply the same attribute values to every fixture in the chase
+        let fixtures: Vec<&str> = effect
+            .get("fixtures")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let steps: Vec<Value> = steps
+            .iter()
+            .enumerate()
+            .map(|(s, step)| {
+                let values: Map<String, Value> = fixtures.iter().map(|f| (f.to_string(), step.clone())).collect();
+                let path = format!("effects[{}].steps[{}]", i, s);
+                json!({ "values": convert_values(&values, &offsets, &path, changes) })
+            })
+            .collect();
+        let speed = effect.get("speed").and_then(Value::as_f64).unwrap_or(1.0);
+
+        let chasers = array_mut(root, "chasers");
+        let id = chasers.iter().filter_map(|c| c["id"].as_u64()).max().unwrap_or(0) + 1;
+        chasers.push(json!({ "id": id, "name": name, "steps": steps, "bpm": (speed * 60.0).clamp(1.0, 999.0) }));
+        changes.push(format!("effects[{}] ('{}'): now chaser {}", i, name, id));
+    }
+    root.insert("effects".to_string(), Value::Array(kept));
+}
+
+/// Format 1 -> 2. Universes are numbered from 1 in the file, like the console shows them.
+fn universe_numbering_v2(root: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    let mut renumbered = 0;
+    for fixture in array_mut(root, "fixtures").iter_mut().filter_map(Value::as_object_mut) {
+        if let Some(address) = fixture.remove("address") {
+            fixture.insert("dmx_address".to_string(), address);
+        }
+        if let Some(universe) = fixture.get("universe").and_then(Value::as_u64) {
+            fixture.insert("universe".to_string(), json!(universe + 1));
+            renumbered += 1;
+        }
+    }
+    if renumbered > 0 {
+        changes.push(format!("fixtures: {} fixtures renumbered to 1-based universes", renumbered));
+    }
+}
+
+/// Format 2 -> 3. Palettes were grouped by feature name with string ids; presets are
+/// numbered per feature set in the order the palettes were listed.
+fn presets_v3(root: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    let palettes = match root.remove("palettes") {
+        Some(Value::Object(palettes)) => palettes,
+        _ => return,
+    };
+    for (feature, list) in palettes {
+        let Some(number) = FeatureSet::from_name(&feature).and_then(FeatureSet::number) else {
+            changes.push(format!("palettes.{}: dropped, '{}' has no presets", feature, feature));
+            continue;
+        };
+        for (i, palette) in list.as_array().into_iter().flatten().enumerate() {
+            let name = palette.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+            let id = format!("{}.{}", number, i + 1);
+            let values = palette.get("values").cloned().unwrap_or_else(|| json!({}));
+            array_mut(root, "presets").push(json!({ "id": id, "name": name, "values": values }));
+            changes.push(format!("palettes.{}[{}] ('{}'): now global preset {}", feature, i, name, id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_show_migrates() {
+        let mut doc: Value = serde_json::from_str(include_str!("../../demo-show.json")).unwrap();
+        let report = migrate(&mut doc).unwrap().unwrap();
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.written_by.as_deref(), Some("0.1.0"));
+
+        assert_eq!(doc["format_version"], json!(SHOW_FORMAT_VERSION));
+        assert!(doc.get("cues").is_none());
+        assert_eq!(doc["cue_lists"].as_array().unwrap().len(), 4);
+        assert_eq!(doc["cue_lists"][0]["cues"][0]["values"]["wash1:1"], json!(255));
+        assert_eq!(doc["chasers"].as_array().unwrap().len(), 1);
+        assert_eq!(doc["fixtures"][0]["universe"], json!(1));
+        assert!(doc["fixtures"][0].get("address").is_none());
+        assert!(doc.get("palettes").is_none());
+        let presets = doc["presets"].as_array().unwrap();
+        assert!(presets.iter().any(|p| p["id"] == json!("3.1") && p["name"] == json!("Red")));
+
+        // Migrating again is a no-op
+        assert!(migrate(&mut doc).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_newer_format_is_rejected() {
+        let mut doc = json!({ "format_version": SHOW_FORMAT_VERSION + 1 });
+        assert!(migrate(&mut doc).is_err());
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use xml::reader::{EventReader, XmlEvent};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::gdtf::{self, GdtfDescription};
+use crate::patch;
+use crate::show_import::{unique_id, ImportReport};
+use crate::{Fixture, FixturePosition, GdtfFixtureType};
+
+const SCENE_FILE: &str = "GeneralSceneDescription.xml";
+
+/// Fixture as described in an MVR scene
+#[derive(Debug, Clone, Default)]
+struct SceneFixture {
+    name: String,
+    fixture_id: String,
+    gdtf_spec: String,
+    gdtf_mode: String,
+    address: Option<(u8, u16)>,
+    /// MVR coordinates in millimetres, Z up
+    translation: [f32; 3],
+}
+
+/// Translation part of an MVR matrix "{ux,uy,uz}{vx,vy,vz}{wx,wy,wz}{ox,oy,oz}"
+fn parse_translation(matrix: &str) -> Option<[f32; 3]> {
+    let last = matrix.rsplit('{').next()?.trim_end().trim_end_matches('}');
+    let values: Vec<f32> = last.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+    (values.len() == 3).then(|| [values[0], values[1], values[2]])
+}
+
+/// DMX address from an absolute value ("1025") or "universe.address" ("3.1").
+/// Returns the 0-based universe and the channel.
+fn parse_address(text: &str) -> Option<(u8, u16)> {
+    let (universe, channel) = match text.trim().split_once('.') {
+        Some((universe, channel)) => (universe.parse::<u32>().ok()?.checked_sub(1)?, channel.parse::<u32>().ok()?),
+        None => {
+            let absolute = text.trim().parse::<u32>().ok()?.checked_sub(1)?;
+            (absolute / 512, absolute % 512 + 1)
+        }
+    };
+    (universe < 256 && (1..=512).contains(&channel)).then_some((universe as u8, channel as u16))
+}
+
+/// Collect the fixtures of a GeneralSceneDescription. Group transforms are applied
+/// as translations.
+fn parse_scene(xml: &str) -> Result<Vec<SceneFixture>, String> {
+    let mut fixtures = Vec::new();
+    let mut current: Option<SceneFixture> = None;
+    let mut groups: Vec<[f32; 3]> = Vec::new();
+    let mut element = String::new();
+    let mut address_read = false;
+
+    for event in EventReader::new(xml.as_bytes()) {
+        match event.map_err(|e| format!("Invalid MVR scene: {}", e))? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                element = name.local_name.clone();
+                match element.as_str() {
+                    "Fixture" => {
+                        let name = attributes
+                            .iter()
+                            .find(|a| a.name.local_name == "name")
+                            .map(|a| a.value.clone())
+                            .unwrap_or_default();
+                        current = Some(SceneFixture {
+                            name,
+                            ..SceneFixture::default()
+                        });
+                        address_read = false;
+                    }
+                    "GroupObject" => groups.push([0.0; 3]),
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(text) => {
+                if let Some(fixture) = current.as_mut() {
+                    match element.as_str() {
+                        "Matrix" => fixture.translation = parse_translation(&text).unwrap_or_default(),
+                        "GDTFSpec" => fixture.gdtf_spec = text.trim().to_string(),
+                        "GDTFMode" => fixture.gdtf_mode = text.trim().to_string(),
+                        "FixtureID" => fixture.fixture_id = text.trim().to_string(),
+                        // Only the first break is patched
+                        "Address" if !address_read => {
+                            fixture.address = parse_address(&text);
+                            address_read = true;
+                        }
+                        _ => {}
+                    }
+                } else if element == "Matrix" {
+                    if let (Some(group), Some(translation)) = (groups.last_mut(), parse_translation(&text)) {
+                        *group = translation;
+                    }
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                element.clear();
+                match name.local_name.as_str() {
+                    "Fixture" => {
+                        if let Some(mut fixture) = current.take() {
+                            for group in &groups {
+                                for (axis, offset) in fixture.translation.iter_mut().zip(group) {
+                                    *axis += offset;
+                                }
+                            }
+                            fixtures.push(fixture);
+                        }
+                    }
+                    "GroupObject" => {
+                        groups.pop();
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(fixtures)
+}
+
+/// MVR is Z-up in millimetres, show positions are Y-up in metres
+fn to_position(translation: [f32; 3]) -> FixturePosition {
+    FixturePosition {
+        x: translation[0] / 1000.0,
+        y: translation[2] / 1000.0,
+        z: translation[1] / 1000.0,
+    }
+}
+
+fn to_translation(position: &FixturePosition) -> [f32; 3] {
+    [position.x * 1000.0, position.z * 1000.0, position.y * 1000.0]
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Name to extract an embedded GDTF under: the last part of the spec, so a crafted MVR
+/// can't write outside the GDTF folder. Specs climbing up with ".." or rooted
+/// somewhere else are refused.
+fn gdtf_file_name(spec: &str) -> Option<String> {
+    let rooted = spec.starts_with(['/', '\\']) || spec.contains(':');
+    if rooted || spec.split(['/', '\\']).any(|part| part == "..") {
+        return None;
+    }
+    let name = spec.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() || name == "." {
+        return None;
+    }
+    Some(if name.to_lowercase().ends_with(".gdtf") { name.to_string() } else { format!("{}.gdtf", name) })
+}
+
+/// Fixtures and fixture types read from an MVR file
+pub struct MvrImport {
+    pub fixtures: Vec<Fixture>,
+    pub fixture_types: Vec<GdtfFixtureType>,
+}
+
+/// Read an MVR file. Embedded GDTFs become library entries (one per mode) and are
+/// extracted to `gdtf_dir` when given, so the patch can be exported again.
+pub fn import_mvr<R: Read + Seek>(
+    reader: R,
+    library: &HashMap<String, GdtfFixtureType>,
+    existing: &HashMap<String, Fixture>,
+    gdtf_dir: Option<&Path>,
+    report: &mut ImportReport,
+) -> Result<MvrImport, String> {
+    let mut archive = ZipArchive::new(reader).map_err(|e| format!("Not an MVR file: {}", e))?;
+    let scene = read_entry(&mut archive, SCENE_FILE).ok_or_else(|| format!("MVR file has no {}", SCENE_FILE))?;
+    let scene = parse_scene(&String::from_utf8_lossy(&scene))?;
+
+    // Embedded GDTFs, parsed once per spec
+    let mut descriptions: HashMap<String, (GdtfDescription, Option<PathBuf>)> = HashMap::new();
+    for spec in scene.iter().map(|f| f.gdtf_spec.clone()).collect::<HashSet<_>>() {
+        if spec.is_empty() {
+            continue;
+        }
+        let Some(file_name) = gdtf_file_name(&spec) else {
+            report.warnings.push(format!("{}: GDTF file name points outside the MVR", spec));
+            continue;
+        };
+        let entry = if spec.to_lowercase().ends_with(".gdtf") { spec.clone() } else { format!("{}.gdtf", spec) };
+        let Some(bytes) = read_entry(&mut archive, &entry) else {
+            report.warnings.push(format!("{} is not embedded in the MVR", entry));
+            continue;
+        };
+        let description = match gdtf::read_gdtf(Cursor::new(&bytes)) {
+            Ok(description) => description,
+            Err(e) => {
+                report.warnings.push(format!("{}: {}", file_name, e));
+                continue;
+            }
+        };
+        let path = match gdtf_dir {
+            Some(dir) => {
+                let path = dir.join(&file_name);
+                fs::create_dir_all(dir).and_then(|_| fs::write(&path, &bytes)).map_err(|e| e.to_string())?;
+                Some(path)
+            }
+            None => None,
+        };
+        descriptions.insert(spec, (description, path));
+    }
+
+    let mut taken: HashSet<String> = existing.keys().cloned().collect();
+    // Existing and accepted fixtures, so overlapping ones are left out like in the patch
+    let mut patched = existing.clone();
+    let mut fixture_types: HashMap<String, GdtfFixtureType> = HashMap::new();
+    let mut fixtures = Vec::new();
+
+    for scene_fixture in scene {
+        let label = if scene_fixture.name.is_empty() { scene_fixture.fixture_id.clone() } else { scene_fixture.name.clone() };
+        let mode = (!scene_fixture.gdtf_mode.is_empty()).then_some(scene_fixture.gdtf_mode.as_str());
+
+        let (fixture_type, gdtf_file) = match descriptions.get(&scene_fixture.gdtf_spec) {
+            Some((description, path)) => {
+                for fixture_type in description.fixture_types() {
+                    fixture_types.entry(fixture_type.name.clone()).or_insert(fixture_type);
+                }
+                (description.fixture_type(mode), path.clone())
+            }
+            // Fall back to a library entry with the same name as the spec
+            None => {
+                let name = scene_fixture.gdtf_spec.trim_end_matches(".gdtf");
+                (library.get(name).cloned(), None)
+            }
+        };
+        let Some(fixture_type) = fixture_type else {
+            report.unmapped(None, &label, format!("fixture type '{}' not found", scene_fixture.gdtf_spec));
+            continue;
+        };
+        let Some((universe, address)) = scene_fixture.address else {
+            report.unmapped(None, &label, "no DMX address");
+            continue;
+        };
+        let channel_count = fixture_type.channels.iter().map(|c| c.offset + 1).max().unwrap_or(1);
+        if address as u32 + channel_count as u32 - 1 > 512 {
+            report.unmapped(None, &label, format!("{} channels from address {} run past channel 512", channel_count, address));
+            continue;
+        }
+
+        let id = if !scene_fixture.fixture_id.is_empty() && !taken.contains(&scene_fixture.fixture_id) {
+            scene_fixture.fixture_id.clone()
+        } else {
+            unique_id(&label, &taken)
+        };
+        let fixture = Fixture {
+            id,
+            name: label,
+            fixture_type: fixture_type.name.clone(),
+            dmx_address: address,
+            universe,
+            channel_count,
+            gdtf_file: gdtf_file.map(|p| p.display().to_string()),
+            is_video: None,
+            video_source_type: None,
+            video_source_path: None,
+            position: Some(to_position(scene_fixture.translation)),
+        };
+        if let Err(e) = patch::check_address(&fixture, &patched) {
+            report.unmapped(None, &fixture.name, e);
+            continue;
+        }
+
+        report.imported.push(format!("{} ({}) at {}.{}", fixture.name, fixture_type.name, universe as u16 + 1, address));
+        taken.insert(fixture.id.clone());
+        patched.insert(fixture.id.clone(), fixture.clone());
+        fixtures.push(fixture);
+    }
+
+    Ok(MvrImport {
+        fixtures,
+        fixture_types: fixture_types.into_values().collect(),
+    })
+}
+
+/// Stable UUID-formatted id for a fixture, so re-exports keep their identity in visualisers
+fn fixture_uuid(id: &str) -> String {
+    // FNV-1a, run twice with different offsets for 128 bits
+    let hash = |seed: u64| {
+        id.bytes().fold(seed, |h, b| (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3))
+    };
+    let (a, b) = (hash(0xCBF2_9CE4_8422_2325), hash(0x6C62_272E_07BB_0142));
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:04X}-{:012X}",
+        a >> 32,
+        (a >> 16) & 0xFFFF,
+        a & 0xFFFF,
+        b >> 48,
+        b & 0xFFFF_FFFF_FFFF
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Write the patch as an MVR file, embedding the GDTF of every fixture that has one on disk
+pub fn export_mvr<W: Write + Seek>(
+    writer: W,
+    fixtures: &HashMap<String, Fixture>,
+    library: &HashMap<String, GdtfFixtureType>,
+) -> Result<usize, String> {
+    let mut sorted: Vec<&Fixture> = fixtures.values().collect();
+    sorted.sort_by(|a, b| (a.universe, a.dmx_address, &a.id).cmp(&(b.universe, b.dmx_address, &b.id)));
+
+    let mut embedded: HashMap<String, PathBuf> = HashMap::new();
+    let mut children = String::new();
+    for fixture in &sorted {
+        let gdtf_path = fixture.gdtf_file.as_ref().map(PathBuf::from).filter(|p| p.is_file());
+        let spec = match &gdtf_path {
+            Some(path) => {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                embedded.insert(name.clone(), path.clone());
+                name
+            }
+            None => format!("{}.gdtf", fixture.fixture_type),
+        };
+        let mode = library
+            .get(&fixture.fixture_type)
+            .and_then(|t| t.modes.first().cloned())
+            .unwrap_or_default();
+        let [x, y, z] = fixture.position.as_ref().map(to_translation).unwrap_or_default();
+        let absolute = fixture.universe as u32 * 512 + fixture.dmx_address as u32;
+
+        children.push_str(&format!(
+            concat!(
+                "          <Fixture name=\"{}\" uuid=\"{}\">\n",
+                "            <Matrix>{{1,0,0}}{{0,1,0}}{{0,0,1}}{{{},{},{}}}</Matrix>\n",
+                "            <GDTFSpec>{}</GDTFSpec>\n",
+                "            <GDTFMode>{}</GDTFMode>\n",
+                "            <FixtureID>{}</FixtureID>\n",
+                "            <UnitNumber>0</UnitNumber>\n",
+                "            <Addresses><Address break=\"0\">{}</Address></Addresses>\n",
+                "          </Fixture>\n"
+            ),
+            escape(&fixture.name),
+            fixture_uuid(&fixture.id),
+            x,
+            y,
+            z,
+            escape(&spec),
+            escape(&mode),
+            escape(&fixture.id),
+            absolute
+        ));
+    }
+
+    let scene = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n",
+            "<GeneralSceneDescription verMajor=\"1\" verMinor=\"5\" provider=\"RoControl\" providerVersion=\"{}\">\n",
+            "  <Scene>\n",
+            "    <Layers>\n",
+            "      <Layer name=\"Patch\" uuid=\"{}\">\n",
+            "        <ChildList>\n",
+            "{}",
+            "        </ChildList>\n",
+            "      </Layer>\n",
+            "    </Layers>\n",
+            "  </Scene>\n",
+            "</GeneralSceneDescription>\n"
+        ),
+        env!("CARGO_PKG_VERSION"),
+        fixture_uuid("layer:patch"),
+        children
+    );
+
+    let mut zip = ZipWriter::new(writer);
+    let options = FileOptions::default();
+    zip.start_file(SCENE_FILE, options).map_err(|e| e.to_string())?;
+    zip.write_all(scene.as_bytes()).map_err(|e| e.to_string())?;
+    for (name, path) in &embedded {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        zip.start_file(name.as_str(), options).map_err(|e| e.to_string())?;
+        zip.write_all(&bytes).map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(sorted.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("1025"), Some((2, 1)));
+        assert_eq!(parse_address("3.1"), Some((2, 1)));
+        assert_eq!(parse_address("512"), Some((0, 512)));
+        assert_eq!(parse_address("0"), None);
+    }
+
+    #[test]
+    fn test_export_then_import_round_trips() {
+        let fixture_type = GdtfFixtureType {
+            name: "Par".to_string(),
+            manufacturer: "Generic".to_string(),
+            channels: vec![crate::FixtureChannel {
+                name: "Dimmer".to_string(),
+                offset: 0,
+                default_value: 0,
+                channel_type: "Dimmer".to_string(),
+            }],
+            modes: vec!["1ch".to_string()],
+        };
+        let library: HashMap<String, GdtfFixtureType> = [(fixture_type.name.clone(), fixture_type)].into_iter().collect();
+        let fixture = Fixture {
+            id: "par1".to_string(),
+            name: "Par 1".to_string(),
+            fixture_type: "Par".to_string(),
+            dmx_address: 10,
+            universe: 1,
+            channel_count: 1,
+            gdtf_file: None,
+            is_video: None,
+            video_source_type: None,
+            video_source_path: None,
+            position: Some(FixturePosition { x: 1.5, y: 4.0, z: -2.0 }),
+        };
+        let fixtures: HashMap<String, Fixture> = [(fixture.id.clone(), fixture)].into_iter().collect();
+
+        let mut buffer = Cursor::new(Vec::new());
+        export_mvr(&mut buffer, &fixtures, &library).unwrap();
+
+        let mut report = ImportReport::default();
+        let imported = import_mvr(Cursor::new(buffer.into_inner()), &library, &HashMap::new(), None, &mut report).unwrap();
+        let fixture = &imported.fixtures[0];
+        assert_eq!((fixture.id.as_str(), fixture.universe, fixture.dmx_address), ("par1", 1, 10));
+        let position = fixture.position.as_ref().unwrap();
+        assert_eq!((position.x, position.y, position.z), (1.5, 4.0, -2.0));
+    }
+
+    #[test]
+    fn test_embedded_gdtf_stays_in_gdtf_dir() {
+        assert_eq!(gdtf_file_name("Acme@Spot"), Some("Acme@Spot.gdtf".to_string()));
+        assert_eq!(gdtf_file_name("gdtf/Spot.gdtf"), Some("Spot.gdtf".to_string()));
+        assert_eq!(gdtf_file_name("../../x.gdtf"), None);
+        assert_eq!(gdtf_file_name("/etc/x.gdtf"), None);
+        assert_eq!(gdtf_file_name("C:\\x.gdtf"), None);
+
+        let mut gdtf = ZipWriter::new(Cursor::new(Vec::new()));
+        gdtf.start_file("description.xml", FileOptions::default()).unwrap();
+        gdtf.write_all(
+            br#"<GDTF><FixtureType Name="Spot" Manufacturer="Acme"><DMXModes><DMXMode Name="Basic">
+            <DMXChannels><DMXChannel DMXBreak="1" Offset="1"><LogicalChannel Attribute="Dimmer"/></DMXChannel>
+            </DMXChannels></DMXMode></DMXModes></FixtureType></GDTF>"#,
+        )
+        .unwrap();
+        let gdtf = gdtf.finish().unwrap().into_inner();
+
+        let fixture = |spec: &str, id: u32| {
+            format!(
+                "<Fixture name=\"F{id}\" uuid=\"{id}\"><GDTFSpec>{spec}</GDTFSpec><GDTFMode>Basic</GDTFMode>\
+                 <FixtureID>{id}</FixtureID><Addresses><Address break=\"0\">{id}</Address></Addresses></Fixture>"
+            )
+        };
+        let scene = format!(
+            "<GeneralSceneDescription><Scene><Layers><Layer name=\"Patch\"><ChildList>{}{}</ChildList></Layer></Layers></Scene></GeneralSceneDescription>",
+            fixture("../../evil.gdtf", 1),
+            fixture("gdtf/Spot.gdtf", 2)
+        );
+        let mut mvr = ZipWriter::new(Cursor::new(Vec::new()));
+        mvr.start_file(SCENE_FILE, FileOptions::default()).unwrap();
+        mvr.write_all(scene.as_bytes()).unwrap();
+        for name in ["../../evil.gdtf", "gdtf/Spot.gdtf"] {
+            mvr.start_file(name, FileOptions::default()).unwrap();
+            mvr.write_all(&gdtf).unwrap();
+        }
+        let mvr = mvr.finish().unwrap().into_inner();
+
+        let root = std::env::temp_dir().join(format!("rocontrol-mvr-{}", std::process::id()));
+        let gdtf_dir = root.join("a").join("b").join("GDTF");
+        let mut report = ImportReport::default();
+        import_mvr(Cursor::new(mvr), &HashMap::new(), &HashMap::new(), Some(&gdtf_dir), &mut report).unwrap();
+
+        assert!(report.warnings.iter().any(|w| w.contains("../../evil.gdtf")));
+        assert!(!root.join("a").join("evil.gdtf").exists());
+        assert!(gdtf_dir.join("Spot.gdtf").is_file());
+        fs::remove_dir_all(&root).unwrap();
+    }
+    #[test]
+    fn test_overlapping_fixtures_are_left_out() {
+        let fixture_type = GdtfFixtureType {
+            name: "Par".to_string(),
+            manufacturer: "Generic".to_string(),
+            channels: (0..4)
+                .map(|offset| crate::FixtureChannel {
+                    name: format!("Channel {}", offset + 1),
+                    offset,
+                    default_value: 0,
+                    channel_type: "Dimmer".to_string(),
+                })
+                .collect(),
+            modes: vec!["4ch".to_string()],
+        };
+        let library: HashMap<String, GdtfFixtureType> = [(fixture_type.name.clone(), fixture_type)].into_iter().collect();
+        let existing = Fixture {
+            id: "par1".to_string(),
+            name: "Par 1".to_string(),
+            fixture_type: "Par".to_string(),
+            dmx_address: 1,
+            universe: 0,
+            channel_count: 4,
+            gdtf_file: None,
+            is_video: None,
+            video_source_type: None,
+            video_source_path: None,
+            position: None,
+        };
+        let existing: HashMap<String, Fixture> = [(existing.id.clone(), existing)].into_iter().collect();
+
+        // 3 overlaps the patch, 7 overlaps the fixture at 5 from the same file
+        let fixtures: String = [3, 5, 7]
+            .iter()
+            .map(|address| {
+                format!(
+                    "<Fixture name=\"F{address}\" uuid=\"{address}\"><GDTFSpec>Par.gdtf</GDTFSpec>\
+                     <Addresses><Address break=\"0\">{address}</Address></Addresses></Fixture>"
+                )
+            })
+            .collect();
+        let scene = format!(
+            "<GeneralSceneDescription><Scene><Layers><Layer name=\"Patch\"><ChildList>{}</ChildList></Layer></Layers></Scene></GeneralSceneDescription>",
+            fixtures
+        );
+        let mut mvr = ZipWriter::new(Cursor::new(Vec::new()));
+        mvr.start_file(SCENE_FILE, FileOptions::default()).unwrap();
+        mvr.write_all(scene.as_bytes()).unwrap();
+        let mvr = mvr.finish().unwrap().into_inner();
+
+        let mut report = ImportReport::default();
+        let imported = import_mvr(Cursor::new(mvr), &library, &existing, None, &mut report).unwrap();
+        assert_eq!(imported.fixtures.iter().map(|f| f.dmx_address).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(report.unmapped.iter().map(|u| u.item.as_str()).collect::<Vec<_>>(), vec!["F3", "F7"]);
+    }
+}
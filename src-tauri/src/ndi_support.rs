@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -5,7 +6,7 @@ use mdns_sd::{ServiceDaemon, ServiceEvent};
 use tokio::sync::broadcast;
 
 /// NDI Source information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct NdiSource {
     pub name: String,
     pub address: String,
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::attributes::{channel_key, FeatureSet, PatchLookup};
+use crate::cli;
+use crate::ws_protocol::Event;
+use crate::AppState;
+
+/// DMX refresh rate in frames per second (matches `network_config.artnet.refresh_rate`)
+pub const DEFAULT_REFRESH_RATE: f64 = 44.0;
+
+/// Output that wins over playback, the programmer and effects
+#[derive(Debug, Default)]
+pub struct OutputOverrides {
+    /// Active selection's intensity at full
+    pub highlight: bool,
+    /// Channel key -> value held regardless of everything else
+    pub parked: HashMap<String, u8>,
+}
+
+/// Start the backend output loop. Every frame it evaluates cue playback, layers the
+/// programmer and effects on top and transmits all universes, so fades and effects
+/// keep running even when the UI is busy.
+pub fn spawn(state: AppState) {
+    thread::spawn(move || {
+        let frame = Duration::from_secs_f64(1.0 / DEFAULT_REFRESH_RATE);
+        let mut last_written: HashSet<(u8, u16)> = HashSet::new();
+        let mut feedback = Feedback::default();
+        let mut next_frame = Instant::now();
+
+        println!("[Output] Output loop running at {} Hz", DEFAULT_REFRESH_RATE);
+
+        loop {
+            if let Err(e) = render_frame(&state, &mut last_written) {
+                eprintln!("[Output] Frame error: {}", e);
+                state.health.report_error("output", &e);
+            }
+            if let Err(e) = publish_feedback(&state, &mut feedback) {
+                eprintln!("[Output] Feedback error: {}", e);
+            }
+
+            next_frame += frame;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                // Running behind - don't try to catch up with a burst of frames
+                next_frame = now;
+            }
+        }
+    });
+}
+
+/// Compute one frame of output and send it
+fn render_frame(state: &AppState, last_written: &mut HashSet<(u8, u16)>) -> Result<(), String> {
+    let levels: HashMap<(u8, u16), u8> = {
+        let fixtures = state.fixtures.lock().map_err(|e| e.to_string())?;
+        let library = state.fixture_library.lock().map_err(|e| e.to_string())?;
+        let lookup = PatchLookup::new(&fixtures, &library);
+
+        let (t, mut values, cue_effects) = {
+            let mut cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+            let t = cue_engine.now();
+            cue_engine.update(t, &|key| lookup.feature_set(key));
+            (t, cue_engine.render(t), cue_engine.active_effects())
+        };
+
+        // Chasers run on top of cue playback
+        state.chaser_engine.lock().map_err(|e| e.to_string())?.render(&mut values);
+
+        // Programmer values always win over playback
+        let programmer = state.programmer.lock().map_err(|e| e.to_string())?;
+        for (key, value) in programmer.iter() {
+            values.insert(key.clone(), *value as f32);
+        }
+
+        // Effects modulate the final base values so relative effects follow the look
+        let effects = state.effects_engine.lock().map_err(|e| e.to_string())?;
+        effects.render(t, &cue_effects, &mut values, &lookup);
+
+        let overrides = state.overrides.lock().map_err(|e| e.to_string())?;
+        if overrides.highlight {
+            for fixture_id in state.selection.lock().map_err(|e| e.to_string())?.active() {
+                for channel in lookup.channels(&fixture_id) {
+                    if FeatureSet::from_channel_type(&channel.channel_type) == FeatureSet::Intensity {
+                        values.insert(channel_key(&fixture_id, channel.offset), 255.0);
+                    }
+                }
+            }
+        }
+        // Parked channels always win
+        for (key, value) in &overrides.parked {
+            values.insert(key.clone(), *value as f32);
+        }
+
+        values
+            .iter()
+            .filter_map(|(key, value)| {
+                lookup
+                    .address(key)
+                    .map(|address| (address, value.round().clamp(0.0, 255.0) as u8))
+            })
+            .collect()
+    };
+
+    let mut engine = state.dmx_engine.lock().map_err(|e| e.to_string())?;
+
+    // Channels that are no longer driven by playback or the programmer drop to zero
+    for (universe, channel) in last_written.iter() {
+        if !levels.contains_key(&(*universe, *channel)) {
+            engine.set_channel(*universe, *channel, 0);
+        }
+    }
+    for ((universe, channel), value) in &levels {
+        engine.set_channel(*universe, *channel, *value);
+    }
+    *last_written = levels.into_keys().collect();
+
+    let universes: Vec<u8> = engine.universes.keys().copied().collect();
+    let ready = engine.output_ready();
+    for universe in universes {
+        // Transmission errors are transient (e.g. interface down) - keep the loop alive
+        match engine.send_dmx(universe) {
+            Ok(()) if ready => state.health.packet_sent(universe),
+            Ok(()) => {}
+            Err(e) => state.health.report_error("output", format!("Universe {}: {}", universe as u16 + 1, e)),
+        }
+    }
+
+    Ok(())
+}
+
+/// What web clients were last told, so only changes are published
+#[derive(Default)]
+struct Feedback {
+    cues: HashMap<u32, Option<f64>>,
+    programmer: HashMap<String, u8>,
+}
+
+/// Publish cue and programmer changes since the last frame. Output levels are streamed
+/// to the clients that subscribed to them.
+fn publish_feedback(state: &AppState, feedback: &mut Feedback) -> Result<(), String> {
+    if state.events.receiver_count() == 0 {
+        return Ok(());
+    }
+
+    let cues: Vec<(u32, Option<f64>)> = {
+        let cue_engine = state.cue_engine.lock().map_err(|e| e.to_string())?;
+        let t = cue_engine.now();
+        cue_engine.lists().map(|list| (list.number, list.status(t).current_cue)).collect()
+    };
+    for (cue_list, cue) in cues {
+        if feedback.cues.insert(cue_list, cue).flatten() != cue {
+            let _ = state.events.send(Event::CueFired { cue_list, cue });
+        }
+    }
+
+    let programmer = state.programmer.lock().map_err(|e| e.to_string())?.clone();
+    if programmer != feedback.programmer {
+        feedback.programmer = programmer;
+        let _ = state.events.send(Event::ProgrammerChanged { programmer: cli::programmer_summary(state)? });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{Fixture, FixturePosition};
+
+/// Changes to a patched fixture from the web API. Fields left out stay as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct FixtureUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Art-Net numbering, 0-based
+    #[serde(default)]
+    pub universe: Option<u8>,
+    /// First DMX channel, 1-512
+    #[serde(default)]
+    pub dmx_address: Option<u16>,
+    #[serde(default)]
+    pub position: Option<FixturePosition>,
+}
+
+/// Check a fixture fits in its universe and doesn't share channels with another one
+pub fn check_address(fixture: &Fixture, fixtures: &HashMap<String, Fixture>) -> Result<(), String> {
+    let last = fixture.dmx_address as u32 + fixture.channel_count.max(1) as u32 - 1;
+    if fixture.dmx_address == 0 || last > 512 {
+        return Err(format!(
+            "Address {} doesn't fit {} channels in a universe",
+            fixture.dmx_address, fixture.channel_count
+        ));
+    }
+    let overlapping = fixtures.values().find(|other| {
+        let other_last = other.dmx_address as u32 + other.channel_count.max(1) as u32 - 1;
+        other.id != fixture.id
+            && other.universe == fixture.universe
+            && fixture.dmx_address as u32 <= other_last
+            && other.dmx_address as u32 <= last
+    });
+    match overlapping {
+        Some(other) => Err(format!(
+            "{} would overlap {} at {}.{}",
+            fixture.id,
+            other.id,
+            other.universe as u16 + 1,
+            other.dmx_address
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Patch a new fixture
+pub fn add(fixtures: &mut HashMap<String, Fixture>, fixture: Fixture) -> Result<(), String> {
+    if fixture.id.trim().is_empty() {
+        return Err("Fixture id is empty".to_string());
+    }
+    if fixtures.contains_key(&fixture.id) {
+        return Err(format!("Fixture {} is already patched", fixture.id));
+    }
+    check_address(&fixture, fixtures)?;
+    fixtures.insert(fixture.id.clone(), fixture);
+    Ok(())
+}
+
+/// Patch several fixtures at once. If any of them doesn't fit, none are added.
+pub fn add_all(fixtures: &mut HashMap<String, Fixture>, new: Vec<Fixture>) -> Result<(), String> {
+    let mut patched = fixtures.clone();
+    for fixture in new {
+        add(&mut patched, fixture)?;
+    }
+    *fixtures = patched;
+    Ok(())
+}
+
+/// Rename, repatch or move a fixture. Nothing changes if the new address doesn't fit.
+pub fn update(fixtures: &mut HashMap<String, Fixture>, id: &str, update: FixtureUpdate) -> Result<Fixture, String> {
+    let mut fixture = fixtures.get(id).cloned().ok_or_else(|| format!("Fixture {} not found", id))?;
+    if let Some(name) = update.name {
+        fixture.name = name;
+    }
+    if let Some(universe) = update.universe {
+        fixture.universe = universe;
+    }
+    if let Some(address) = update.dmx_address {
+        fixture.dmx_address = address;
+    }
+    if let Some(position) = update.position {
+        fixture.position = Some(position);
+    }
+    check_address(&fixture, fixtures)?;
+    fixtures.insert(id.to_string(), fixture.clone());
+    Ok(fixture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(id: &str, universe: u8, dmx_address: u16, channel_count: u16) -> Fixture {
+        Fixture {
+            id: id.to_string(),
+            name: format!("Fixture {}", id),
+            fixture_type: "Generic".to_string(),
+            dmx_address,
+            universe,
+            channel_count,
+            gdtf_file: None,
+            is_video: None,
+            video_source_type: None,
+            video_source_path: None,
+            position: None,
+        }
+    }
+
+    #[test]
+    fn test_add_checks_address_and_overlap() {
+        let mut fixtures = HashMap::new();
+        add(&mut fixtures, fixture("1", 0, 1, 16)).unwrap();
+        assert!(add(&mut fixtures, fixture("1", 0, 100, 16)).is_err());
+        assert!(add(&mut fixtures, fixture("2", 0, 16, 4)).unwrap_err().contains("overlap 1"));
+        assert!(add(&mut fixtures, fixture("2", 0, 510, 4)).is_err());
+        add(&mut fixtures, fixture("2", 1, 16, 4)).unwrap();
+        add(&mut fixtures, fixture("3", 0, 17, 4)).unwrap();
+    }
+
+    #[test]
+    fn test_add_all_is_all_or_nothing() {
+        let mut fixtures = HashMap::new();
+        add(&mut fixtures, fixture("1", 0, 1, 8)).unwrap();
+
+        let batch = vec![fixture("2", 0, 9, 8), fixture("3", 0, 17, 8), fixture("1", 0, 25, 8)];
+        assert!(add_all(&mut fixtures, batch).unwrap_err().contains("already patched"));
+        assert_eq!(fixtures.len(), 1);
+
+        add_all(&mut fixtures, vec![fixture("2", 0, 9, 8), fixture("3", 0, 17, 8)]).unwrap();
+        assert_eq!(fixtures.len(), 3);
+    }
+
+    #[test]
+    fn test_update_keeps_fixture_on_bad_address() {
+        let mut fixtures = HashMap::new();
+        add(&mut fixtures, fixture("1", 0, 1, 8)).unwrap();
+        add(&mut fixtures, fixture("2", 0, 9, 8)).unwrap();
+
+        let moved = update(&mut fixtures, "2", FixtureUpdate { dmx_address: Some(5), ..Default::default() });
+        assert!(moved.is_err());
+        assert_eq!(fixtures["2"].dmx_address, 9);
+
+        let moved = update(&mut fixtures, "2", FixtureUpdate { universe: Some(1), dmx_address: Some(5), ..Default::default() });
+        assert_eq!(moved.map(|f| (f.universe, f.dmx_address)), Ok((1, 5)));
+        assert!(update(&mut fixtures, "9", FixtureUpdate::default()).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::attributes::{channel_key, parse_channel_key, FeatureSet, PatchLookup};
+use crate::selection::patch_order;
+use crate::FixtureChannel;
+
+/// Preset address in dot notation: feature set number and preset number ("3.5" is colour 5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PresetId {
+    pub feature_set: FeatureSet,
+    pub number: u32,
+}
+
+impl PresetId {
+    pub fn new(feature_set: FeatureSet, number: u32) -> Result<Self, String> {
+        if feature_set.number().is_none() {
+            return Err(format!("Feature set {:?} has no presets", feature_set));
+        }
+        if number == 0 {
+            return Err("Preset numbers start at 1".to_string());
+        }
+        Ok(PresetId { feature_set, number })
+    }
+
+    /// Parse "3.5" or "color.5"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (set, number) = text
+            .trim()
+            .split_once('.')
+            .ok_or_else(|| format!("Invalid preset '{}', expected feature.preset like 3.5", text))?;
+        let feature_set = match set.parse::<u32>() {
+            Ok(n) => FeatureSet::from_number(n),
+            Err(_) => FeatureSet::from_name(set),
+        }
+        .ok_or_else(|| format!("Unknown feature set '{}' in preset '{}'", set, text))?;
+        let number = number
+            .parse()
+            .map_err(|_| format!("Invalid preset number '{}' in preset '{}'", number, text))?;
+        PresetId::new(feature_set, number)
+    }
+}
+
+impl fmt::Display for PresetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.feature_set.number().unwrap_or(0), self.number)
+    }
+}
+
+impl TryFrom<String> for PresetId {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        PresetId::parse(&text)
+    }
+}
+
+impl From<PresetId> for String {
+    fn from(id: PresetId) -> String {
+        id.to_string()
+    }
+}
+
+/// Stored look for one feature set. Values are kept by attribute name so a preset
+/// applies to any fixture that has the attribute, whatever its channel layout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub id: PresetId,
+    #[serde(default)]
+    pub name: String,
+    /// Attribute -> value for every fixture with that attribute
+    #[serde(default)]
+    pub values: BTreeMap<String, u8>,
+    /// Fixture id -> attribute -> value. Wins over `values` for that fixture.
+    #[serde(default)]
+    pub fixtures: BTreeMap<String, BTreeMap<String, u8>>,
+}
+
+fn attribute_value(values: &BTreeMap<String, u8>, channel: &FixtureChannel) -> Option<u8> {
+    values
+        .iter()
+        .find(|(attribute, _)| {
+            attribute.eq_ignore_ascii_case(&channel.name) || attribute.eq_ignore_ascii_case(&channel.channel_type)
+        })
+        .map(|(_, value)| *value)
+}
+
+impl Preset {
+    /// Record the programmer's values for the preset's feature set. Global presets keep one
+    /// value per attribute, taken from the first fixture in patch order.
+    pub fn record(
+        id: PresetId,
+        name: String,
+        programmer: &HashMap<String, u8>,
+        lookup: &PatchLookup,
+        global: bool,
+    ) -> Result<Self, String> {
+        let mut preset = Preset { id, name, values: BTreeMap::new(), fixtures: BTreeMap::new() };
+
+        for fixture_id in patch_order(lookup.fixtures) {
+            for channel in lookup.channels(&fixture_id) {
+                let Some(value) = programmer.get(&channel_key(&fixture_id, channel.offset)) else {
+                    continue;
+                };
+                if FeatureSet::from_channel_type(&channel.channel_type) != id.feature_set {
+                    continue;
+                }
+                if global {
+                    preset.values.entry(channel.name.clone()).or_insert(*value);
+                } else {
+                    preset.fixtures.entry(fixture_id.clone()).or_default().insert(channel.name.clone(), *value);
+                }
+            }
+        }
+
+        if preset.values.is_empty() && preset.fixtures.is_empty() {
+            return Err(format!("Programmer has no values for preset {}", id));
+        }
+        Ok(preset)
+    }
+
+    /// Value this preset gives a programmer/cue key, if it covers that channel
+    pub fn value_for(&self, key: &str, lookup: &PatchLookup) -> Option<u8> {
+        let (fixture_id, offset) = parse_channel_key(key)?;
+        let channel = lookup.channel(fixture_id, offset)?;
+        self.fixtures
+            .get(fixture_id)
+            .and_then(|values| attribute_value(values, channel))
+            .or_else(|| attribute_value(&self.values, channel))
+    }
+
+    /// Channel values for a fixture
+    pub fn resolve(&self, fixture_id: &str, lookup: &PatchLookup) -> Vec<(String, u8)> {
+        lookup
+            .channels(fixture_id)
+            .iter()
+            .map(|c| channel_key(fixture_id, c.offset))
+            .filter_map(|key| self.value_for(&key, lookup).map(|value| (key, value)))
+            .collect()
+    }
+}
+
+/// Preset pool, plus which programmer values came from a preset so recording a cue
+/// can store the reference instead of a hard value
+#[derive(Debug, Default)]
+pub struct PresetEngine {
+    presets: BTreeMap<PresetId, Preset>,
+    /// Programmer key -> preset and the value it set
+    programmer_refs: HashMap<String, (PresetId, u8)>,
+}
+
+impl PresetEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn presets(&self) -> impl Iterator<Item = &Preset> {
+        self.presets.values()
+    }
+
+    pub fn get(&self, id: PresetId) -> Result<&Preset, String> {
+        self.presets.get(&id).ok_or_else(|| format!("Preset {} not found", id))
+    }
+
+    /// Replace all presets, e.g. when a show is loaded
+    pub fn load(&mut self, presets: Vec<Preset>) {
+        self.presets = presets.into_iter().map(|p| (p.id, p)).collect();
+        self.programmer_refs.clear();
+    }
+
+    /// Replace the presets (undo/redo), keeping programmer references to presets that still exist
+    pub fn restore(&mut self, presets: Vec<Preset>) {
+        self.presets = presets.into_iter().map(|p| (p.id, p)).collect();
+        let presets = &self.presets;
+        self.programmer_refs.retain(|_, (id, _)| presets.contains_key(id));
+    }
+
+    pub fn store(&mut self, preset: Preset) {
+        self.presets.insert(preset.id, preset);
+    }
+
+    pub fn remove(&mut self, id: PresetId) -> bool {
+        self.programmer_refs.retain(|_, (preset, _)| *preset != id);
+        self.presets.remove(&id).is_some()
+    }
+
+    /// Put a preset's values for the given fixtures into the programmer. Returns how
+    /// many channels were set.
+    pub fn apply(
+        &mut self,
+        id: PresetId,
+        fixture_ids: &[String],
+        lookup: &PatchLookup,
+        programmer: &mut HashMap<String, u8>,
+    ) -> Result<usize, String> {
+        let preset = self.presets.get(&id).ok_or_else(|| format!("Preset {} not found", id))?;
+        let mut count = 0;
+        for fixture_id in fixture_ids {
+            for (key, value) in preset.resolve(fixture_id, lookup) {
+                programmer.insert(key.clone(), value);
+                self.programmer_refs.insert(key, (id, value));
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Programmer channels still holding the value a preset gave them
+    pub fn programmer_refs(&self, programmer: &HashMap<String, u8>) -> HashMap<String, PresetId> {
+        self.programmer_refs
+            .iter()
+            .filter(|(key, (_, value))| programmer.get(*key) == Some(value))
+            .map(|(key, (id, _))| (key.clone(), *id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue_engine::{CueList, RecordMode};
+    use crate::{Fixture, GdtfFixtureType};
+
+    fn patch() -> (HashMap<String, Fixture>, HashMap<String, GdtfFixtureType>) {
+        let channel = |name: &str, offset| FixtureChannel {
+            name: name.to_string(),
+            offset,
+            default_value: 0,
+            channel_type: name.to_string(),
+        };
+        let library = HashMap::from([(
+            "LED".to_string(),
+            GdtfFixtureType {
+                name: "LED".to_string(),
+                manufacturer: String::new(),
+                channels: vec![channel("Dimmer", 0), channel("Red", 1), channel("Green", 2)],
+                modes: Vec::new(),
+            },
+        )]);
+        let fixtures = (1..=2)
+            .map(|i| {
+                let id = format!("led{}", i);
+                let fixture = Fixture {
+                    id: id.clone(),
+                    name: id.clone(),
+                    fixture_type: "LED".to_string(),
+                    dmx_address: i * 10,
+                    universe: 0,
+                    channel_count: 3,
+                    gdtf_file: None,
+                    is_video: None,
+                    video_source_type: None,
+                    video_source_path: None,
+                    position: None,
+                };
+                (id, fixture)
+            })
+            .collect();
+        (fixtures, library)
+    }
+
+    #[test]
+    fn test_preset_id_notation() {
+        let id = PresetId::parse("color.5").unwrap();
+        assert_eq!(id, PresetId::parse("3.5").unwrap());
+        assert_eq!(id.to_string(), "3.5");
+        assert!(PresetId::parse("7.1").is_err());
+        assert!(PresetId::parse("3.0").is_err());
+    }
+
+    #[test]
+    fn test_fixture_values_override_global() {
+        let (fixtures, library) = patch();
+        let lookup = PatchLookup::new(&fixtures, &library);
+        let mut preset = Preset {
+            id: PresetId::parse("3.1").unwrap(),
+            name: "Red".to_string(),
+            values: BTreeMap::from([("red".to_string(), 255), ("green".to_string(), 0)]),
+            fixtures: BTreeMap::new(),
+        };
+        preset.fixtures.insert("led2".to_string(), BTreeMap::from([("Green".to_string(), 40)]));
+
+        assert_eq!(preset.resolve("led1", &lookup), vec![("led1:1".to_string(), 255), ("led1:2".to_string(), 0)]);
+        assert_eq!(preset.value_for("led2:2", &lookup), Some(40));
+        assert_eq!(preset.value_for("led2:0", &lookup), None);
+    }
+
+    #[test]
+    fn test_editing_preset_updates_cues() {
+        let (fixtures, library) = patch();
+        let lookup = PatchLookup::new(&fixtures, &library);
+        let id = PresetId::parse("3.1").unwrap();
+        let mut engine = PresetEngine::new();
+        let mut programmer = HashMap::from([("led1:1".to_string(), 200)]);
+        engine.store(Preset::record(id, "Red".to_string(), &programmer, &lookup, true).unwrap());
+
+        programmer.clear();
+        programmer.insert("led1:0".to_string(), 255);
+        engine.apply(id, &["led1".to_string(), "led2".to_string()], &lookup, &mut programmer).unwrap();
+        let mut list = CueList::new(1, String::new());
+        list.record(1.0, String::new(), &programmer, None, RecordMode::Tracking, false);
+        list.link_presets(1.0, &engine.programmer_refs(&programmer)).unwrap();
+
+        programmer.insert("led1:1".to_string(), 90);
+        programmer.remove("led2:1");
+        let edited = Preset::record(id, "Red".to_string(), &programmer, &lookup, false).unwrap();
+        assert_eq!(list.update_preset_refs(id, &|key| edited.value_for(key, &lookup)), 1);
+
+        let cue = &list.cues[0];
+        assert_eq!(cue.values["led1:1"], 90);
+        assert_eq!(cue.values["led1:0"], 255);
+        // led2 isn't in the edited preset any more, so its value becomes a hard value
+        assert_eq!(cue.values["led2:1"], 200);
+        assert!(!cue.presets.contains_key("led2:1"));
+    }
+}
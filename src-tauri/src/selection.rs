@@ -0,0 +1,272 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::Fixture;
+
+/// Named, ordered set of fixtures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    /// Order matters: fans, effects and stepping follow it
+    #[serde(default)]
+    pub fixture_ids: Vec<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// Selection operations shared by the CLI, web remote and Stream Deck
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SelectionCommand {
+    /// Replace the selection
+    Select { fixture_ids: Vec<String> },
+    /// Append to the selection, keeping the existing order
+    Add { fixture_ids: Vec<String> },
+    Remove { fixture_ids: Vec<String> },
+    /// Replace the selection with a group's fixtures
+    SelectGroup { group_id: String },
+    AddGroup { group_id: String },
+    /// Every patched fixture that isn't selected, in patch order
+    Invert,
+    /// Every patched fixture, in patch order
+    All,
+    Clear,
+    /// Keep the 1st, 3rd, 5th... fixture
+    Odd,
+    /// Keep the 2nd, 4th, 6th... fixture
+    Even,
+    /// Keep every `n`th fixture, starting at 1-based position `start`
+    EveryNth { n: usize, start: Option<usize> },
+    /// Step to the next fixture of the selection
+    Next,
+    Previous,
+    /// Stop stepping and work on the whole selection again
+    AllSteps,
+}
+
+/// Current selection as reported to clients
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SelectionStatus {
+    /// Full ordered selection
+    pub fixture_ids: Vec<String>,
+    /// Fixtures commands apply to (a single fixture while stepping)
+    pub active: Vec<String>,
+    /// 0-based position while stepping with next/previous
+    pub step: Option<usize>,
+}
+
+/// Patched fixture ids ordered by universe and address
+pub fn patch_order(fixtures: &HashMap<String, Fixture>) -> Vec<String> {
+    let mut sorted: Vec<&Fixture> = fixtures.values().collect();
+    sorted.sort_by(|a, b| (a.universe, a.dmx_address, &a.id).cmp(&(b.universe, b.dmx_address, &b.id)));
+    sorted.into_iter().map(|f| f.id.clone()).collect()
+}
+
+/// Apply a selection command against the current patch. Used by the Tauri commands and
+/// the web remote so both drive the same selection.
+pub fn run_command(
+    fixtures: &Mutex<HashMap<String, Fixture>>,
+    selection: &Mutex<SelectionEngine>,
+    command: SelectionCommand,
+) -> Result<SelectionStatus, String> {
+    let order = patch_order(&*fixtures.lock().map_err(|e| e.to_string())?);
+    let mut selection = selection.lock().map_err(|e| e.to_string())?;
+    selection.apply(command, &order)?;
+    Ok(selection.status())
+}
+
+/// Groups and the current fixture selection
+#[derive(Debug, Default)]
+pub struct SelectionEngine {
+    groups: Vec<Group>,
+    selected: Vec<String>,
+    step: Option<usize>,
+}
+
+impl SelectionEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn group(&self, id: &str) -> Result<&Group, String> {
+        self.groups
+            .iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| format!("Group {} not found", id))
+    }
+
+    /// Replace all groups, e.g. when a show is loaded
+    pub fn load(&mut self, groups: Vec<Group>) {
+        self.groups = groups;
+        self.selected.clear();
+        self.step = None;
+    }
+
+    /// Store a group, replacing one with the same id in place
+    pub fn store_group(&mut self, group: Group) {
+        match self.groups.iter_mut().find(|g| g.id == group.id) {
+            Some(existing) => *existing = group,
+            None => self.groups.push(group),
+        }
+    }
+
+    /// Replace the groups (undo/redo), keeping the current selection
+    pub fn restore_groups(&mut self, groups: Vec<Group>) {
+        self.groups = groups;
+    }
+
+    pub fn remove_group(&mut self, id: &str) -> bool {
+        let before = self.groups.len();
+        self.groups.retain(|g| g.id != id);
+        self.groups.len() != before
+    }
+
+    pub fn selected(&self) -> &[String] {
+        &self.selected
+    }
+
+    /// Fixtures commands should apply to: the stepped fixture, or the whole selection
+    pub fn active(&self) -> Vec<String> {
+        match self.step.and_then(|i| self.selected.get(i)) {
+            Some(fixture_id) => vec![fixture_id.clone()],
+            None => self.selected.clone(),
+        }
+    }
+
+    pub fn status(&self) -> SelectionStatus {
+        SelectionStatus {
+            fixture_ids: self.selected.clone(),
+            active: self.active(),
+            step: self.step,
+        }
+    }
+
+    fn set_selection(&mut self, fixture_ids: Vec<String>) {
+        let mut seen = HashSet::new();
+        self.selected = fixture_ids.into_iter().filter(|f| seen.insert(f.clone())).collect();
+        self.step = None;
+    }
+
+    fn keep_positions(&mut self, keep: impl Fn(usize) -> bool) {
+        let kept = self
+            .selected
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| keep(*i))
+            .map(|(_, f)| f.clone())
+            .collect();
+        self.set_selection(kept);
+    }
+
+    /// Apply a selection command. `patch_order` lists every patched fixture in patch order
+    /// and is used by invert/all; unknown fixture ids are rejected.
+    pub fn apply(&mut self, command: SelectionCommand, patch_order: &[String]) -> Result<(), String> {
+        let check = |ids: &[String]| match ids.iter().find(|id| !patch_order.contains(id)) {
+            Some(id) => Err(format!("Fixture {} not found", id)),
+            None => Ok(()),
+        };
+
+        match command {
+            SelectionCommand::Select { fixture_ids } => {
+                check(&fixture_ids)?;
+                self.set_selection(fixture_ids);
+            }
+            SelectionCommand::Add { fixture_ids } => {
+                check(&fixture_ids)?;
+                let mut selection = self.selected.clone();
+                selection.extend(fixture_ids);
+                self.set_selection(selection);
+            }
+            SelectionCommand::Remove { fixture_ids } => {
+                let kept = self.selected.iter().filter(|f| !fixture_ids.contains(f)).cloned().collect();
+                self.set_selection(kept);
+            }
+            SelectionCommand::SelectGroup { group_id } => {
+                let fixture_ids = self.group(&group_id)?.fixture_ids.clone();
+                self.set_selection(fixture_ids);
+            }
+            SelectionCommand::AddGroup { group_id } => {
+                let mut selection = self.selected.clone();
+                selection.extend(self.group(&group_id)?.fixture_ids.iter().cloned());
+                self.set_selection(selection);
+            }
+            SelectionCommand::Invert => {
+                let inverted = patch_order.iter().filter(|f| !self.selected.contains(f)).cloned().collect();
+                self.set_selection(inverted);
+            }
+            SelectionCommand::All => self.set_selection(patch_order.to_vec()),
+            SelectionCommand::Clear => self.set_selection(Vec::new()),
+            SelectionCommand::Odd => self.keep_positions(|i| i % 2 == 0),
+            SelectionCommand::Even => self.keep_positions(|i| i % 2 == 1),
+            SelectionCommand::EveryNth { n, start } => {
+                if n == 0 {
+                    return Err("Every-Nth needs n of at least 1".to_string());
+                }
+                let start = start.unwrap_or(1).max(1) - 1;
+                self.keep_positions(|i| i >= start && (i - start) % n == 0);
+            }
+            SelectionCommand::Next | SelectionCommand::Previous => {
+                let count = self.selected.len();
+                if count == 0 {
+                    return Err("Nothing selected".to_string());
+                }
+                let forward = matches!(command, SelectionCommand::Next);
+                self.step = Some(match (self.step, forward) {
+                    (None, true) => 0,
+                    (None, false) => count - 1,
+                    (Some(i), true) => (i + 1) % count,
+                    (Some(i), false) => (i + count - 1) % count,
+                });
+            }
+            SelectionCommand::AllSteps => self.step = None,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch() -> Vec<String> {
+        (1..=6).map(|i| format!("f{}", i)).collect()
+    }
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_odd_even_and_every_nth() {
+        let mut selection = SelectionEngine::new();
+        selection.apply(SelectionCommand::All, &patch()).unwrap();
+        selection.apply(SelectionCommand::Odd, &patch()).unwrap();
+        assert_eq!(selection.selected(), ids(&["f1", "f3", "f5"]).as_slice());
+
+        selection.apply(SelectionCommand::All, &patch()).unwrap();
+        selection.apply(SelectionCommand::EveryNth { n: 3, start: Some(2) }, &patch()).unwrap();
+        assert_eq!(selection.selected(), ids(&["f2", "f5"]).as_slice());
+
+        selection.apply(SelectionCommand::Invert, &patch()).unwrap();
+        assert_eq!(selection.selected(), ids(&["f1", "f3", "f4", "f6"]).as_slice());
+    }
+
+    #[test]
+    fn test_stepping_wraps() {
+        let mut selection = SelectionEngine::new();
+        selection.apply(SelectionCommand::Select { fixture_ids: ids(&["f4", "f2"]) }, &patch()).unwrap();
+        selection.apply(SelectionCommand::Previous, &patch()).unwrap();
+        assert_eq!(selection.active(), ids(&["f2"]));
+        selection.apply(SelectionCommand::Next, &patch()).unwrap();
+        assert_eq!(selection.active(), ids(&["f4"]));
+        selection.apply(SelectionCommand::AllSteps, &patch()).unwrap();
+        assert_eq!(selection.active(), ids(&["f4", "f2"]));
+    }
+}
@@ -0,0 +1,688 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::chaser::Chaser;
+use crate::cue_engine::{CueList, DEFAULT_FADE_TIME};
+use crate::effects_engine::Effect;
+use crate::migration::{self, MigrationReport};
+use crate::presets::Preset;
+use crate::selection::Group;
+use crate::{AppState, DmxProtocol, Fixture, FixtureChannel, FixturePosition, GdtfFixtureType};
+
+/// Schema version written by this build. Files without `format_version` are treated as version 0.
+pub const SHOW_FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Error)]
+pub enum ShowFileError {
+    #[error("Failed to access {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid show file at {path}: {message}")]
+    Parse { path: String, message: String },
+    #[error("Show file failed validation: {}", format_issues(.0))]
+    Validation(Vec<ValidationIssue>),
+    #[error("{0}")]
+    State(String),
+}
+
+/// A semantic problem in a show file, located by its JSON path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub path: String,
+    pub message: String,
+}
+
+fn format_issues(issues: &[ValidationIssue]) -> String {
+    issues
+        .iter()
+        .map(|i| format!("{}: {}", i.path, i.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShowInfo {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub created: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Default for ShowInfo {
+    fn default() -> Self {
+        ShowInfo {
+            name: "Untitled Show".to_string(),
+            version: String::new(),
+            created: String::new(),
+            description: String::new(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_broadcast() -> String {
+    "2.255.255.255".to_string()
+}
+
+fn default_artnet_port() -> u16 {
+    6454
+}
+
+fn default_refresh_rate() -> u32 {
+    44
+}
+
+fn default_universe_start() -> u16 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtNetConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_broadcast")]
+    pub broadcast_address: String,
+    #[serde(default = "default_artnet_port")]
+    pub port: u16,
+    #[serde(default = "default_refresh_rate")]
+    pub refresh_rate: u32,
+}
+
+impl Default for ArtNetConfig {
+    fn default() -> Self {
+        ArtNetConfig {
+            enabled: true,
+            broadcast_address: default_broadcast(),
+            port: default_artnet_port(),
+            refresh_rate: default_refresh_rate(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SacnConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_universe_start")]
+    pub universe_start: u16,
+}
+
+impl Default for SacnConfig {
+    fn default() -> Self {
+        SacnConfig {
+            enabled: false,
+            universe_start: default_universe_start(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub artnet: ArtNetConfig,
+    #[serde(default)]
+    pub sacn: SacnConfig,
+    /// IP of the interface DMX is sent from (all interfaces when unset)
+    #[serde(default)]
+    pub interface: Option<String>,
+}
+
+/// Fixture as stored in a show file. Channels can be given inline (demo-show.json style)
+/// or by referencing an entry in `fixture_library` through `fixture_type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShowFixture {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub manufacturer: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub dmx_mode: String,
+    #[serde(default)]
+    pub fixture_type: Option<String>,
+    /// 1-based universe as shown on the console
+    pub universe: u16,
+    pub dmx_address: u16,
+    pub channel_count: u16,
+    /// 1-based channel number -> attribute name
+    #[serde(default)]
+    pub channels: BTreeMap<u16, String>,
+    #[serde(default)]
+    pub position: Option<FixturePosition>,
+    #[serde(default)]
+    pub gdtf_file: Option<String>,
+    #[serde(default)]
+    pub is_video: Option<bool>,
+    #[serde(default)]
+    pub video_source_type: Option<String>,
+    #[serde(default)]
+    pub video_source_path: Option<String>,
+}
+
+impl ShowFixture {
+    /// Library name used for fixtures that only carry inline channels
+    fn inline_type_name(&self) -> String {
+        let base = format!("{} {}", self.manufacturer, self.model).trim().to_string();
+        let base = if base.is_empty() { self.name.clone() } else { base };
+        if self.dmx_mode.is_empty() {
+            base
+        } else {
+            format!("{} ({})", base, self.dmx_mode)
+        }
+    }
+}
+
+/// Executor button/fader and the cue list it plays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Executor {
+    pub number: u32,
+    pub cue_list: u32,
+    #[serde(default)]
+    pub name: String,
+}
+
+/// Video source routed to a video output ("route video1 output2")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoRoute {
+    pub source: String,
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preferences {
+    #[serde(default = "default_fade_time")]
+    pub default_fade_time: f64,
+    #[serde(default = "default_true")]
+    pub auto_save: bool,
+    /// Seconds between autosaves
+    #[serde(default = "default_auto_save_interval")]
+    pub auto_save_interval: u64,
+    /// Number of rotating autosave files kept
+    #[serde(default = "default_auto_save_backups")]
+    pub auto_save_backups: usize,
+    /// Steps kept in the undo history
+    #[serde(default = "default_undo_levels")]
+    pub undo_levels: usize,
+    #[serde(default = "default_true")]
+    pub enable_gamepad: bool,
+    #[serde(default = "default_gamepad_deadzone")]
+    pub gamepad_deadzone: f32,
+    #[serde(default = "default_touchscreen_mode")]
+    pub touchscreen_mode: String,
+}
+
+fn default_fade_time() -> f64 {
+    DEFAULT_FADE_TIME
+}
+
+fn default_auto_save_interval() -> u64 {
+    300
+}
+
+fn default_auto_save_backups() -> usize {
+    5
+}
+
+fn default_undo_levels() -> usize {
+    100
+}
+
+fn default_gamepad_deadzone() -> f32 {
+    0.1
+}
+
+fn default_touchscreen_mode() -> String {
+    "absolute".to_string()
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            default_fade_time: default_fade_time(),
+            auto_save: true,
+            auto_save_interval: default_auto_save_interval(),
+            auto_save_backups: default_auto_save_backups(),
+            undo_levels: default_undo_levels(),
+            enable_gamepad: true,
+            gamepad_deadzone: default_gamepad_deadzone(),
+            touchscreen_mode: default_touchscreen_mode(),
+        }
+    }
+}
+
+/// On-disk show file. Layout follows demo-show.json, extended with the backend
+/// cue, effect and chaser models. Older layouts are upgraded by `migration` first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShowFile {
+    #[serde(default)]
+    pub format_version: u32,
+    #[serde(default)]
+    pub show_info: ShowInfo,
+    #[serde(default)]
+    pub network_config: NetworkConfig,
+    #[serde(default)]
+    pub fixture_library: Vec<GdtfFixtureType>,
+    #[serde(default)]
+    pub fixtures: Vec<ShowFixture>,
+    #[serde(default)]
+    pub groups: Vec<Group>,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    #[serde(default)]
+    pub cue_lists: Vec<CueList>,
+    #[serde(default)]
+    pub executors: Vec<Executor>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub chasers: Vec<Chaser>,
+    #[serde(default)]
+    pub routing: Vec<VideoRoute>,
+    #[serde(default)]
+    pub preferences: Preferences,
+}
+
+/// Show sections that are kept as loaded until saved again, plus where the show lives on disk
+#[derive(Debug, Clone, Default)]
+pub struct ShowDocument {
+    pub info: ShowInfo,
+    pub network: NetworkConfig,
+    pub executors: Vec<Executor>,
+    pub routing: Vec<VideoRoute>,
+    pub preferences: Preferences,
+    pub path: Option<PathBuf>,
+}
+
+/// Parse show JSON, upgrading older formats first. Structural errors report the JSON
+/// path of the first problem (in the upgraded document).
+pub fn parse_show(json: &str) -> Result<(ShowFile, Option<MigrationReport>), ShowFileError> {
+    let mut doc: serde_json::Value = serde_json::from_str(json).map_err(|e| ShowFileError::Parse {
+        path: format!("line {} column {}", e.line(), e.column()),
+        message: e.to_string(),
+    })?;
+    let report = migration::migrate(&mut doc).map_err(|message| ShowFileError::Parse {
+        path: "format_version".to_string(),
+        message,
+    })?;
+    let show: ShowFile = serde_path_to_error::deserialize(doc).map_err(|e| ShowFileError::Parse {
+        path: e.path().to_string(),
+        message: e.inner().to_string(),
+    })?;
+    validate(&show)?;
+    Ok((show, report))
+}
+
+/// Check references and address ranges that serde can't
+pub fn validate(show: &ShowFile) -> Result<(), ShowFileError> {
+    let mut issues = Vec::new();
+    let mut issue = |path: String, message: String| issues.push(ValidationIssue { path, message });
+
+    let library: HashSet<&str> = show.fixture_library.iter().map(|t| t.name.as_str()).collect();
+    let mut fixture_ids = HashSet::new();
+
+    for (i, fixture) in show.fixtures.iter().enumerate() {
+        let path = format!("fixtures[{}]", i);
+        if fixture.id.trim().is_empty() {
+            issue(format!("{}.id", path), "fixture id must not be empty".to_string());
+        } else if !fixture_ids.insert(fixture.id.as_str()) {
+            issue(format!("{}.id", path), format!("duplicate fixture id '{}'", fixture.id));
+        }
+        if !(1..=256).contains(&fixture.universe) {
+            issue(format!("{}.universe", path), format!("universe {} is outside 1-256", fixture.universe));
+        }
+        if !(1..=512).contains(&fixture.dmx_address) {
+            issue(format!("{}.dmx_address", path), format!("address {} is outside 1-512", fixture.dmx_address));
+        } else if fixture.dmx_address as u32 + fixture.channel_count as u32 - 1 > 512 {
+            issue(
+                format!("{}.channel_count", path),
+                format!("{} channels from address {} run past channel 512", fixture.channel_count, fixture.dmx_address),
+            );
+        }
+        if let Some(type_name) = &fixture.fixture_type {
+            if fixture.channels.is_empty() && !library.contains(type_name.as_str()) {
+                issue(
+                    format!("{}.fixture_type", path),
+                    format!("fixture type '{}' is not in fixture_library", type_name),
+                );
+            }
+        }
+        for number in fixture.channels.keys() {
+            if *number == 0 || *number > fixture.channel_count {
+                issue(
+                    format!("{}.channels.{}", path, number),
+                    format!("channel {} is outside 1-{}", number, fixture.channel_count),
+                );
+            }
+        }
+    }
+
+    for (i, group) in show.groups.iter().enumerate() {
+        for (j, fixture_id) in group.fixture_ids.iter().enumerate() {
+            if !fixture_ids.contains(fixture_id.as_str()) {
+                issue(format!("groups[{}].fixture_ids[{}]", i, j), format!("unknown fixture '{}'", fixture_id));
+            }
+        }
+    }
+
+    let mut preset_ids = HashSet::new();
+    for (i, preset) in show.presets.iter().enumerate() {
+        if !preset_ids.insert(preset.id) {
+            issue(format!("presets[{}].id", i), format!("duplicate preset {}", preset.id));
+        }
+        for fixture_id in preset.fixtures.keys() {
+            if !fixture_ids.contains(fixture_id.as_str()) {
+                issue(format!("presets[{}].fixtures.{}", i, fixture_id), format!("unknown fixture '{}'", fixture_id));
+            }
+        }
+    }
+
+    let mut cue_list_numbers = HashSet::new();
+    for (i, list) in show.cue_lists.iter().enumerate() {
+        if !cue_list_numbers.insert(list.number) {
+            issue(format!("cue_lists[{}].number", i), format!("duplicate cue list {}", list.number));
+        }
+        for (j, cue) in list.cues.iter().enumerate() {
+            for key in cue.values.keys() {
+                let known = crate::attributes::parse_channel_key(key)
+                    .is_some_and(|(fixture_id, _)| fixture_ids.contains(fixture_id));
+                if !known {
+                    issue(
+                        format!("cue_lists[{}].cues[{}].values.{}", i, j, key),
+                        "value does not reference a patched fixture channel".to_string(),
+                    );
+                }
+            }
+            for (key, preset) in &cue.presets {
+                if !preset_ids.contains(preset) {
+                    issue(format!("cue_lists[{}].cues[{}].presets.{}", i, j, key), format!("preset {} does not exist", preset));
+                }
+            }
+        }
+    }
+    for (i, executor) in show.executors.iter().enumerate() {
+        if !cue_list_numbers.contains(&executor.cue_list) {
+            issue(format!("executors[{}].cue_list", i), format!("cue list {} does not exist", executor.cue_list));
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(ShowFileError::Validation(issues))
+    }
+}
+
+/// Replace the running show with the contents of a show file
+pub fn apply_show(state: &AppState, show: ShowFile, path: Option<PathBuf>) -> Result<(), ShowFileError> {
+    let lock_err = |e: String| ShowFileError::State(e);
+
+    let mut library: HashMap<String, GdtfFixtureType> =
+        show.fixture_library.iter().map(|t| (t.name.clone(), t.clone())).collect();
+    let mut fixtures = HashMap::new();
+
+    for show_fixture in &show.fixtures {
+        let fixture_type = match &show_fixture.fixture_type {
+            Some(name) if show_fixture.channels.is_empty() => name.clone(),
+            _ => {
+                // Inline channels become a library entry of their own
+                let name = show_fixture.fixture_type.clone().unwrap_or_else(|| show_fixture.inline_type_name());
+                library.entry(name.clone()).or_insert_with(|| GdtfFixtureType {
+                    name: name.clone(),
+                    manufacturer: show_fixture.manufacturer.clone(),
+                    channels: show_fixture
+                        .channels
+                        .iter()
+                        .map(|(number, attribute)| FixtureChannel {
+                            name: attribute.clone(),
+                            offset: number - 1,
+                            default_value: 0,
+                            channel_type: attribute.clone(),
+                        })
+                        .collect(),
+                    modes: vec![show_fixture.dmx_mode.clone()],
+                });
+                name
+            }
+        };
+
+        fixtures.insert(
+            show_fixture.id.clone(),
+            Fixture {
+                id: show_fixture.id.clone(),
+                name: show_fixture.name.clone(),
+                fixture_type,
+                dmx_address: show_fixture.dmx_address,
+                universe: (show_fixture.universe - 1) as u8,
+                channel_count: show_fixture.channel_count,
+                gdtf_file: show_fixture.gdtf_file.clone(),
+                is_video: show_fixture.is_video,
+                video_source_type: show_fixture.video_source_type.clone(),
+                video_source_path: show_fixture.video_source_path.clone(),
+                position: show_fixture.position.clone(),
+            },
+        );
+    }
+
+    // Swap everything in only after the whole file converted cleanly
+    *state.fixture_library.lock().map_err(|e| lock_err(e.to_string()))? = library;
+    *state.fixtures.lock().map_err(|e| lock_err(e.to_string()))? = fixtures;
+    state.programmer.lock().map_err(|e| lock_err(e.to_string()))?.clear();
+    state.cue_engine.lock().map_err(|e| lock_err(e.to_string()))?.load(show.cue_lists);
+    state.effects_engine.lock().map_err(|e| lock_err(e.to_string()))?.load(show.effects);
+    state.chaser_engine.lock().map_err(|e| lock_err(e.to_string()))?.load(show.chasers);
+    state.selection.lock().map_err(|e| lock_err(e.to_string()))?.load(show.groups);
+    state.presets.lock().map_err(|e| lock_err(e.to_string()))?.load(show.presets);
+    state.history.lock().map_err(|e| lock_err(e.to_string()))?.clear();
+
+    {
+        let mut engine = state.dmx_engine.lock().map_err(|e| lock_err(e.to_string()))?;
+        engine.broadcast_address = show.network_config.artnet.broadcast_address.clone();
+        engine.set_protocol(if show.network_config.sacn.enabled && !show.network_config.artnet.enabled {
+            DmxProtocol::Sacn
+        } else {
+            DmxProtocol::ArtNet
+        });
+        if engine.selected_interface != show.network_config.interface {
+            engine
+                .set_network_interface(show.network_config.interface.clone())
+                .map_err(|e| lock_err(e.to_string()))?;
+        }
+    }
+
+    *state.show.lock().map_err(|e| lock_err(e.to_string()))? = ShowDocument {
+        info: show.show_info,
+        network: show.network_config,
+        executors: show.executors,
+        routing: show.routing,
+        preferences: show.preferences,
+        path,
+    };
+
+    Ok(())
+}
+
+/// Snapshot the running show into the on-disk format
+pub fn collect_show(state: &AppState) -> Result<ShowFile, ShowFileError> {
+    let lock_err = |e: String| ShowFileError::State(e);
+
+    let document = state.show.lock().map_err(|e| lock_err(e.to_string()))?.clone();
+    let library = state.fixture_library.lock().map_err(|e| lock_err(e.to_string()))?.clone();
+    let fixtures = state.fixtures.lock().map_err(|e| lock_err(e.to_string()))?.clone();
+
+    let mut network = document.network.clone();
+    {
+        let engine = state.dmx_engine.lock().map_err(|e| lock_err(e.to_string()))?;
+        network.artnet.enabled = engine.protocol == DmxProtocol::ArtNet;
+        network.artnet.broadcast_address = engine.broadcast_address.clone();
+        network.sacn.enabled = engine.protocol == DmxProtocol::Sacn;
+        network.interface = engine.selected_interface.clone();
+    }
+
+    let mut show_fixtures: Vec<ShowFixture> = fixtures
+        .values()
+        .map(|f| {
+            let fixture_type = library.get(&f.fixture_type);
+            ShowFixture {
+                id: f.id.clone(),
+                name: f.name.clone(),
+                manufacturer: fixture_type.map(|t| t.manufacturer.clone()).unwrap_or_default(),
+                model: f.fixture_type.clone(),
+                dmx_mode: fixture_type.and_then(|t| t.modes.first().cloned()).unwrap_or_default(),
+                fixture_type: Some(f.fixture_type.clone()),
+                universe: f.universe as u16 + 1,
+                dmx_address: f.dmx_address,
+                channel_count: f.channel_count,
+                channels: BTreeMap::new(),
+                position: f.position.clone(),
+                gdtf_file: f.gdtf_file.clone(),
+                is_video: f.is_video,
+                video_source_type: f.video_source_type.clone(),
+                video_source_path: f.video_source_path.clone(),
+            }
+        })
+        .collect();
+    show_fixtures.sort_by(|a, b| (a.universe, a.dmx_address, &a.id).cmp(&(b.universe, b.dmx_address, &b.id)));
+
+    // Only library entries in use are written, so the file stays self-contained
+    let used: HashSet<&String> = fixtures.values().map(|f| &f.fixture_type).collect();
+    let mut fixture_library: Vec<GdtfFixtureType> =
+        library.values().filter(|t| used.contains(&t.name)).cloned().collect();
+    fixture_library.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let cue_lists = state.cue_engine.lock().map_err(|e| lock_err(e.to_string()))?.lists().cloned().collect();
+    let effects = state
+        .effects_engine
+        .lock()
+        .map_err(|e| lock_err(e.to_string()))?
+        .effects()
+        .cloned()
+        .collect();
+    let chasers = state.chaser_engine.lock().map_err(|e| lock_err(e.to_string()))?.chasers().cloned().collect();
+    let groups = state.selection.lock().map_err(|e| lock_err(e.to_string()))?.groups().to_vec();
+    let presets = state.presets.lock().map_err(|e| lock_err(e.to_string()))?.presets().cloned().collect();
+
+    Ok(ShowFile {
+        format_version: SHOW_FORMAT_VERSION,
+        show_info: ShowInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            ..document.info
+        },
+        network_config: network,
+        fixture_library,
+        fixtures: show_fixtures,
+        groups,
+        presets,
+        cue_lists,
+        executors: document.executors,
+        effects,
+        chasers,
+        routing: document.routing,
+        preferences: document.preferences,
+    })
+}
+
+/// Result of loading a show, including what was upgraded on the way
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadedShow {
+    pub info: ShowInfo,
+    pub migration: Option<MigrationReport>,
+}
+
+/// Read, upgrade, validate and apply a show file
+pub fn load_show_file(state: &AppState, path: &Path) -> Result<LoadedShow, ShowFileError> {
+    let io_err = |source| ShowFileError::Io {
+        path: path.display().to_string(),
+        source,
+    };
+    let json = fs::read_to_string(path).map_err(io_err)?;
+    let (show, migration) = parse_show(&json)?;
+
+    // Keep the original next to the show, since the next save writes the new format. The
+    // show may sit on read-only media, so it still loads when that isn't possible.
+    if let Some(report) = &migration {
+        let backup = path.with_extension(format!("v{}.json", report.from_version));
+        let notes = path.with_extension("migration.txt");
+        for (file, contents) in [(&backup, json.clone()), (&notes, report.to_text())] {
+            if let Err(e) = fs::write(file, contents) {
+                eprintln!("[Show] Warning: couldn't write {}: {}", file.display(), e);
+            }
+        }
+    }
+
+    let info = show.show_info.clone();
+    apply_show(state, show, Some(path.to_path_buf()))?;
+    Ok(LoadedShow { info, migration })
+}
+
+/// Write JSON to `path`. The file is written next to the target and renamed into
+/// place so a crash mid-write never leaves a truncated show.
+pub fn write_atomic(path: &Path, json: &str) -> Result<(), ShowFileError> {
+    let io_err = |source| ShowFileError::Io {
+        path: path.display().to_string(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_err)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(io_err)?;
+    fs::rename(&tmp_path, path).map_err(io_err)
+}
+
+/// Serialize the running show
+pub fn show_json(state: &AppState) -> Result<String, ShowFileError> {
+    let show = collect_show(state)?;
+    serde_json::to_string_pretty(&show).map_err(|e| ShowFileError::State(e.to_string()))
+}
+
+/// Write the running show to disk and remember it as the show's file
+pub fn save_show_file(state: &AppState, path: &Path) -> Result<(), ShowFileError> {
+    write_atomic(path, &show_json(state)?)?;
+    state.show.lock().map_err(|e| ShowFileError::State(e.to_string()))?.path = Some(path.to_path_buf());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_show_parses() {
+        let (show, report) = parse_show(include_str!("../../demo-show.json")).unwrap();
+        assert_eq!(show.fixtures.len(), 3);
+        assert_eq!(show.cue_lists.len(), 4);
+        assert_eq!(show.executors.len(), 4);
+        assert!(report.is_some());
+    }
+
+    #[test]
+    fn test_parse_error_reports_path() {
+        let json = r#"{"format_version": 3, "fixtures": [{"id": "a", "name": "A", "universe": 1, "dmx_address": "one", "channel_count": 1}]}"#;
+        match parse_show(json) {
+            Err(ShowFileError::Parse { path, .. }) => assert_eq!(path, "fixtures[0].dmx_address"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validation_reports_unknown_group_member() {
+        let json = r#"{"show_info": {"name": "x"}, "groups": [{"id": "g", "name": "G", "fixture_ids": ["missing"]}]}"#;
+        match parse_show(json) {
+            Err(ShowFileError::Validation(issues)) => assert_eq!(issues[0].path, "groups[0].fixture_ids[0]"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
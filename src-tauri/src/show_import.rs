@@ -0,0 +1,462 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::attributes::channel_key;
+use crate::cue_engine::{Cue, CueTiming, FollowMode, DEFAULT_FADE_TIME};
+use crate::patch;
+use crate::{Fixture, GdtfFixtureType};
+
+/// Line of an import file that could not be brought into the show
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unmapped {
+    /// Line in the import file, for line-based formats
+    pub line: Option<usize>,
+    pub item: String,
+    pub reason: String,
+}
+
+/// Outcome of an import. With `dry_run` set nothing was changed in the show.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Fixtures patched or cues stored
+    pub imported: Vec<String>,
+    pub unmapped: Vec<Unmapped>,
+    pub warnings: Vec<String>,
+}
+
+impl ImportReport {
+    pub(crate) fn unmapped(&mut self, line: Option<usize>, item: impl Into<String>, reason: impl Into<String>) {
+        self.unmapped.push(Unmapped {
+            line,
+            item: item.into(),
+            reason: reason.into(),
+        });
+    }
+}
+
+// CSV patch lists
+
+/// Header names accepted for each patch column
+const NAME_HEADERS: &[&str] = &["name", "fixture", "fixture name", "label"];
+const TYPE_HEADERS: &[&str] = &["type", "fixture type", "model"];
+const UNIVERSE_HEADERS: &[&str] = &["universe", "univ"];
+const ADDRESS_HEADERS: &[&str] = &["address", "dmx address", "addr", "start"];
+const MODE_HEADERS: &[&str] = &["mode", "dmx mode"];
+const ID_HEADERS: &[&str] = &["id", "fixture id"];
+
+fn column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| names.contains(&h.trim().to_lowercase().as_str()))
+}
+
+/// Library entry for a type/mode pair. Types match on the library name, or on
+/// "manufacturer name"; when the library has several entries for a type the mode decides.
+fn find_type<'a>(
+    library: &'a HashMap<String, GdtfFixtureType>,
+    type_name: &str,
+    mode: &str,
+) -> Option<&'a GdtfFixtureType> {
+    let type_name = type_name.trim().to_lowercase();
+    let mode = mode.trim().to_lowercase();
+    let mut candidates: Vec<&GdtfFixtureType> = library
+        .values()
+        .filter(|t| {
+            t.name.to_lowercase() == type_name
+                || format!("{} {}", t.manufacturer, t.name).to_lowercase() == type_name
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.name.cmp(&b.name));
+    if mode.is_empty() || candidates.len() <= 1 {
+        return candidates.first().copied();
+    }
+    candidates
+        .iter()
+        .find(|t| t.modes.iter().any(|m| m.to_lowercase() == mode))
+        .or(candidates.first())
+        .copied()
+}
+
+/// Fixture id derived from its name ("Spot 1" -> "spot_1"), unique within `taken`
+pub(crate) fn unique_id(name: &str, taken: &HashSet<String>) -> String {
+    let base: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let base = if base.is_empty() { "fixture".to_string() } else { base };
+    if !taken.contains(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{}_{}", base, n))
+        .find(|id| !taken.contains(id))
+        .expect("unbounded range")
+}
+
+/// Parse a CSV patch list (name, type, universe, address, mode). Universes are 1-based
+/// as in the show file. Fixtures whose type isn't in the library are reported as unmapped.
+pub fn import_csv_patch(
+    data: &str,
+    library: &HashMap<String, GdtfFixtureType>,
+    existing: &HashMap<String, Fixture>,
+    report: &mut ImportReport,
+) -> Result<Vec<Fixture>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+
+    let required = |names: &[&str], label: &str| {
+        column(&headers, names).ok_or_else(|| format!("CSV patch is missing a '{}' column", label))
+    };
+    let name_col = required(NAME_HEADERS, "name")?;
+    let type_col = required(TYPE_HEADERS, "type")?;
+    let universe_col = required(UNIVERSE_HEADERS, "universe")?;
+    let address_col = required(ADDRESS_HEADERS, "address")?;
+    let mode_col = column(&headers, MODE_HEADERS);
+    let id_col = column(&headers, ID_HEADERS);
+
+    let mut taken: HashSet<String> = existing.keys().cloned().collect();
+    // Existing and accepted fixtures, so overlapping rows are left out like in the patch
+    let mut patched = existing.clone();
+
+    let mut fixtures = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        // Line 1 is the header
+        let line = index + 2;
+        let record = record.map_err(|e| format!("Line {}: {}", line, e))?;
+        let field = |col: usize| record.get(col).unwrap_or("").to_string();
+        let name = field(name_col);
+        if name.is_empty() && record.iter().all(str::is_empty) {
+            continue;
+        }
+
+        let type_name = field(type_col);
+        let mode = mode_col.map(field).unwrap_or_default();
+        let Some(fixture_type) = find_type(library, &type_name, &mode) else {
+            report.unmapped(Some(line), &name, format!("fixture type '{}' is not in the library", type_name));
+            continue;
+        };
+        if !mode.is_empty() && !fixture_type.modes.iter().any(|m| m.eq_ignore_ascii_case(&mode)) {
+            report
+                .warnings
+                .push(format!("Line {}: mode '{}' not found for '{}', using its channel layout", line, mode, fixture_type.name));
+        }
+
+        let universe = match field(universe_col).parse::<u16>() {
+            Ok(universe @ 1..=256) => (universe - 1) as u8,
+            _ => {
+                report.unmapped(Some(line), &name, format!("invalid universe '{}' (expected 1-256)", field(universe_col)));
+                continue;
+            }
+        };
+        let channel_count = fixture_type.channels.iter().map(|c| c.offset + 1).max().unwrap_or(0);
+        let address = match field(address_col).parse::<u16>() {
+            Ok(address) if address >= 1 && address as u32 + channel_count as u32 - 1 <= 512 => address,
+            _ => {
+                report.unmapped(Some(line), &name, format!("invalid address '{}' for {} channels", field(address_col), channel_count));
+                continue;
+            }
+        };
+
+        let id = match id_col.map(field).filter(|id| !id.is_empty()) {
+            Some(id) if taken.contains(&id) => {
+                report.unmapped(Some(line), &name, format!("fixture id '{}' already exists", id));
+                continue;
+            }
+            Some(id) => id,
+            None => unique_id(&name, &taken),
+        };
+
+        let fixture = Fixture {
+            id,
+            name,
+            fixture_type: fixture_type.name.clone(),
+            dmx_address: address,
+            universe,
+            channel_count,
+            gdtf_file: None,
+            is_video: None,
+            video_source_type: None,
+            video_source_path: None,
+            position: None,
+        };
+        if let Err(e) = patch::check_address(&fixture, &patched) {
+            report.unmapped(Some(line), &fixture.name, e);
+            continue;
+        }
+
+        report.imported.push(format!(
+            "{} ({}) at {}.{}",
+            fixture.name,
+            fixture_type.name,
+            universe as u16 + 1,
+            address
+        ));
+        taken.insert(fixture.id.clone());
+        patched.insert(fixture.id.clone(), fixture.clone());
+        fixtures.push(fixture);
+    }
+
+    Ok(fixtures)
+}
+
+// USITT ASCII (ASCII Light Cues)
+
+/// Seconds from "5", "2.5" or "1:30"
+fn parse_time(text: &str) -> Option<f64> {
+    match text.split_once(':') {
+        Some((minutes, seconds)) => Some(minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?),
+        None => text.parse().ok(),
+    }
+}
+
+/// DMX value from an ASCII level: percent (0-100), "FL"/"FULL", or hex "Hxx"
+fn parse_level(text: &str) -> Option<u8> {
+    let upper = text.to_uppercase();
+    if upper == "FL" || upper == "FULL" {
+        return Some(255);
+    }
+    if let Some(hex) = upper.strip_prefix('H') {
+        return u8::from_str_radix(hex, 16).ok();
+    }
+    let percent: f64 = text.parse().ok()?;
+    (0.0..=100.0)
+        .contains(&percent)
+        .then(|| (percent * 255.0 / 100.0).round() as u8)
+}
+
+#[derive(Default)]
+struct PendingCue {
+    number: f64,
+    line: usize,
+    text: String,
+    up: Option<(f64, f64)>,
+    down: Option<f64>,
+    follow: Option<f64>,
+    /// console channel -> DMX value
+    levels: BTreeMap<u32, u8>,
+}
+
+/// Parse an ASCII Light Cues file into cues. Console channels go through the file's
+/// PATCH (or a 1:1 patch when there is none) to DMX addresses, which are matched against
+/// the patched fixtures. Dimmers above 512 continue into the next universe.
+pub fn import_usitt_ascii(
+    data: &str,
+    fixtures: &HashMap<String, Fixture>,
+    report: &mut ImportReport,
+) -> Result<Vec<Cue>, String> {
+    // Absolute DMX address (0-based universe, channel) -> fixture channel key
+    let mut addresses: HashMap<(u8, u16), String> = HashMap::new();
+    for fixture in fixtures.values() {
+        for offset in 0..fixture.channel_count {
+            addresses.insert((fixture.universe, fixture.dmx_address + offset), channel_key(&fixture.id, offset));
+        }
+    }
+
+    let mut patch: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut pending: Vec<PendingCue> = Vec::new();
+    let mut current: Option<PendingCue> = None;
+    let mut ignored: HashSet<String> = HashSet::new();
+
+    for (index, raw) in data.lines().enumerate() {
+        let line = index + 1;
+        // "!" starts a comment
+        let text = raw.split('!').next().unwrap_or("").trim();
+        let mut tokens = text.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let keyword = keyword.to_uppercase();
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword.as_str() {
+            "CUE" => {
+                pending.extend(current.take());
+                let number = args.first().and_then(|n| n.parse::<f64>().ok()).ok_or_else(|| format!("Line {}: CUE needs a cue number", line))?;
+                current = Some(PendingCue {
+                    number,
+                    line,
+                    ..PendingCue::default()
+                });
+            }
+            "TEXT" | "UP" | "DOWN" | "FOLLOW" | "CHAN" => {
+                let Some(cue) = current.as_mut() else {
+                    report.warnings.push(format!("Line {}: {} outside a cue, ignored", line, keyword));
+                    continue;
+                };
+                match keyword.as_str() {
+                    "TEXT" => cue.text = args.join(" "),
+                    "UP" => {
+                        let time = args.first().and_then(|t| parse_time(t));
+                        let delay = args.get(1).and_then(|t| parse_time(t)).unwrap_or(0.0);
+                        cue.up = time.map(|t| (t, delay));
+                    }
+                    "DOWN" => {
+                        cue.down = args.first().and_then(|t| parse_time(t));
+                        if args.len() > 1 {
+                            report.warnings.push(format!("Line {}: down delay is not supported, ignored", line));
+                        }
+                    }
+                    "FOLLOW" => cue.follow = args.first().and_then(|t| parse_time(t)),
+                    _ => {
+                        for entry in &args {
+                            let parsed = entry
+                                .split_once('/')
+                                .and_then(|(chan, level)| Some((chan.parse::<u32>().ok()?, parse_level(level)?)));
+                            match parsed {
+                                Some((chan, level)) => {
+                                    cue.levels.insert(chan, level);
+                                }
+                                None => report.warnings.push(format!("Line {}: bad channel level '{}'", line, entry)),
+                            }
+                        }
+                    }
+                }
+            }
+            "PATCH" => {
+                // PATCH page channel<dimmer@level ...
+                for entry in args.iter().skip(1) {
+                    let parsed = entry.split_once('<').and_then(|(chan, dimmer)| {
+                        let dimmer = dimmer.split('@').next()?;
+                        Some((chan.parse::<u32>().ok()?, dimmer.parse::<u32>().ok()?))
+                    });
+                    match parsed {
+                        Some((chan, dimmer)) => patch.entry(chan).or_default().push(dimmer),
+                        None => report.warnings.push(format!("Line {}: bad patch entry '{}'", line, entry)),
+                    }
+                }
+            }
+            "ENDDATA" => break,
+            "IDENT" | "MANUFACTURER" | "CONSOLE" | "CLEAR" | "SET" => {}
+            _ if keyword.starts_with('$') => {}
+            _ => {
+                if ignored.insert(keyword.clone()) {
+                    report.warnings.push(format!("Line {}: {} is not supported, ignored", line, keyword));
+                }
+            }
+        }
+    }
+    pending.extend(current.take());
+
+    let mut unmapped_channels: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut cues = Vec::new();
+    for pending_cue in pending {
+        let mut values = HashMap::new();
+        for (chan, level) in &pending_cue.levels {
+            let dimmers = patch.get(chan).cloned().unwrap_or_else(|| vec![*chan]);
+            let mut mapped = false;
+            for dimmer in dimmers {
+                if dimmer == 0 || dimmer > 256 * 512 {
+                    continue;
+                }
+                let address = (((dimmer - 1) / 512) as u8, ((dimmer - 1) % 512 + 1) as u16);
+                if let Some(key) = addresses.get(&address) {
+                    values.insert(key.clone(), *level);
+                    mapped = true;
+                }
+            }
+            if !mapped {
+                unmapped_channels.entry(*chan).or_insert(pending_cue.line);
+            }
+        }
+
+        let (up, delay) = pending_cue.up.unwrap_or((DEFAULT_FADE_TIME, 0.0));
+        let name = if pending_cue.text.is_empty() {
+            format!("Cue {}", pending_cue.number)
+        } else {
+            pending_cue.text
+        };
+        report.imported.push(format!("Cue {} '{}' ({} values)", pending_cue.number, name, values.len()));
+        cues.push(Cue {
+            number: pending_cue.number,
+            name,
+            values,
+            timing: CueTiming {
+                fade: up,
+                delay,
+                intensity_down: pending_cue.down,
+                follow: pending_cue
+                    .follow
+                    .map(|seconds| FollowMode::Wait { seconds })
+                    .unwrap_or_default(),
+                ..CueTiming::default()
+            },
+            block: false,
+            effects: Vec::new(),
+            presets: HashMap::new(),
+        });
+    }
+
+    for (chan, line) in unmapped_channels {
+        report.unmapped(Some(line), format!("channel {}", chan), "no patched fixture at its DMX address");
+    }
+    Ok(cues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixtureChannel;
+
+    fn library() -> HashMap<String, GdtfFixtureType> {
+        let channel = |name: &str, offset| FixtureChannel {
+            name: name.to_string(),
+            offset,
+            default_value: 0,
+            channel_type: name.to_string(),
+        };
+        let par = GdtfFixtureType {
+            name: "LED PAR".to_string(),
+            manufacturer: "Generic".to_string(),
+            channels: vec![channel("Dimmer", 0), channel("Red", 1), channel("Green", 2), channel("Blue", 3)],
+            modes: vec!["4ch".to_string()],
+        };
+        [(par.name.clone(), par)].into_iter().collect()
+    }
+
+    #[test]
+    fn test_csv_patch_reports_unmapped_types() {
+        let csv = "Name,Type,Universe,Address,Mode\nPar 1,Generic LED PAR,1,1,4ch\nPar 2,LED PAR,2,5,\nSpot 1,Mystery Spot,1,20,16ch\n";
+        let mut report = ImportReport::default();
+        let fixtures = import_csv_patch(csv, &library(), &HashMap::new(), &mut report).unwrap();
+
+        assert_eq!(fixtures.len(), 2);
+        assert_eq!(fixtures[0].id, "par_1");
+        assert_eq!((fixtures[1].universe, fixtures[1].dmx_address, fixtures[1].channel_count), (1, 5, 4));
+        assert_eq!(report.unmapped.len(), 1);
+        assert_eq!(report.unmapped[0].line, Some(4));
+
+        // Overlapping the existing patch or an earlier row is left out
+        let existing: HashMap<String, Fixture> = fixtures.into_iter().map(|f| (f.id.clone(), f)).collect();
+        let csv = "Name,Type,Universe,Address\nPar 3,LED PAR,1,3\nPar 4,LED PAR,1,5\nPar 5,LED PAR,1,7\n";
+        let mut report = ImportReport::default();
+        let fixtures = import_csv_patch(csv, &library(), &existing, &mut report).unwrap();
+        assert_eq!(fixtures.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["Par 4"]);
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(report.unmapped.iter().map(|u| u.line).collect::<Vec<_>>(), vec![Some(2), Some(4)]);
+    }
+
+    #[test]
+    fn test_usitt_ascii_cues() {
+        let mut report = ImportReport::default();
+        let fixtures = import_csv_patch("name,type,universe,address\nPar,LED PAR,1,10\n", &library(), &HashMap::new(), &mut report).unwrap();
+        let fixtures: HashMap<String, Fixture> = fixtures.into_iter().map(|f| (f.id.clone(), f)).collect();
+
+        let ascii = "IDENT 3:0\nPATCH 1 1<10@100 2<11@100\nCUE 1\nTEXT Opening look\nUP 5 1\nDOWN 3\nCHAN 1/50 2/FL 3/H80\nCUE 2\nFOLLOW 2.5\nCHAN 1/0\nENDDATA\n";
+        let mut report = ImportReport::default();
+        let cues = import_usitt_ascii(ascii, &fixtures, &mut report).unwrap();
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].name, "Opening look");
+        assert_eq!(cues[0].values["par:0"], 128);
+        assert_eq!(cues[0].values["par:1"], 255);
+        assert_eq!((cues[0].timing.fade, cues[0].timing.delay, cues[0].timing.intensity_down), (5.0, 1.0, Some(3.0)));
+        assert_eq!(cues[1].timing.follow, FollowMode::Wait { seconds: 2.5 });
+        // Channel 3 has no patch entry, so it maps 1:1 to address 3 where nothing is patched
+        assert_eq!(report.unmapped.len(), 1);
+        assert_eq!(report.unmapped[0].item, "channel 3");
+    }
+}
@@ -117,6 +117,16 @@ impl StreamDeckManager {
         Err(format!("Device not found: {}", serial))
     }
 
+    /// Serial numbers of the devices currently connected
+    pub fn connected_devices(&self) -> Vec<String> {
+        let mut serials: Vec<String> = match self.devices.lock() {
+            Ok(devices) => devices.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        serials.sort();
+        serials
+    }
+
     pub fn disconnect_device(&self, serial: &str) -> Result<String, String> {
         let mut devices = self.devices.lock().map_err(|e| e.to_string())?;
 
@@ -0,0 +1,150 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File types the web remote accepts as videos
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "webm", "mkv", "avi", "mpg", "mpeg", "mxf"];
+
+/// Prefix of files still being uploaded. Like other dot files they're left out of the
+/// video list.
+const PARTIAL_PREFIX: &str = ".upload-";
+
+/// Longest file name most file systems accept, in bytes
+const MAX_NAME_BYTES: usize = 255;
+
+/// The client's file name without any directories or characters Windows and macOS don't
+/// allow, so it can't point outside the video directory
+pub fn sanitize_file_name(name: &str) -> Result<String, String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base.chars().filter(|c| !c.is_control() && !r#"<>:"|?*"#.contains(*c)).collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').trim_end_matches(['.', ' ']);
+    if cleaned.is_empty() {
+        return Err(format!("'{}' isn't a usable file name", name));
+    }
+    if cleaned.len() > MAX_NAME_BYTES {
+        return Err(format!("File names are limited to {} characters", MAX_NAME_BYTES));
+    }
+    Ok(cleaned.to_string())
+}
+
+/// Accept known video extensions, sent as a video type or as plain binary (browsers
+/// don't know a type for every container)
+pub fn check_video(name: &str, content_type: Option<&str>) -> Result<(), String> {
+    let extension = Path::new(name).extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+    if !extension.map(|e| VIDEO_EXTENSIONS.contains(&e.as_str())).unwrap_or(false) {
+        return Err(format!("'{}' isn't a supported video file ({})", name, VIDEO_EXTENSIONS.join(", ")));
+    }
+    if let Some(content_type) = content_type {
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        if !(mime.starts_with("video/") || mime == "application/octet-stream" || mime == "application/mxf") {
+            return Err(format!("'{}' was sent as {}, not as a video", name, mime));
+        }
+    }
+    Ok(())
+}
+
+/// Where an upload is written until it's complete
+pub fn partial_path(dir: &Path) -> PathBuf {
+    dir.join(format!("{}{:016x}.part", PARTIAL_PREFIX, rand::random::<u64>()))
+}
+
+/// Delete uploads left behind by a crash
+pub fn remove_partial_uploads(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(PARTIAL_PREFIX) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Claim `name` in `dir`, or "name (2).ext", "name (3).ext"... when it's taken. The file
+/// is created empty and exclusively, so two uploads of the same name can't both get it.
+pub fn reserve_name(dir: &Path, name: &str) -> io::Result<(String, PathBuf)> {
+    let path = Path::new(name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    for copy in 1..=1000 {
+        let candidate = match copy {
+            1 => name.to_string(),
+            n => format!("{} ({}){}", stem, n, extension),
+        };
+        let candidate_path = dir.join(&candidate);
+        match OpenOptions::new().write(true).create_new(true).open(&candidate_path) {
+            Ok(_) => return Ok((candidate, candidate_path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Too many videos named '{}'", name)))
+}
+
+/// Decides when an upload has made enough progress for another event: every 5% of a
+/// known size (at least 1 MB), otherwise every 8 MB
+pub struct ProgressThrottle {
+    step: u64,
+    next: u64,
+}
+
+impl ProgressThrottle {
+    pub fn new(total: Option<u64>) -> Self {
+        let step = total.map(|t| (t / 20).max(1024 * 1024)).unwrap_or(8 * 1024 * 1024);
+        ProgressThrottle { step, next: step }
+    }
+
+    pub fn should_report(&mut self, received: u64) -> bool {
+        if received < self.next {
+            return false;
+        }
+        self.next = (received / self.step + 1) * self.step;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_names_stay_in_the_video_directory() {
+        assert_eq!(sanitize_file_name("../../etc/passwd"), Ok("passwd".to_string()));
+        assert_eq!(sanitize_file_name("C:\\shows\\intro.mp4"), Ok("intro.mp4".to_string()));
+        assert_eq!(sanitize_file_name(" .hidden:clip?.mov. "), Ok("hiddenclip.mov".to_string()));
+        assert!(sanitize_file_name("..").is_err());
+        assert!(sanitize_file_name("videos/").is_err());
+
+        assert!(check_video("intro.MP4", Some("video/mp4")).is_ok());
+        assert!(check_video("intro.mkv", Some("application/octet-stream")).is_ok());
+        assert!(check_video("intro.mkv", None).is_ok());
+        assert!(check_video("intro.mp4", Some("text/html")).is_err());
+        assert!(check_video("script.sh", Some("video/mp4")).is_err());
+        assert!(check_video("mp4", None).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_names_get_a_number() {
+        let dir = std::env::temp_dir().join(format!("rocontrol-upload-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let (first, _) = reserve_name(&dir, "intro.mp4").unwrap();
+        let (second, _) = reserve_name(&dir, "intro.mp4").unwrap();
+        let (third, path) = reserve_name(&dir, "intro.mp4").unwrap();
+        assert_eq!((first.as_str(), second.as_str(), third.as_str()), ("intro.mp4", "intro (2).mp4", "intro (3).mp4"));
+        assert!(path.exists());
+
+        fs::write(partial_path(&dir), b"half a video").unwrap();
+        remove_partial_uploads(&dir);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_progress_throttle() {
+        let mut throttle = ProgressThrottle::new(Some(100 * 1024 * 1024));
+        assert!(!throttle.should_report(1024));
+        assert!(throttle.should_report(5 * 1024 * 1024));
+        assert!(!throttle.should_report(6 * 1024 * 1024));
+        assert!(throttle.should_report(23 * 1024 * 1024));
+        assert!(!throttle.should_report(24 * 1024 * 1024));
+        assert!(throttle.should_report(25 * 1024 * 1024));
+    }
+}
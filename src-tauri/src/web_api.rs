@@ -0,0 +1,289 @@
+use axum::{
+    body::HttpBody,
+    extract::DefaultBodyLimit,
+    handler::Handler,
+    http::{header, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{self, MethodRouter},
+    Json,
+};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+use crate::web_auth;
+
+/// Current REST API version. Routes live under `/api/v1`; breaking changes get a new prefix.
+pub const API_PREFIX: &str = "/api/v1";
+
+/// Body of every failed API request
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApiError {
+    /// HTTP status code, repeated for clients that only see the body
+    pub status: u16,
+    /// Status as a snake_case word: bad_request, unauthorized, forbidden, not_found...
+    pub error: String,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        let error = status.canonical_reason().unwrap_or("error").to_lowercase().replace([' ', '-'], "_");
+        ApiError { status: status.as_u16(), error, message: message.into() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn internal(e: impl fmt::Display) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        Self::new(status, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Response middleware for the API routes: errors axum produces itself (unknown routes,
+/// malformed JSON, bad path parameters) get the same body as handler errors
+pub async fn error_bodies(response: Response) -> Response {
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("application/json"))
+        .unwrap_or(false);
+    if !(status.is_client_error() || status.is_server_error()) || is_json {
+        return response;
+    }
+    let mut body = response.into_body();
+    let mut text = Vec::new();
+    while let Some(Ok(chunk)) = body.data().await {
+        text.extend_from_slice(&chunk);
+    }
+    let message = String::from_utf8_lossy(&text).trim().to_string();
+    let message = if message.is_empty() { status.canonical_reason().unwrap_or_default().to_string() } else { message };
+    ApiError::new(status, message).into_response()
+}
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+enum RequestBody {
+    Json(SchemaFn),
+    /// File upload form
+    Multipart,
+}
+
+/// A REST route and what the OpenAPI document says about it. The router and the document
+/// are built from the same list, so they can't disagree.
+pub struct Endpoint<S> {
+    pub method: Method,
+    /// Axum path below the API prefix, e.g. "/chaser/:id/start"
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub route: MethodRouter<S>,
+    params: Vec<(&'static str, SchemaFn)>,
+    request: Option<RequestBody>,
+    response: Option<SchemaFn>,
+}
+
+impl<S: Clone + Send + Sync + 'static> Endpoint<S> {
+    fn new(method: Method, path: &'static str, summary: &'static str, route: MethodRouter<S>) -> Self {
+        Endpoint { method, path, summary, route, params: Vec::new(), request: None, response: None }
+    }
+
+    pub fn get<H: Handler<T, S>, T: 'static>(path: &'static str, summary: &'static str, handler: H) -> Self {
+        Self::new(Method::GET, path, summary, routing::get(handler))
+    }
+
+    pub fn post<H: Handler<T, S>, T: 'static>(path: &'static str, summary: &'static str, handler: H) -> Self {
+        Self::new(Method::POST, path, summary, routing::post(handler))
+    }
+
+    pub fn patch<H: Handler<T, S>, T: 'static>(path: &'static str, summary: &'static str, handler: H) -> Self {
+        Self::new(Method::PATCH, path, summary, routing::patch(handler))
+    }
+
+    pub fn delete<H: Handler<T, S>, T: 'static>(path: &'static str, summary: &'static str, handler: H) -> Self {
+        Self::new(Method::DELETE, path, summary, routing::delete(handler))
+    }
+
+    /// Type of the `:name` path parameter
+    pub fn param<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        self.params.push((name, |g| g.subschema_for::<T>()));
+        self
+    }
+
+    /// JSON request body
+    pub fn body<T: JsonSchema>(mut self) -> Self {
+        self.request = Some(RequestBody::Json(|g| g.subschema_for::<T>()));
+        self
+    }
+
+    /// File upload form. Uploads aren't held to axum's 2 MB body limit; the handler
+    /// enforces its own while it streams the file.
+    pub fn multipart(mut self) -> Self {
+        self.request = Some(RequestBody::Multipart);
+        self.route = self.route.layer(DefaultBodyLimit::disable());
+        self
+    }
+
+    /// JSON body of a successful response
+    pub fn returns<T: JsonSchema>(mut self) -> Self {
+        self.response = Some(|g| g.subschema_for::<T>());
+        self
+    }
+}
+
+/// "/chaser/:id/start" as OpenAPI writes it: "/chaser/{id}/start"
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn json_content(schema: Schema) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+/// OpenAPI 3.0 description of `endpoints`, with schemas generated from the Rust types
+pub fn document<S>(endpoints: &[Endpoint<S>]) -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let error = generator.subschema_for::<ApiError>();
+    let mut paths = Map::new();
+
+    for endpoint in endpoints {
+        let mut operation = Map::new();
+        operation.insert("summary".into(), endpoint.summary.into());
+        let tag = endpoint.path.split('/').find(|s| !s.is_empty()).unwrap_or("api");
+        operation.insert("tags".into(), json!([tag]));
+
+        let parameters: Vec<Value> = endpoint
+            .path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix(':'))
+            .map(|name| {
+                let schema = match endpoint.params.iter().find(|(param, _)| *param == name) {
+                    Some((_, schema)) => schema(&mut generator),
+                    None => generator.subschema_for::<String>(),
+                };
+                json!({ "name": name, "in": "path", "required": true, "schema": schema })
+            })
+            .collect();
+        if !parameters.is_empty() {
+            operation.insert("parameters".into(), parameters.into());
+        }
+
+        match &endpoint.request {
+            Some(RequestBody::Json(schema)) => {
+                let content = json_content(schema(&mut generator));
+                operation.insert("requestBody".into(), json!({ "required": true, "content": content }));
+            }
+            Some(RequestBody::Multipart) => {
+                let form = json!({
+                    "type": "object",
+                    "properties": { "file": { "type": "string", "format": "binary" } },
+                });
+                operation.insert(
+                    "requestBody".into(),
+                    json!({ "required": true, "content": { "multipart/form-data": { "schema": form } } }),
+                );
+            }
+            None => {}
+        }
+
+        let mut ok = json!({ "description": "Success" });
+        if let Some(schema) = endpoint.response {
+            ok["content"] = json_content(schema(&mut generator));
+        }
+        let failed = json!({ "description": "Error", "content": json_content(error.clone()) });
+        operation.insert("responses".into(), json!({ "200": ok, "default": failed }));
+
+        let role = web_auth::required_role(&endpoint.method, &format!("{}{}", API_PREFIX, endpoint.path));
+        let security = match role {
+            Some(_) => json!([{ "session": [] }]),
+            None => json!([]),
+        };
+        operation.insert("security".into(), security);
+        if let Some(role) = role {
+            operation.insert("x-required-role".into(), role.to_string().into());
+        }
+
+        let item = paths.entry(openapi_path(endpoint.path)).or_insert_with(|| json!({}));
+        item[endpoint.method.as_str().to_lowercase()] = Value::Object(operation);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "RoControl API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "REST API of the RoControl web remote. When login is required, \
+                send the token from POST /login as `Authorization: Bearer <token>`. \
+                Live updates use the WebSocket at /ws.",
+        },
+        "servers": [{ "url": API_PREFIX }],
+        "paths": paths,
+        "components": {
+            "schemas": generator.take_definitions(),
+            "securitySchemes": { "session": { "type": "http", "scheme": "bearer" } },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct Tempo {
+        bpm: f64,
+    }
+
+    async fn handler() {}
+
+    #[test]
+    fn test_error_body() {
+        let error = ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Slow down");
+        let body = serde_json::to_value(&error).unwrap();
+        assert_eq!(body, json!({ "status": 429, "error": "too_many_requests", "message": "Slow down" }));
+        assert_eq!(ApiError::from((StatusCode::FORBIDDEN, "No".to_string())).error, "forbidden");
+    }
+
+    #[test]
+    fn test_document_from_endpoints() {
+        let endpoints: Vec<Endpoint<()>> = vec![
+            Endpoint::post("/chaser/:id/tempo", "Set a chaser's tempo", handler).param::<u32>("id").body::<Tempo>(),
+            Endpoint::get("/login", "Log in", handler),
+        ];
+        let document = document(&endpoints);
+        let operation = &document["paths"]["/chaser/{id}/tempo"]["post"];
+        assert_eq!(operation["parameters"][0]["schema"]["type"], "integer");
+        assert_eq!(operation["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/Tempo");
+        assert_eq!(operation["x-required-role"], "operator");
+        assert_eq!(document["paths"]["/login"]["get"]["security"], json!([]));
+        assert!(document["components"]["schemas"]["ApiError"].is_object());
+    }
+}
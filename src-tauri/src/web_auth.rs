@@ -0,0 +1,369 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cli::CliCommand;
+use crate::web_api::ApiError;
+use crate::web_settings::WebSettings;
+use crate::ws_protocol::ClientMessage;
+
+/// Sessions last a show day and are extended while in use
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Failed logins from one address before it has to wait
+const MAX_FAILED_LOGINS: u32 = 5;
+const LOGIN_LOCKOUT: Duration = Duration::from_secs(30);
+
+/// What a web client may do. Each role includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Watch status, lists and output levels
+    Viewer,
+    /// Run playback: go, chasers, executors, blackout
+    Operator,
+    /// Select fixtures, set values, patch, record and undo
+    Programmer,
+    /// Upload files, manage NDI sources and web users
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Programmer => "programmer",
+            Role::Admin => "admin",
+        };
+        f.write_str(name)
+    }
+}
+
+pub fn hash_secret(secret: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(secret.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn verify_secret(secret: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| Argon2::default().verify_password(secret.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Who a request comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Access {
+    pub user: Option<String>,
+    pub role: Role,
+    #[serde(skip)]
+    pub token: Option<String>,
+    /// Address the request came from
+    #[serde(skip)]
+    pub client: Option<IpAddr>,
+}
+
+impl Access {
+    /// Who made an edit, for the undo history: the client address, plus the user when
+    /// logged in
+    pub fn source(&self) -> String {
+        let client = self.client.map(|ip| ip.to_string()).unwrap_or_else(|| "web".to_string());
+        match &self.user {
+            Some(user) => format!("{}@{}", user, client),
+            None => client,
+        }
+    }
+
+    pub fn require(&self, role: Role) -> Result<(), (StatusCode, String)> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err((StatusCode::FORBIDDEN, format!("Needs {} access", role)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LoginReply {
+    pub token: String,
+    pub user: String,
+    pub role: Role,
+}
+
+struct Session {
+    user: String,
+    expires: Instant,
+}
+
+/// Web logins: the installation's web settings plus the open sessions
+#[derive(Clone)]
+pub struct Auth {
+    pub settings: Arc<Mutex<WebSettings>>,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+    /// Failed logins per address: count and time of the last one
+    failures: Arc<Mutex<HashMap<IpAddr, (u32, Instant)>>>,
+}
+
+impl Auth {
+    pub fn new(settings: Arc<Mutex<WebSettings>>) -> Self {
+        Auth { settings, sessions: Arc::default(), failures: Arc::default() }
+    }
+
+    /// Who is behind `token`. Without required login everyone is admin. Roles come from
+    /// the settings each time, so removed or demoted users lose access right away.
+    pub fn access(&self, token: Option<&str>) -> Result<Access, (StatusCode, String)> {
+        let settings = self.settings.lock().map_err(internal)?;
+        let mut sessions = self.sessions.lock().map_err(internal)?;
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires > now);
+
+        let session = token.and_then(|token| sessions.get_mut(token).map(|session| (token, session)));
+        let user = session.and_then(|(token, session)| {
+            session.expires = now + SESSION_TTL;
+            settings.user(&session.user).map(|user| (token, user))
+        });
+        match user {
+            Some((token, user)) => Ok(Access {
+                user: Some(user.name.clone()),
+                role: if settings.require_login { user.role } else { Role::Admin },
+                token: Some(token.to_string()),
+                client: None,
+            }),
+            None if !settings.require_login => Ok(Access { user: None, role: Role::Admin, token: None, client: None }),
+            None => Err((StatusCode::UNAUTHORIZED, "Login required".to_string())),
+        }
+    }
+
+    /// Log in with a PIN or password, optionally for a named user
+    pub fn login(&self, from: IpAddr, user: Option<&str>, secret: &str) -> Result<LoginReply, (StatusCode, String)> {
+        {
+            let failures = self.failures.lock().map_err(internal)?;
+            if let Some((count, last)) = failures.get(&from) {
+                if *count >= MAX_FAILED_LOGINS && last.elapsed() < LOGIN_LOCKOUT {
+                    let wait = (LOGIN_LOCKOUT - last.elapsed()).as_secs() + 1;
+                    return Err((StatusCode::TOO_MANY_REQUESTS, format!("Too many failed logins, try again in {} s", wait)));
+                }
+            }
+        }
+
+        let found = {
+            let settings = self.settings.lock().map_err(internal)?;
+            settings
+                .users
+                .iter()
+                .filter(|u| user.map(|name| name == u.name).unwrap_or(true))
+                .find(|u| verify_secret(secret, &u.secret_hash))
+                .map(|u| (u.name.clone(), u.role))
+        };
+
+        let mut failures = self.failures.lock().map_err(internal)?;
+        let Some((user, role)) = found else {
+            let entry = failures.entry(from).or_insert((0, Instant::now()));
+            if entry.1.elapsed() >= LOGIN_LOCKOUT {
+                entry.0 = 0;
+            }
+            entry.0 += 1;
+            entry.1 = Instant::now();
+            return Err((StatusCode::UNAUTHORIZED, "Wrong PIN or password".to_string()));
+        };
+        failures.remove(&from);
+
+        let token = new_token();
+        let session = Session { user: user.clone(), expires: Instant::now() + SESSION_TTL };
+        self.sessions.lock().map_err(internal)?.insert(token.clone(), session);
+        Ok(LoginReply { token, user, role })
+    }
+
+    pub fn logout(&self, token: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(token);
+        }
+    }
+}
+
+fn internal(e: impl fmt::Display) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Role a route needs. None for the pages and calls needed to log in.
+/// `/api/command` and `/ws` check each command on its own. Versioned paths need the same
+/// role as the unversioned ones.
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+    let path = match path.strip_prefix(crate::web_api::API_PREFIX) {
+        Some(rest) => format!("/api{}", rest),
+        None => path.to_string(),
+    };
+    let path = path.trim_end_matches('/');
+    match path {
+        "" | "/api/login" | "/api/logout" | "/api/session" | "/api/openapi.json" => return None,
+        "/ws" | "/api/command" => return Some(Role::Viewer),
+        _ if path.starts_with("/api/auth") => return Some(Role::Admin),
+        _ => {}
+    }
+    if method == Method::GET {
+        return Some(Role::Viewer);
+    }
+    match path {
+        "/api/video/upload" | "/api/ndi/add" | "/api/autosave" => Some(Role::Admin),
+        _ if path.starts_with("/api/ndi/remove") => Some(Role::Admin),
+        "/api/selection" | "/api/undo" | "/api/redo" | "/api/programmer" => Some(Role::Programmer),
+        _ if path.starts_with("/api/fixtures") => Some(Role::Programmer),
+        _ => Some(Role::Operator),
+    }
+}
+
+/// Role a CLI command needs
+pub fn command_role(command: &CliCommand) -> Role {
+    match command {
+        CliCommand::Help => Role::Viewer,
+        CliCommand::Go { .. } | CliCommand::RecallCue { .. } | CliCommand::Blackout | CliCommand::Desktop { .. } => {
+            Role::Operator
+        }
+        _ => Role::Programmer,
+    }
+}
+
+/// Role a WebSocket request needs. Command lines that don't parse need nothing, so the
+/// client gets the parse error.
+pub fn message_role(message: &ClientMessage) -> Role {
+    match message {
+        ClientMessage::Command { command, .. } => {
+            crate::cli::parse(command).map(|c| command_role(&c)).unwrap_or(Role::Viewer)
+        }
+        ClientMessage::Select { .. } | ClientMessage::Undo { .. } | ClientMessage::Redo { .. } => Role::Programmer,
+        ClientMessage::ChaserTap { .. } => Role::Operator,
+        ClientMessage::Ping { .. } | ClientMessage::SubscribeLevels { .. } | ClientMessage::UnsubscribeLevels { .. } => {
+            Role::Viewer
+        }
+    }
+}
+
+/// Session token from `Authorization: Bearer <token>`. Only the WebSocket upgrade takes
+/// `?token=` too, since browsers can't set headers on it; elsewhere a token in the URL
+/// would end up in access logs and browser history.
+pub fn request_token<B>(request: &Request<B>) -> Option<String> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    if bearer.is_some() || request.uri().path() != "/ws" {
+        return bearer;
+    }
+    request
+        .uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(|token| token.to_string())
+}
+
+/// Route middleware: checks the route's role and hands the handlers an `Access`. Public
+/// routes get one only when the client is logged in (or login isn't required).
+pub async fn authorize<B>(State(auth): State<Auth>, mut request: Request<B>, next: Next<B>) -> Response {
+    let token = request_token(&request);
+    let client = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(from)| from.ip());
+    let access = auth.access(token.as_deref()).map(|access| Access { client, ..access });
+    match (required_role(request.method(), request.uri().path()), access) {
+        (Some(role), Ok(access)) => {
+            if let Err(e) = access.require(role) {
+                return ApiError::from(e).into_response();
+            }
+            request.extensions_mut().insert(access);
+        }
+        (Some(_), Err(e)) => return ApiError::from(e).into_response(),
+        (None, Ok(access)) => {
+            request.extensions_mut().insert(access);
+        }
+        (None, Err(_)) => {}
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_settings::WebUser;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_route_roles() {
+        assert_eq!(required_role(&Method::GET, "/"), None);
+        assert_eq!(required_role(&Method::POST, "/api/login"), None);
+        assert_eq!(required_role(&Method::GET, "/api/chasers"), Some(Role::Viewer));
+        assert_eq!(required_role(&Method::POST, "/api/chaser/1/start"), Some(Role::Operator));
+        assert_eq!(required_role(&Method::POST, "/api/selection"), Some(Role::Programmer));
+        assert_eq!(required_role(&Method::PATCH, "/api/v1/fixtures/3"), Some(Role::Programmer));
+        assert_eq!(required_role(&Method::POST, "/api/v1/blackout"), Some(Role::Operator));
+        assert_eq!(required_role(&Method::POST, "/api/video/upload"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::POST, "/api/v1/autosave"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::GET, "/api/auth/users"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::POST, "/api/v1/chaser/1/start"), Some(Role::Operator));
+        assert_eq!(required_role(&Method::GET, "/api/v1/openapi.json"), None);
+        assert!(Role::Admin > Role::Programmer && Role::Operator > Role::Viewer);
+
+        let go = crate::cli::parse("go cue 2").unwrap();
+        assert_eq!(command_role(&go), Role::Operator);
+        assert_eq!(command_role(&crate::cli::parse("record cue 2").unwrap()), Role::Programmer);
+    }
+
+    #[test]
+    fn test_query_token_only_on_websocket() {
+        let request = |uri: &str| Request::builder().uri(uri).body(()).unwrap();
+        assert_eq!(request_token(&request("/ws?token=abc")), Some("abc".to_string()));
+        assert_eq!(request_token(&request("/api/v1/fixtures?token=abc")), None);
+
+        let bearer = Request::builder().uri("/api/v1/fixtures").header(header::AUTHORIZATION, "Bearer xyz").body(()).unwrap();
+        assert_eq!(request_token(&bearer), Some("xyz".to_string()));
+    }
+
+    #[test]
+    fn test_login_sessions_and_lockout() {
+        let settings = WebSettings {
+            require_login: true,
+            users: vec![WebUser { name: "op".to_string(), role: Role::Operator, secret_hash: hash_secret("1234").unwrap() }],
+            ..WebSettings::default()
+        };
+        let auth = Auth::new(Arc::new(Mutex::new(settings)));
+        let from = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(auth.access(None).unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let login = auth.login(from, None, "1234").unwrap();
+        let access = auth.access(Some(&login.token)).unwrap();
+        assert_eq!((access.user.as_deref(), access.role), (Some("op"), Role::Operator));
+        assert!(access.require(Role::Programmer).is_err());
+
+        // Removing the user ends the session
+        auth.settings.lock().unwrap().users.clear();
+        assert!(auth.access(Some(&login.token)).is_err());
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert_eq!(auth.login(from, None, "0000").unwrap_err().0, StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(auth.login(from, None, "1234").unwrap_err().0, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
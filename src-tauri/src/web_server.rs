@@ -1,155 +1,640 @@
 use axum::{
-    extract::{Multipart, Path, State, ws::{Message, WebSocket, WebSocketUpgrade}},
-    http::StatusCode,
+    extract::{ConnectInfo, Multipart, Path, State, multipart::Field, ws::{Message, WebSocket, WebSocketUpgrade}},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Response},
-    routing::{get, post},
-    Json, Router,
+    routing::get,
+    Extension, Json, Router,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use tokio::sync::broadcast;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use std::path::PathBuf;
 use std::fs;
 use futures::{StreamExt, SinkExt};
-use crate::ndi_support::{NdiManager, NdiSource};
+use tokio::io::AsyncWriteExt;
+use crate::ndi_support::NdiSource;
+use crate::chaser::ChaserStatus;
+use crate::cli;
+use crate::actions;
+use crate::autosave::{self, AutosaveSettings};
+use crate::health::{ErrorReport, Health, UniverseRate};
+use crate::history::{self, Domain};
+use crate::patch::{self, FixtureUpdate};
+use crate::selection::{self, SelectionCommand, SelectionStatus};
+use crate::video_upload::{self, ProgressThrottle};
+use crate::web_api::{self, ApiError, Endpoint};
+use crate::web_auth::{self, Access, LoginReply, Role};
+use crate::web_settings::{self, ServerSettings, WebAccessStatus};
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::IpAddr;
+use std::time::Duration;
+use crate::ws_protocol::{ClientMessage, Event, LevelStream, LevelSubscription, ServerMessage};
+use crate::{AppState, Fixture};
+use std::net::SocketAddr;
 
-#[derive(Clone)]
-pub struct AppState {
-    pub tx: broadcast::Sender<String>,
-    pub video_dir: PathBuf,
-    pub ndi_manager: Arc<NdiManager>,
-}
-
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CommandRequest {
     pub command: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CommandResponse {
     pub success: bool,
     pub message: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct LoginRequest {
+    /// Only needed when users share a PIN
+    #[serde(default)]
+    pub user: Option<String>,
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SessionStatus {
+    pub login_required: bool,
+    pub user: Option<String>,
+    /// None until logged in
+    pub role: Option<Role>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WebUserRequest {
+    pub name: String,
+    pub role: Role,
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AuthSettingsRequest {
+    pub require_login: Option<bool>,
+    pub allowed_origins: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct VideoFile {
     pub name: String,
     pub size: u64,
     pub path: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SteamDeckButtonEvent {
     pub button: String,
     pub pressed: bool,
     pub timestamp: u64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct WindowNavigationRequest {
     pub window_id: u32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CueExecutionRequest {
     pub cue_number: Option<u32>,
     pub action: String, // "go", "pause", "resume", "stop"
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ExecutorRequest {
     pub executor_number: Option<u32>,
     pub action: String, // "go", "pause", "resume", "stop", "flash"
 }
 
-/// Start the web server on port 8080
-pub async fn start_server(video_dir: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    let (tx, _rx) = broadcast::channel(100);
+/// Output levels of one universe
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct UniverseLevels {
+    /// Art-Net numbering, 0-based
+    pub universe: u8,
+    /// Channels 1-512
+    pub levels: Vec<u8>,
+}
 
-    // Initialize NDI manager
-    let ndi_manager = Arc::new(NdiManager::new());
+/// A universe that has output
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct UniverseSummary {
+    pub universe: u8,
+    /// Channels above zero
+    pub active_channels: usize,
+}
 
-    // Start NDI discovery
-    ndi_manager.start_discovery().await?;
+/// DMX output as configured on the engine
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct OutputStatus {
+    /// "artnet" or "sacn"
+    pub protocol: String,
+    /// False when the protocol's socket couldn't be opened
+    pub ready: bool,
+    /// Art-Net broadcast address
+    pub destination: String,
+    /// Local interface the output is bound to, None for all
+    pub interface: Option<String>,
+    /// Universes transmitted since startup
+    pub universes: Vec<UniverseRate>,
+}
+
+/// Health of the running console, for monitoring
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ServerStatus {
+    pub status: String,
+    pub version: String,
+    pub uptime_seconds: u64,
+    pub output: OutputStatus,
+    /// REST routes as "METHOD /path", plus the WebSocket
+    pub routes: Vec<String>,
+    /// Serial numbers of connected Stream Decks
+    pub stream_decks: Vec<String>,
+    pub ndi_sources: usize,
+    pub websocket_clients: usize,
+    pub show_name: String,
+    /// None until the show is saved or loaded
+    pub show_path: Option<String>,
+    pub last_error: Option<ErrorReport>,
+}
+
+/// Where the web server listens and how to reach it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebServerStatus {
+    pub bind_address: String,
+    pub port: u16,
+    pub tls: bool,
+    /// Certificate in use; browsers ask before trusting a self-signed one
+    pub certificate: Option<PathBuf>,
+    pub urls: Vec<String>,
+}
+
+/// The running web server, kept so it can be restarted with other settings
+pub struct WebServerControl {
+    app: Router,
+    settings: ServerSettings,
+    status: WebServerStatus,
+    handle: axum_server::Handle,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl WebServerControl {
+    pub fn status(&self) -> WebServerStatus {
+        self.status.clone()
+    }
+
+    /// Close connections and wait for the listener to be released
+    async fn stop(self) {
+        self.handle.graceful_shutdown(Some(Duration::from_secs(1)));
+        let _ = self.task.await;
+    }
+}
+
+/// Listen with `settings` and serve `app` in the background
+async fn serve(app: Router, settings: &ServerSettings, health: Arc<Health>) -> Result<WebServerControl, String> {
+    let addr = settings.socket_addr()?;
+    let tls = match settings.tls {
+        true => {
+            let (cert, key) = certificate_files(settings)?;
+            let config = RustlsConfig::from_pem_file(&cert, &key)
+                .await
+                .map_err(|e| format!("Can't load certificate {}: {}", cert.display(), e))?;
+            Some((config, cert))
+        }
+        false => None,
+    };
+
+    let listener = std::net::TcpListener::bind(addr).map_err(|e| format!("Can't listen on {}: {}", addr, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let handle = axum_server::Handle::new();
+    let service = app.clone().into_make_service_with_connect_info::<SocketAddr>();
+    let (task, certificate) = match tls {
+        Some((config, cert)) => {
+            let server = axum_server::from_tcp_rustls(listener, config).handle(handle.clone());
+            (tokio::spawn(async move { report(server.serve(service).await, &health) }), Some(cert))
+        }
+        None => {
+            let server = axum_server::from_tcp(listener).handle(handle.clone());
+            (tokio::spawn(async move { report(server.serve(service).await, &health) }), None)
+        }
+    };
 
-    let state = AppState {
-        tx,
-        video_dir,
-        ndi_manager,
+    let interfaces: Vec<IpAddr> = if_addrs::get_if_addrs()
+        .map(|list| list.into_iter().map(|i| i.ip()).collect())
+        .unwrap_or_default();
+    let status = WebServerStatus {
+        bind_address: addr.ip().to_string(),
+        port: addr.port(),
+        tls: settings.tls,
+        certificate,
+        urls: reachable_urls(addr, settings.tls, &interfaces),
     };
+    Ok(WebServerControl { app, settings: settings.clone(), status, handle, task })
+}
+
+fn report(result: std::io::Result<()>, health: &Health) {
+    if let Err(e) = result {
+        eprintln!("Web server error: {}", e);
+        health.report_error("web", &e);
+    }
+}
+
+/// URLs clients can use: every IPv4 interface when listening on all of them
+fn reachable_urls(addr: SocketAddr, tls: bool, interfaces: &[IpAddr]) -> Vec<String> {
+    let scheme = if tls { "https" } else { "http" };
+    let mut ips: Vec<IpAddr> = if addr.ip().is_unspecified() {
+        interfaces.iter().copied().filter(|ip| ip.is_ipv4() && !ip.is_loopback()).collect()
+    } else {
+        vec![addr.ip()]
+    };
+    if addr.ip().is_unspecified() || addr.ip().is_loopback() {
+        ips.push(IpAddr::from([127, 0, 0, 1]));
+    }
+    ips.dedup();
+    ips.iter().map(|ip| format!("{}://{}", scheme, SocketAddr::new(*ip, addr.port()))).collect()
+}
+
+/// Certificate and key for HTTPS: the configured files, or a self-signed pair made once
+/// for this machine's names and addresses
+fn certificate_files(settings: &ServerSettings) -> Result<(PathBuf, PathBuf), String> {
+    if let (Some(cert), Some(key)) = (&settings.cert_path, &settings.key_path) {
+        return Ok((cert.clone(), key.clone()));
+    }
+    let dir = web_settings::tls_dir();
+    let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+    if cert.exists() && key.exists() {
+        return Ok((cert, key));
+    }
 
+    let mut names = vec!["localhost".to_string()];
+    if let Ok(interfaces) = if_addrs::get_if_addrs() {
+        names.extend(interfaces.iter().map(|i| i.ip().to_string()));
+    }
+    let certificate = rcgen::generate_simple_self_signed(names).map_err(|e| e.to_string())?;
+    let cert_pem = certificate.serialize_pem().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    fs::write(&cert, cert_pem).map_err(|e| e.to_string())?;
+    write_private(&key, certificate.serialize_private_key_pem().as_bytes())?;
+    println!("[Web] Generated a self-signed certificate at {}", cert.display());
+    Ok((cert, key))
+}
+
+/// Write a file only the current user can read
+fn write_private(path: &std::path::Path, contents: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path).and_then(|mut file| file.write_all(contents)).map_err(|e| e.to_string())
+}
+
+/// Restart the web server with new settings and save them. If it can't listen with them,
+/// it goes back to the previous ones.
+pub async fn restart(console: &AppState, settings: ServerSettings) -> Result<WebServerStatus, String> {
+    settings.socket_addr()?;
+    let mut running = console.web_server.lock().await;
+    let previous = running.take().ok_or("The web server isn't running")?;
+    let (app, previous_settings) = (previous.app.clone(), previous.settings.clone());
+    previous.stop().await;
+
+    match serve(app.clone(), &settings, console.health.clone()).await {
+        Ok(control) => {
+            let status = control.status();
+            *running = Some(control);
+            println!("Web Remote Server listening on {}", status.urls.join(", "));
+            web_settings::update(&console.web_settings, |s| {
+                s.server = settings;
+                Ok(())
+            })
+            .map_err(|e| format!("Restarted, but the settings weren't saved: {}", e))?;
+            Ok(status)
+        }
+        Err(e) => {
+            *running = Some(serve(app, &previous_settings, console.health.clone()).await?);
+            Err(e)
+        }
+    }
+}
+
+/// Start the web server with the saved settings. It runs in the background; `restart`
+/// applies new settings.
+pub async fn start_server(state: AppState) -> Result<(), Box<dyn std::error::Error>> {
+    // Start NDI discovery
+    state.ndi_manager.start_discovery().await?;
+
+    // Discovered and manually added sources reach web clients as events
+    let mut ndi_sources = state.ndi_manager.subscribe();
+    let events = state.events.clone();
+    tokio::spawn(async move {
+        loop {
+            match ndi_sources.recv().await {
+                Ok(source) => {
+                    let _ = events.send(Event::NdiSourceAdded { source });
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    // Browsers only let the configured origins call the API; the web remote is same-origin
+    let web_settings = state.web_settings.clone();
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            let origin = origin.to_str().unwrap_or_default();
+            web_settings.lock().map(|s| s.allows_origin(origin)).unwrap_or(false)
+        }))
         .allow_methods(Any)
         .allow_headers(Any);
 
+    let mut api = Router::new().route("/openapi.json", get(openapi_handler));
+    for endpoint in endpoints() {
+        api = api.route(endpoint.path, endpoint.route);
+    }
+    let api = api
+        .fallback(|| async { ApiError::not_found("No such API route") })
+        .layer(middleware::map_response(web_api::error_bodies));
+
     let app = Router::new()
         .route("/", get(index_handler))
-        .route("/api/command", post(command_handler))
-        .route("/api/status", get(status_handler))
-        .route("/api/videos", get(list_videos))
-        .route("/api/video/upload", post(upload_video))
-        .route("/api/video/:name", get(get_video))
-        // Steam Deck integration endpoints
-        .route("/api/steamdeck/button", post(steamdeck_button_handler))
-        .route("/api/steamdeck/window", post(navigate_window_handler))
-        .route("/api/steamdeck/cue", post(cue_handler))
-        .route("/api/steamdeck/executor", post(executor_handler))
-        // NDI endpoints
-        .route("/api/ndi/sources", get(ndi_list_sources))
-        .route("/api/ndi/discover", post(ndi_start_discovery))
-        .route("/api/ndi/add", post(ndi_add_manual_source))
-        .route("/api/ndi/remove/:name", post(ndi_remove_source))
-        .route("/api/ndi/test/:name", get(ndi_test_connection))
+        .nest(web_api::API_PREFIX, api.clone())
+        // Unversioned paths from before /api/v1, kept for existing scripts
+        .nest("/api", api)
         .route("/ws", get(ws_handler))
+        .route_layer(middleware::from_fn_with_state(state.web_auth.clone(), web_auth::authorize))
         .layer(cors)
-        .with_state(state);
+        .with_state(state.clone());
 
-    let addr = "0.0.0.0:8080".parse::<std::net::SocketAddr>().unwrap();
-    println!("Web Remote Server starting on http://{}", addr);
-
-    // axum 0.6 API - use hyper::Server
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await?;
+    let settings = state.web_settings.lock().map_err(|e| e.to_string())?.server.clone();
+    let control = serve(app, &settings, state.health.clone()).await?;
+    println!("Web Remote Server listening on {}", control.status.urls.join(", "));
+    *state.web_server.lock().await = Some(control);
 
     Ok(())
 }
 
+/// The REST API, served under `/api/v1` and described by its OpenAPI document
+fn endpoints() -> Vec<Endpoint<AppState>> {
+    vec![
+        Endpoint::post("/command", "Run a CLI command line", command_handler)
+            .body::<CommandRequest>()
+            .returns::<cli::CommandResult>(),
+        // Web remote logins
+        Endpoint::post("/login", "Log in with a PIN or password", login_handler)
+            .body::<LoginRequest>()
+            .returns::<LoginReply>(),
+        Endpoint::post("/logout", "End this session", logout_handler).returns::<CommandResponse>(),
+        Endpoint::get("/session", "Whether login is required and who is logged in", session_handler)
+            .returns::<SessionStatus>(),
+        Endpoint::get("/auth", "Web users and access settings", auth_get).returns::<WebAccessStatus>(),
+        Endpoint::post("/auth/settings", "Require login or change allowed origins", auth_settings)
+            .body::<AuthSettingsRequest>()
+            .returns::<WebAccessStatus>(),
+        Endpoint::post("/auth/users", "Add a web user or change one", auth_save_user)
+            .body::<WebUserRequest>()
+            .returns::<WebAccessStatus>(),
+        Endpoint::post("/auth/users/remove/:name", "Remove a web user", auth_remove_user).returns::<WebAccessStatus>(),
+        Endpoint::get("/status", "Version, uptime, output and connected devices", status_handler).returns::<ServerStatus>(),
+        Endpoint::get("/autosave", "Autosave interval and backup count", autosave_get).returns::<AutosaveSettings>(),
+        Endpoint::post("/autosave", "Change the autosave interval and backup count", autosave_set)
+            .body::<AutosaveSettings>()
+            .returns::<AutosaveSettings>(),
+        Endpoint::get("/videos", "Video files", list_videos).returns::<Vec<VideoFile>>(),
+        Endpoint::post("/video/upload", "Upload a video file", upload_video).multipart().returns::<VideoFile>(),
+        Endpoint::get("/video/:name", "Video file details", get_video).returns::<VideoFile>(),
+        // Steam Deck integration
+        Endpoint::post("/steamdeck/button", "Steam Deck button event", steamdeck_button_handler)
+            .body::<SteamDeckButtonEvent>()
+            .returns::<CommandResponse>(),
+        Endpoint::post("/steamdeck/window", "Show a window on the desktop", navigate_window_handler)
+            .body::<WindowNavigationRequest>()
+            .returns::<CommandResponse>(),
+        Endpoint::post("/steamdeck/cue", "Cue action for the desktop UI", cue_handler)
+            .body::<CueExecutionRequest>()
+            .returns::<CommandResponse>(),
+        Endpoint::post("/steamdeck/executor", "Executor action for the desktop UI", executor_handler)
+            .body::<ExecutorRequest>()
+            .returns::<CommandResponse>(),
+        // NDI sources
+        Endpoint::get("/ndi/sources", "Known NDI sources", ndi_list_sources).returns::<Vec<NdiSource>>(),
+        Endpoint::post("/ndi/discover", "Start NDI discovery", ndi_start_discovery).returns::<CommandResponse>(),
+        Endpoint::post("/ndi/add", "Add an NDI source by address", ndi_add_manual_source)
+            .body::<NdiAddRequest>()
+            .returns::<NdiSource>(),
+        Endpoint::post("/ndi/remove/:name", "Remove an NDI source", ndi_remove_source).returns::<CommandResponse>(),
+        Endpoint::get("/ndi/test/:name", "Check an NDI source can be reached", ndi_test_connection)
+            .returns::<CommandResponse>(),
+        // Chasers
+        Endpoint::get("/chasers", "Chasers and their playback state", chaser_list).returns::<Vec<ChaserStatus>>(),
+        Endpoint::post("/chaser/:id/start", "Start a chaser", chaser_start).param::<u32>("id").returns::<CommandResponse>(),
+        Endpoint::post("/chaser/:id/stop", "Stop a chaser", chaser_stop).param::<u32>("id").returns::<CommandResponse>(),
+        Endpoint::post("/chaser/:id/tap", "Tap a chaser's tempo", chaser_tap).param::<u32>("id").returns::<CommandResponse>(),
+        // Selection (shared with the desktop UI and CLI)
+        Endpoint::get("/selection", "Current fixture selection", selection_get).returns::<SelectionStatus>(),
+        Endpoint::post("/selection", "Change the selection", selection_command)
+            .body::<SelectionCommand>()
+            .returns::<SelectionStatus>(),
+        // Programmer
+        Endpoint::get("/programmer", "Programmer values by fixture", programmer_get)
+            .returns::<Vec<cli::ProgrammerFixture>>(),
+        Endpoint::patch("/programmer", "Set programmer values by attribute", programmer_set)
+            .body::<Vec<cli::ProgrammerValue>>()
+            .returns::<CommandResponse>(),
+        Endpoint::delete("/programmer", "Clear the programmer", programmer_clear).returns::<CommandResponse>(),
+        // Patch
+        Endpoint::get("/fixtures", "Patched fixtures in patch order", fixtures_get).returns::<Vec<Fixture>>(),
+        Endpoint::post("/fixtures", "Patch a fixture", fixture_add).body::<Fixture>().returns::<Fixture>(),
+        Endpoint::get("/fixtures/:id", "One patched fixture", fixture_get).returns::<Fixture>(),
+        Endpoint::patch("/fixtures/:id", "Rename, repatch or move a fixture", fixture_update)
+            .body::<FixtureUpdate>()
+            .returns::<Fixture>(),
+        // DMX output
+        Endpoint::get("/universes", "Universes with output", universes_get).returns::<Vec<UniverseSummary>>(),
+        Endpoint::get("/universes/:universe", "Output levels of a universe", universe_get)
+            .param::<u8>("universe")
+            .returns::<UniverseLevels>(),
+        Endpoint::post("/blackout", "Release playback, clear the programmer and black out", blackout_handler)
+            .returns::<CommandResponse>(),
+        // Undo history (shared with the desktop UI and CLI)
+        Endpoint::post("/undo", "Undo the last edit", undo_handler).returns::<CommandResponse>(),
+        Endpoint::post("/redo", "Redo the last undone edit", redo_handler).returns::<CommandResponse>(),
+        Endpoint::get("/history", "Undo and redo steps", history_get).returns::<history::HistoryStatus>(),
+    ]
+}
+
 /// Serve the web remote interface
 async fn index_handler() -> Html<&'static str> {
     Html(include_str!("../web_remote/index.html"))
 }
 
-/// Handle CLI commands via REST API
+async fn openapi_handler() -> Json<serde_json::Value> {
+    static DOCUMENT: OnceLock<serde_json::Value> = OnceLock::new();
+    Json(DOCUMENT.get_or_init(|| web_api::document(&endpoints())).clone())
+}
+
+/// Run a CLI command line. The reply carries the parsed command, what it affected and,
+/// for lines that don't parse, where the error is.
 async fn command_handler(
     State(state): State<AppState>,
+    Extension(access): Extension<Access>,
     Json(payload): Json<CommandRequest>,
-) -> Json<CommandResponse> {
+) -> Result<Json<cli::CommandResult>, ApiError> {
     println!("Received command: {}", payload.command);
 
-    // Broadcast command to WebSocket clients
-    let _ = state.tx.send(payload.command.clone());
+    // Lines that don't parse go through so the client gets the parse error
+    if let Ok(command) = cli::parse(&payload.command) {
+        access.require(web_auth::command_role(&command))?;
+    }
 
-    // TODO: Actually execute the command via Tauri IPC
-    // For now, return success
-    Json(CommandResponse {
-        success: true,
-        message: format!("Command '{}' queued", payload.command),
+    // WebSocket clients hear about the command from the CLI itself
+    Ok(Json(cli::run_structured(&state, &access.source(), &payload.command)))
+}
+
+/// Log in with a PIN or password and get a session token
+async fn login_handler(
+    State(state): State<AppState>,
+    ConnectInfo(from): ConnectInfo<SocketAddr>,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<LoginReply>, ApiError> {
+    // Checking secrets is deliberately slow; keep it off the async workers
+    let auth = state.web_auth.clone();
+    let reply = tokio::task::spawn_blocking(move || auth.login(from.ip(), payload.user.as_deref(), &payload.secret))
+        .await
+        .map_err(ApiError::internal)??;
+    println!("[Web] {} logged in as {} from {}", reply.user, reply.role, from.ip());
+    Ok(Json(reply))
+}
+
+async fn logout_handler(
+    State(state): State<AppState>,
+    access: Option<Extension<Access>>,
+) -> Json<CommandResponse> {
+    if let Some(token) = access.and_then(|Extension(access)| access.token) {
+        state.web_auth.logout(&token);
+    }
+    Json(CommandResponse { success: true, message: "Logged out".to_string() })
+}
+
+/// Whether this client has to log in, and who it is logged in as
+async fn session_handler(
+    State(state): State<AppState>,
+    access: Option<Extension<Access>>,
+) -> Result<Json<SessionStatus>, ApiError> {
+    let login_required = state.web_settings.lock().map_err(ApiError::internal)?.require_login;
+    let (user, role) = match access {
+        Some(Extension(access)) => (access.user, Some(access.role)),
+        None => (None, None),
+    };
+    Ok(Json(SessionStatus { login_required, user, role }))
+}
+
+async fn auth_get(State(state): State<AppState>) -> Result<Json<WebAccessStatus>, ApiError> {
+    let settings = state.web_settings.lock().map_err(ApiError::internal)?;
+    Ok(Json(settings.status()))
+}
+
+async fn auth_settings(
+    State(state): State<AppState>,
+    Json(payload): Json<AuthSettingsRequest>,
+) -> Result<Json<WebAccessStatus>, ApiError> {
+    web_settings::update(&state.web_settings, |settings| {
+        if let Some(origins) = payload.allowed_origins {
+            settings.set_allowed_origins(origins)?;
+        }
+        match payload.require_login {
+            Some(require) => settings.set_require_login(require),
+            None => Ok(()),
+        }
+    })
+    .map(Json)
+    .map_err(ApiError::bad_request)
+}
+
+async fn auth_save_user(
+    State(state): State<AppState>,
+    Json(payload): Json<WebUserRequest>,
+) -> Result<Json<WebAccessStatus>, ApiError> {
+    web_settings::update(&state.web_settings, |settings| {
+        settings.save_user(&payload.name, payload.role, &payload.secret)
     })
+    .map(Json)
+    .map_err(ApiError::bad_request)
+}
+
+async fn auth_remove_user(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<WebAccessStatus>, ApiError> {
+    web_settings::update(&state.web_settings, |settings| settings.remove_user(&name))
+        .map(Json)
+        .map_err(ApiError::bad_request)
+}
+
+async fn autosave_get(State(state): State<AppState>) -> Result<Json<AutosaveSettings>, ApiError> {
+    autosave::settings(&state).map(Json).map_err(ApiError::internal)
+}
+
+async fn autosave_set(
+    State(state): State<AppState>,
+    Json(payload): Json<AutosaveSettings>,
+) -> Result<Json<AutosaveSettings>, ApiError> {
+    autosave::set_settings(&state, &payload).map(Json).map_err(ApiError::bad_request)
 }
 
 /// Get system status
-async fn status_handler() -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "status": "online",
-        "version": "0.1.0",
-        "features": ["cli", "video_patch", "websocket"]
+async fn status_handler(State(state): State<AppState>) -> Result<Json<ServerStatus>, ApiError> {
+    static ROUTES: OnceLock<Vec<String>> = OnceLock::new();
+    let routes = ROUTES.get_or_init(|| {
+        let mut routes: Vec<String> = endpoints()
+            .iter()
+            .map(|endpoint| format!("{} {}{}", endpoint.method, web_api::API_PREFIX, endpoint.path))
+            .collect();
+        routes.push(format!("GET {}/openapi.json", web_api::API_PREFIX));
+        routes.push("GET /ws".to_string());
+        routes
+    });
+
+    let output = {
+        let engine = state.dmx_engine.lock().map_err(ApiError::internal)?;
+        OutputStatus {
+            protocol: match engine.protocol {
+                crate::DmxProtocol::ArtNet => "artnet".to_string(),
+                crate::DmxProtocol::Sacn => "sacn".to_string(),
+            },
+            ready: engine.output_ready(),
+            destination: engine.broadcast_address.clone(),
+            interface: engine.selected_interface.clone(),
+            universes: state.health.packet_rates(),
+        }
+    };
+    let stream_decks = state
+        .streamdeck_manager
+        .lock()
+        .map_err(ApiError::internal)?
+        .as_ref()
+        .map(|manager| manager.connected_devices())
+        .unwrap_or_default();
+    let (show_name, show_path) = {
+        let show = state.show.lock().map_err(ApiError::internal)?;
+        (show.info.name.clone(), show.path.as_ref().map(|path| path.display().to_string()))
+    };
+
+    Ok(Json(ServerStatus {
+        status: "online".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: state.health.uptime().as_secs(),
+        output,
+        routes: routes.clone(),
+        stream_decks,
+        ndi_sources: state.ndi_manager.get_sources().len(),
+        websocket_clients: state.health.websocket_clients(),
+        show_name,
+        show_path,
+        last_error: state.health.last_error(),
     }))
 }
 
-/// List all video files
+/// List all video files. Hidden files, including uploads in progress, are left out.
 async fn list_videos(State(state): State<AppState>) -> Json<Vec<VideoFile>> {
     let mut videos = Vec::new();
 
@@ -157,7 +642,7 @@ async fn list_videos(State(state): State<AppState>) -> Json<Vec<VideoFile>> {
         for entry in entries.flatten() {
             if let Ok(metadata) = entry.metadata() {
                 if metadata.is_file() {
-                    if let Some(name) = entry.file_name().to_str() {
+                    if let Some(name) = entry.file_name().to_str().filter(|name| !name.starts_with('.')) {
                         videos.push(VideoFile {
                             name: name.to_string(),
                             size: metadata.len(),
@@ -172,43 +657,135 @@ async fn list_videos(State(state): State<AppState>) -> Json<Vec<VideoFile>> {
     Json(videos)
 }
 
-/// Upload video file
+/// Room for the multipart boundaries and headers around the file in a request
+const MULTIPART_OVERHEAD: u64 = 64 * 1024;
+
+/// Upload a video file. It's streamed to a hidden file in the video directory and only
+/// renamed into place once complete; a name that's taken gets a number added.
 async fn upload_video(
     State(state): State<AppState>,
+    headers: HeaderMap,
     mut multipart: Multipart,
-) -> Result<Json<CommandResponse>, StatusCode> {
-    while let Some(field) = multipart.next_field().await.unwrap() {
-        let name = field.file_name().unwrap_or("unnamed").to_string();
-        let data = field.bytes().await.unwrap();
-
-        let file_path = state.video_dir.join(&name);
-
-        if let Err(e) = fs::write(&file_path, &data) {
-            return Ok(Json(CommandResponse {
-                success: false,
-                message: format!("Failed to save file: {}", e),
-            }));
+) -> Result<Json<VideoFile>, ApiError> {
+    let max_bytes = state.web_settings.lock().map_err(ApiError::internal)?.uploads.max_video_bytes();
+    let total = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if total.map(|total| total > max_bytes.saturating_add(MULTIPART_OVERHEAD)).unwrap_or(false) {
+        return Err(too_large(max_bytes));
+    }
+
+    loop {
+        let field = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::bad_request(format!("Malformed upload: {}", e)))?
+            .ok_or_else(|| ApiError::bad_request("No file received"))?;
+        // Skip form fields that aren't files
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        let name = video_upload::sanitize_file_name(&file_name).map_err(ApiError::bad_request)?;
+        video_upload::check_video(&name, field.content_type())
+            .map_err(|e| ApiError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, e))?;
+        return receive_video(&state, field, name, total, max_bytes).await.map(Json);
+    }
+}
+
+fn too_large(max_bytes: u64) -> ApiError {
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Videos are limited to {} MB", max_bytes / (1024 * 1024)),
+    )
+}
+
+async fn receive_video(
+    state: &AppState,
+    mut field: Field<'_>,
+    name: String,
+    total: Option<u64>,
+    max_bytes: u64,
+) -> Result<VideoFile, ApiError> {
+    let partial = video_upload::partial_path(&state.video_dir);
+    let result = match write_upload(state, &mut field, &partial, &name, total, max_bytes).await {
+        Ok(size) => save_upload(&state.video_dir, &partial, &name, size),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(video) => {
+            println!("Uploaded video: {} ({} bytes)", video.name, video.size);
+            let _ = state.events.send(Event::VideoUploaded { name, saved_as: video.name.clone(), size: video.size });
+            Ok(video)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            let _ = state.events.send(Event::VideoUploadFailed { name, message: e.message.clone() });
+            Err(e)
         }
+    }
+}
 
-        println!("Uploaded video: {} ({} bytes)", name, data.len());
+/// Stream the file to `partial`, returning its size
+async fn write_upload(
+    state: &AppState,
+    field: &mut Field<'_>,
+    partial: &std::path::Path,
+    name: &str,
+    total: Option<u64>,
+    max_bytes: u64,
+) -> Result<u64, ApiError> {
+    let mut file = tokio::fs::File::create(partial)
+        .await
+        .map_err(|e| ApiError::internal(format!("Can't store the upload: {}", e)))?;
+    let progress = |received| Event::VideoUploadProgress { name: name.to_string(), received, total };
+    let _ = state.events.send(progress(0));
 
-        return Ok(Json(CommandResponse {
-            success: true,
-            message: format!("Video '{}' uploaded successfully", name),
-        }));
+    let mut received = 0u64;
+    let mut throttle = ProgressThrottle::new(total);
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Upload interrupted: {}", e)))?
+    {
+        received += chunk.len() as u64;
+        if received > max_bytes {
+            return Err(too_large(max_bytes));
+        }
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to save file: {}", e)))?;
+        if throttle.should_report(received) {
+            let _ = state.events.send(progress(received));
+        }
+    }
+    if received == 0 {
+        return Err(ApiError::bad_request(format!("'{}' is empty", name)));
     }
+    file.sync_all().await.map_err(|e| ApiError::internal(format!("Failed to save file: {}", e)))?;
+    let _ = state.events.send(progress(received));
+    Ok(received)
+}
 
-    Ok(Json(CommandResponse {
-        success: false,
-        message: "No file received".to_string(),
-    }))
+/// Move a complete upload to its final name
+fn save_upload(dir: &std::path::Path, partial: &std::path::Path, name: &str, size: u64) -> Result<VideoFile, ApiError> {
+    let (saved_as, path) = video_upload::reserve_name(dir, name).map_err(ApiError::internal)?;
+    if let Err(e) = fs::rename(partial, &path) {
+        let _ = fs::remove_file(&path);
+        return Err(ApiError::internal(format!("Failed to save file: {}", e)));
+    }
+    Ok(VideoFile { name: saved_as, size, path: path.to_string_lossy().to_string() })
 }
 
 /// Get video file metadata
 async fn get_video(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<VideoFile>, StatusCode> {
+) -> Result<Json<VideoFile>, ApiError> {
+    // Only plain names of files in the video directory
+    if video_upload::sanitize_file_name(&name).as_deref() != Ok(name.as_str()) {
+        return Err(ApiError::not_found(format!("No video '{}'", name)));
+    }
     let file_path = state.video_dir.join(&name);
 
     if let Ok(metadata) = fs::metadata(&file_path) {
@@ -218,7 +795,7 @@ async fn get_video(
             path: file_path.to_string_lossy().to_string(),
         }))
     } else {
-        Err(StatusCode::NOT_FOUND)
+        Err(ApiError::not_found(format!("No video '{}'", name)))
     }
 }
 
@@ -226,22 +803,70 @@ async fn get_video(
 async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    Extension(access): Extension<Access>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let source = access.source();
+    ws.on_upgrade(|socket| handle_socket(socket, state, access.token, source))
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
+/// `token` is the session the socket was opened with; each request is checked against
+/// it, so logging out or losing a role applies to open sockets too. `source` names the
+/// client in the undo history.
+async fn handle_socket(socket: WebSocket, state: AppState, token: Option<String>, source: String) {
     let (mut sender, mut receiver) = socket.split();
-    let mut rx = state.tx.subscribe();
+    let mut events = state.events.subscribe();
+    // Replies go to this client only
+    let (reply_tx, mut replies) = tokio::sync::mpsc::unbounded_channel::<ServerMessage>();
+    let (subscription_tx, mut subscriptions) = tokio::sync::watch::channel::<Option<LevelSubscription>>(None);
+    let dmx_engine = state.dmx_engine.clone();
 
-    // Send initial connection message
-    let _ = sender.send(Message::Text("Connected to RoControl".to_string())).await;
+    if sender.send(Message::Text(ServerMessage::welcome().to_json())).await.is_err() {
+        return;
+    }
+    let health = state.health.clone();
+    let _client = health.client_connected();
 
-    // Spawn a task to forward broadcasts to this WebSocket
+    // Forward replies, console events and subscribed levels to this WebSocket
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            if sender.send(Message::Text(msg)).await.is_err() {
-                break;
+        let mut levels: Option<(LevelStream, tokio::time::Interval)> = None;
+        loop {
+            let messages = tokio::select! {
+                Some(reply) = replies.recv() => vec![Message::Text(reply.to_json())],
+                event = events.recv() => match event {
+                    Ok(event) => vec![Message::Text(ServerMessage::Event(event).to_json())],
+                    // A slow client misses events rather than holding up the others
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                Ok(()) = subscriptions.changed() => {
+                    levels = subscriptions.borrow().clone().map(|subscription| {
+                        let mut ticks = tokio::time::interval(subscription.interval);
+                        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                        (LevelStream::new(subscription), ticks)
+                    });
+                    continue;
+                }
+                Some(stream) = async {
+                    let (stream, ticks) = levels.as_mut()?;
+                    ticks.tick().await;
+                    Some(stream)
+                } => {
+                    let output = match dmx_engine.lock() {
+                        Ok(engine) => stream
+                            .subscription
+                            .universes
+                            .iter()
+                            .filter_map(|u| engine.universes.get(u).map(|levels| (*u, *levels)))
+                            .collect(),
+                        Err(_) => break,
+                    };
+                    stream.frames(&output)
+                }
+            };
+            for message in messages {
+                if sender.send(message).await.is_err() {
+                    return;
+                }
             }
         }
     });
@@ -250,8 +875,38 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
-                println!("WebSocket received: {}", text);
-                // TODO: Handle incoming commands from WebSocket
+                let message = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        let _ = reply_tx.send(ServerMessage::reply(None, Err(format!("Invalid message: {}", e))));
+                        continue;
+                    }
+                };
+                let allowed = state
+                    .web_auth
+                    .access(token.as_deref())
+                    .and_then(|access| access.require(web_auth::message_role(&message)));
+                let reply = match (allowed, message) {
+                    (Err((_, error)), message) => ServerMessage::reply(message.id(), Err(error)),
+                    (Ok(()), ClientMessage::SubscribeLevels { id, universes, rate, format }) => {
+                        let subscription = LevelSubscription::new(universes, rate, format);
+                        let message = format!(
+                            "Streaming {} universes at {:.0} Hz",
+                            subscription.universes.len(),
+                            1.0 / subscription.interval.as_secs_f64()
+                        );
+                        let _ = subscription_tx.send(Some(subscription));
+                        ServerMessage::reply(id, Ok(message))
+                    }
+                    (Ok(()), ClientMessage::UnsubscribeLevels { id }) => {
+                        let _ = subscription_tx.send(None);
+                        ServerMessage::reply(id, Ok("Level streaming stopped".to_string()))
+                    }
+                    (Ok(()), message) => handle_client_message(&state, &source, message),
+                };
+                if reply_tx.send(reply).is_err() {
+                    break;
+                }
             }
         }
     });
@@ -263,6 +918,46 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     }
 }
 
+/// Run one client request and build the reply
+fn handle_client_message(state: &AppState, source: &str, message: ClientMessage) -> ServerMessage {
+    match message {
+        ClientMessage::Command { id, command } => {
+            let result = cli::run_structured(state, source, &command);
+            ServerMessage::Reply {
+                id,
+                success: result.success,
+                message: result.message.clone(),
+                result: serde_json::to_value(&result).ok(),
+            }
+        }
+        ClientMessage::Select { id, command } => {
+            match selection::run_command(&state.fixtures, &state.selection, command) {
+                Ok(status) => {
+                    let _ = state.events.send(Event::SelectionChanged { active: status.active.clone() });
+                    ServerMessage::Reply {
+                        id,
+                        success: true,
+                        message: format!("{} fixtures selected", status.active.len()),
+                        result: serde_json::to_value(&status).ok(),
+                    }
+                }
+                Err(e) => ServerMessage::reply(id, Err(e)),
+            }
+        }
+        ClientMessage::ChaserTap { id, chaser } => ServerMessage::reply(
+            id,
+            tap_chaser(state, chaser).map(|bpm| format!("Chaser {} tempo {:.1} BPM", chaser, bpm)),
+        ),
+        ClientMessage::Undo { id } => ServerMessage::reply(id, history::undo(state)),
+        ClientMessage::Redo { id } => ServerMessage::reply(id, history::redo(state)),
+        ClientMessage::Ping { id } => ServerMessage::reply(id, Ok("pong".to_string())),
+        // Subscriptions belong to the connection and are handled by the socket
+        ClientMessage::SubscribeLevels { id, .. } | ClientMessage::UnsubscribeLevels { id } => {
+            ServerMessage::reply(id, Err("Level subscriptions are only available over a WebSocket".to_string()))
+        }
+    }
+}
+
 /// Handle Steam Deck button events from web remote
 async fn steamdeck_button_handler(
     State(state): State<AppState>,
@@ -271,8 +966,10 @@ async fn steamdeck_button_handler(
     println!("Steam Deck button: {} ({})", payload.button, if payload.pressed { "pressed" } else { "released" });
 
     // Broadcast button event to WebSocket clients
-    let event_msg = serde_json::to_string(&payload).unwrap_or_default();
-    let _ = state.tx.send(format!("steamdeck:button:{}", event_msg));
+    let _ = state.events.send(Event::SteamdeckButton {
+        button: payload.button.clone(),
+        pressed: payload.pressed,
+    });
 
     Json(CommandResponse {
         success: true,
@@ -288,7 +985,7 @@ async fn navigate_window_handler(
     println!("Navigate to window: {}", payload.window_id);
 
     // Broadcast window navigation to WebSocket clients
-    let _ = state.tx.send(format!("navigate:window:{}", payload.window_id));
+    let _ = state.events.send(Event::NavigateWindow { window_id: payload.window_id });
 
     Json(CommandResponse {
         success: true,
@@ -310,7 +1007,7 @@ async fn cue_handler(
     println!("Cue command: {}", command);
 
     // Broadcast cue command
-    let _ = state.tx.send(format!("command:{}", command));
+    let _ = state.events.send(Event::ExecutorAction { command: command.clone() });
 
     Json(CommandResponse {
         success: true,
@@ -332,7 +1029,7 @@ async fn executor_handler(
     println!("Executor command: {}", command);
 
     // Broadcast executor command
-    let _ = state.tx.send(format!("command:{}", command));
+    let _ = state.events.send(Event::ExecutorAction { command: command.clone() });
 
     Json(CommandResponse {
         success: true,
@@ -352,21 +1049,18 @@ async fn ndi_list_sources(
 /// Start NDI discovery
 async fn ndi_start_discovery(
     State(state): State<AppState>,
-) -> Json<CommandResponse> {
+) -> Result<Json<CommandResponse>, ApiError> {
     match state.ndi_manager.start_discovery().await {
-        Ok(_) => Json(CommandResponse {
+        Ok(_) => Ok(Json(CommandResponse {
             success: true,
             message: "NDI discovery started".to_string(),
-        }),
-        Err(e) => Json(CommandResponse {
-            success: false,
-            message: format!("Failed to start NDI discovery: {}", e),
-        }),
+        })),
+        Err(e) => Err(ApiError::internal(format!("Failed to start NDI discovery: {}", e))),
     }
 }
 
 /// Manually add an NDI source
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 struct NdiAddRequest {
     name: String,
     address: String,
@@ -383,11 +1077,7 @@ async fn ndi_add_manual_source(
         payload.port
     );
 
-    // Broadcast new source to WebSocket clients
-    if let Ok(source_json) = serde_json::to_string(&source) {
-        let _ = state.tx.send(format!("ndi:source:added:{}", source_json));
-    }
-
+    // The NDI manager announces the new source to WebSocket clients
     Json(source)
 }
 
@@ -395,42 +1085,262 @@ async fn ndi_add_manual_source(
 async fn ndi_remove_source(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Json<CommandResponse> {
+) -> Result<Json<CommandResponse>, ApiError> {
     let success = state.ndi_manager.remove_source(&name);
 
     if success {
         // Broadcast source removal to WebSocket clients
-        let _ = state.tx.send(format!("ndi:source:removed:{}", name));
+        let _ = state.events.send(Event::NdiSourceRemoved { name: name.clone() });
 
-        Json(CommandResponse {
+        Ok(Json(CommandResponse {
             success: true,
             message: format!("NDI source '{}' removed", name),
-        })
+        }))
     } else {
-        Json(CommandResponse {
-            success: false,
-            message: format!("NDI source '{}' not found", name),
-        })
+        Err(ApiError::not_found(format!("NDI source '{}' not found", name)))
     }
 }
 
-/// Test connection to an NDI source
+/// Test connection to an NDI source. An unreachable source is a result, not an error.
 async fn ndi_test_connection(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Json<CommandResponse> {
+) -> Result<Json<CommandResponse>, ApiError> {
     match state.ndi_manager.test_connection(&name).await {
-        Ok(connected) => Json(CommandResponse {
+        Ok(connected) => Ok(Json(CommandResponse {
             success: connected,
             message: if connected {
                 format!("Connection to '{}' successful", name)
             } else {
                 format!("Connection to '{}' failed", name)
             },
-        }),
-        Err(e) => Json(CommandResponse {
-            success: false,
-            message: format!("Error testing connection: {}", e),
-        }),
+        })),
+        Err(e) => Err(ApiError::not_found(e.to_string())),
+    }
+}
+
+/// Tap a chaser's tempo and let every client know the new BPM
+fn tap_chaser(state: &AppState, id: u32) -> Result<f64, String> {
+    let bpm = state.chaser_engine.lock().map_err(|e| e.to_string())?.tap(id)?;
+    let _ = state.events.send(Event::ChaserBpm { chaser: id, bpm });
+    Ok(bpm)
+}
+
+/// List all chasers and their playback state
+async fn chaser_list(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ChaserStatus>>, ApiError> {
+    let chasers = state.chaser_engine.lock().map_err(ApiError::internal)?;
+    Ok(Json(chasers.chasers().map(|c| c.status()).collect()))
+}
+
+/// Start a chaser
+async fn chaser_start(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<CommandResponse>, ApiError> {
+    state.chaser_engine.lock().map_err(ApiError::internal)?.start(id).map_err(ApiError::bad_request)?;
+    let _ = state.events.send(Event::ChaserStarted { chaser: id });
+    Ok(Json(CommandResponse {
+        success: true,
+        message: format!("Chaser {} started", id),
+    }))
+}
+
+/// Stop a chaser
+async fn chaser_stop(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<CommandResponse>, ApiError> {
+    state.chaser_engine.lock().map_err(ApiError::internal)?.stop(id).map_err(ApiError::bad_request)?;
+    let _ = state.events.send(Event::ChaserStopped { chaser: id });
+    Ok(Json(CommandResponse {
+        success: true,
+        message: format!("Chaser {} stopped", id),
+    }))
+}
+
+/// Tap tempo for a chaser
+async fn chaser_tap(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<CommandResponse>, ApiError> {
+    let bpm = tap_chaser(&state, id).map_err(ApiError::bad_request)?;
+    Ok(Json(CommandResponse {
+        success: true,
+        message: format!("Chaser {} tempo {:.1} BPM", id, bpm),
+    }))
+}
+
+/// Current fixture selection
+async fn selection_get(
+    State(state): State<AppState>,
+) -> Result<Json<SelectionStatus>, ApiError> {
+    let selection = state.selection.lock().map_err(ApiError::internal)?;
+    Ok(Json(selection.status()))
+}
+
+/// Undo the last edit, whichever client made it
+async fn undo_handler(State(state): State<AppState>) -> Result<Json<CommandResponse>, ApiError> {
+    let message = history::undo(&state).map_err(|e| ApiError::new(StatusCode::CONFLICT, e))?;
+    Ok(Json(CommandResponse { success: true, message }))
+}
+
+async fn redo_handler(State(state): State<AppState>) -> Result<Json<CommandResponse>, ApiError> {
+    let message = history::redo(&state).map_err(|e| ApiError::new(StatusCode::CONFLICT, e))?;
+    Ok(Json(CommandResponse { success: true, message }))
+}
+
+async fn history_get(
+    State(state): State<AppState>,
+) -> Result<Json<history::HistoryStatus>, ApiError> {
+    let history = state.history.lock().map_err(ApiError::internal)?;
+    Ok(Json(history.status()))
+}
+
+/// Programmer contents by fixture and attribute
+async fn programmer_get(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<cli::ProgrammerFixture>>, ApiError> {
+    cli::programmer_summary(&state)
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+/// Set programmer values, all or none
+async fn programmer_set(
+    State(state): State<AppState>,
+    Extension(access): Extension<Access>,
+    Json(values): Json<Vec<cli::ProgrammerValue>>,
+) -> Result<Json<CommandResponse>, ApiError> {
+    let message = cli::set_programmer_values(&state, &access.source(), &values).map_err(ApiError::bad_request)?;
+    Ok(Json(CommandResponse { success: true, message }))
+}
+
+async fn programmer_clear(
+    State(state): State<AppState>,
+    Extension(access): Extension<Access>,
+) -> Result<Json<CommandResponse>, ApiError> {
+    let command = cli::CliCommand::Clear;
+    let outcome =
+        history::journal_from(&state, &access.source(), "Clear", command.domains(), || cli::execute(&state, &command))
+            .map_err(ApiError::internal)?;
+    Ok(Json(CommandResponse { success: true, message: outcome.message }))
+}
+
+/// Patched fixtures
+async fn fixtures_get(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Fixture>>, ApiError> {
+    let fixtures = state.fixtures.lock().map_err(ApiError::internal)?;
+    let order = selection::patch_order(&fixtures);
+    Ok(Json(order.iter().filter_map(|id| fixtures.get(id).cloned()).collect()))
+}
+
+async fn fixture_get(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<Fixture>, ApiError> {
+    let fixtures = state.fixtures.lock().map_err(ApiError::internal)?;
+    let fixture = fixtures.get(&id).cloned().ok_or_else(|| ApiError::not_found(format!("Fixture {} not found", id)))?;
+    Ok(Json(fixture))
+}
+
+async fn fixture_add(
+    State(state): State<AppState>,
+    Extension(access): Extension<Access>,
+    Json(fixture): Json<Fixture>,
+) -> Result<Json<Fixture>, ApiError> {
+    let id = fixture.id.clone();
+    history::journal_from(&state, &access.source(), format!("Add fixture {}", id), &[Domain::Patch], || {
+        patch::add(&mut *state.fixtures.lock().map_err(|e| e.to_string())?, fixture.clone())
+    })
+    .map_err(ApiError::bad_request)?;
+    let _ = state.events.send(Event::PatchChanged { fixture_id: id });
+    Ok(Json(fixture))
+}
+
+async fn fixture_update(
+    State(state): State<AppState>,
+    Extension(access): Extension<Access>,
+    Path(id): Path<String>,
+    Json(update): Json<FixtureUpdate>,
+) -> Result<Json<Fixture>, ApiError> {
+    if !state.fixtures.lock().map_err(ApiError::internal)?.contains_key(&id) {
+        return Err(ApiError::not_found(format!("Fixture {} not found", id)));
+    }
+    let fixture = history::journal_from(&state, &access.source(), format!("Repatch fixture {}", id), &[Domain::Patch], || {
+        patch::update(&mut *state.fixtures.lock().map_err(|e| e.to_string())?, &id, update)
+    })
+    .map_err(ApiError::bad_request)?;
+    let _ = state.events.send(Event::PatchChanged { fixture_id: id });
+    Ok(Json(fixture))
+}
+
+async fn universes_get(State(state): State<AppState>) -> Result<Json<Vec<UniverseSummary>>, ApiError> {
+    let engine = state.dmx_engine.lock().map_err(ApiError::internal)?;
+    let mut universes: Vec<UniverseSummary> = engine
+        .universes
+        .iter()
+        .map(|(universe, levels)| UniverseSummary {
+            universe: *universe,
+            active_channels: levels.iter().filter(|level| **level > 0).count(),
+        })
+        .collect();
+    universes.sort_by_key(|u| u.universe);
+    Ok(Json(universes))
+}
+
+/// Universes without output yet read as all zero
+async fn universe_get(State(state): State<AppState>, Path(universe): Path<u8>) -> Result<Json<UniverseLevels>, ApiError> {
+    let engine = state.dmx_engine.lock().map_err(ApiError::internal)?;
+    let levels = engine.universes.get(&universe).copied().unwrap_or([0; 512]);
+    Ok(Json(UniverseLevels { universe, levels: levels.to_vec() }))
+}
+
+async fn blackout_handler(
+    State(state): State<AppState>,
+    Extension(access): Extension<Access>,
+) -> Result<Json<CommandResponse>, ApiError> {
+    let message =
+        history::journal_from(&state, &access.source(), "Blackout", &[Domain::Programmer], || actions::blackout(&state))
+            .map_err(ApiError::internal)?;
+    Ok(Json(CommandResponse { success: true, message }))
+}
+
+/// Change the selection (select, add, invert, odd/even, next/previous...)
+async fn selection_command(
+    State(state): State<AppState>,
+    Json(command): Json<SelectionCommand>,
+) -> Result<Json<SelectionStatus>, ApiError> {
+    let status = selection::run_command(&state.fixtures, &state.selection, command)
+        .map_err(ApiError::bad_request)?;
+    let _ = state.events.send(Event::SelectionChanged { active: status.active.clone() });
+    Ok(Json(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reachable_urls() {
+        let interfaces = [IpAddr::from([127, 0, 0, 1]), IpAddr::from([192, 168, 1, 20])];
+        let all = SocketAddr::from(([0, 0, 0, 0], 8080));
+        assert_eq!(reachable_urls(all, false, &interfaces), vec!["http://192.168.1.20:8080", "http://127.0.0.1:8080"]);
+        let one = SocketAddr::from(([192, 168, 1, 20], 8443));
+        assert_eq!(reachable_urls(one, true, &interfaces), vec!["https://192.168.1.20:8443"]);
+    }
+
+    #[test]
+    fn test_openapi_document_covers_every_endpoint() {
+        let endpoints = endpoints();
+        let document = web_api::document(&endpoints);
+        let operations: usize = document["paths"].as_object().unwrap().values().map(|item| item.as_object().unwrap().len()).sum();
+        assert_eq!(operations, endpoints.len());
+
+        let start = &document["paths"]["/chaser/{id}/start"]["post"];
+        assert_eq!(start["parameters"][0]["schema"]["type"], "integer");
+        assert_eq!(start["x-required-role"], "operator");
+        let command = &document["paths"]["/command"]["post"];
+        assert_eq!(command["responses"]["200"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/CommandResult");
+        assert!(document["components"]["schemas"]["CliCommand"].is_object());
     }
 }
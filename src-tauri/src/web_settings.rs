@@ -0,0 +1,368 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use crate::web_auth::{self, Role};
+
+/// Shortest PIN or password accepted for a web user
+pub const MIN_SECRET_LEN: usize = 4;
+
+/// Set at startup to create an admin user "admin" with this PIN and require login, so a
+/// headless install can be locked down without the desktop settings
+pub const ADMIN_PIN_ENV: &str = "ROCONTROL_ADMIN_PIN";
+
+/// Web remote settings of this installation. Kept outside show files since they hold
+/// credentials and belong to the machine, not the show.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebSettings {
+    /// Without login every client has admin access
+    #[serde(default)]
+    pub require_login: bool,
+    #[serde(default)]
+    pub users: Vec<WebUser>,
+    /// Origins of other web apps allowed to call the API from a browser; "*" allows any.
+    /// The web remote itself is served by RoControl and needs no entry.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default)]
+    pub server: ServerSettings,
+    #[serde(default)]
+    pub uploads: UploadSettings,
+}
+
+/// Where the web server listens. Changes apply when it is restarted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerSettings {
+    /// Interface address to listen on; 0.0.0.0 for all
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Serve HTTPS. Without a certificate and key, a self-signed pair is made on first use.
+    #[serde(default)]
+    pub tls: bool,
+    /// PEM files
+    #[serde(default)]
+    pub cert_path: Option<PathBuf>,
+    #[serde(default)]
+    pub key_path: Option<PathBuf>,
+}
+
+/// Limits on files uploaded from the web remote. Changes apply to the next upload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UploadSettings {
+    #[serde(default = "default_max_video_size_mb")]
+    pub max_video_size_mb: u64,
+}
+
+fn default_max_video_size_mb() -> u64 {
+    4096
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        UploadSettings { max_video_size_mb: default_max_video_size_mb() }
+    }
+}
+
+impl UploadSettings {
+    pub fn max_video_bytes(&self) -> u64 {
+        self.max_video_size_mb.saturating_mul(1024 * 1024)
+    }
+
+    pub fn set_max_video_size_mb(&mut self, max_size_mb: u64) -> Result<(), String> {
+        if max_size_mb == 0 {
+            return Err("The upload limit must be at least 1 MB".to_string());
+        }
+        self.max_video_size_mb = max_size_mb;
+        Ok(())
+    }
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_port() -> u16 {
+    8080
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            bind_address: default_bind_address(),
+            port: default_port(),
+            tls: false,
+            cert_path: None,
+            key_path: None,
+        }
+    }
+}
+
+impl ServerSettings {
+    pub fn socket_addr(&self) -> Result<SocketAddr, String> {
+        let ip: IpAddr = self
+            .bind_address
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' isn't an IP address", self.bind_address))?;
+        if self.port == 0 {
+            return Err("Port 0 isn't allowed".to_string());
+        }
+        if self.cert_path.is_some() != self.key_path.is_some() {
+            return Err("Set both a certificate and a key, or neither for a self-signed one".to_string());
+        }
+        Ok(SocketAddr::new(ip, self.port))
+    }
+
+    /// Address a client on this machine uses to reach the server
+    pub fn local_address(&self) -> String {
+        match self.socket_addr() {
+            Ok(addr) if !addr.ip().is_unspecified() => addr.to_string(),
+            _ => format!("127.0.0.1:{}", self.port),
+        }
+    }
+}
+
+/// Default home of the self-signed certificate
+pub fn tls_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("RoControl")
+        .join("tls")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebUser {
+    pub name: String,
+    pub role: Role,
+    /// Argon2 hash of the PIN or password
+    pub secret_hash: String,
+}
+
+/// Web settings for the frontend, without the hashes
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WebAccessStatus {
+    pub require_login: bool,
+    pub users: Vec<WebUserInfo>,
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WebUserInfo {
+    pub name: String,
+    pub role: Role,
+}
+
+pub fn settings_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("RoControl")
+        .join("web_remote.json")
+}
+
+impl WebSettings {
+    /// Settings saved at `path`, or the defaults if there are none yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    pub fn status(&self) -> WebAccessStatus {
+        WebAccessStatus {
+            require_login: self.require_login,
+            users: self.users.iter().map(|u| WebUserInfo { name: u.name.clone(), role: u.role }).collect(),
+            allowed_origins: self.allowed_origins.clone(),
+        }
+    }
+
+    pub fn user(&self, name: &str) -> Option<&WebUser> {
+        self.users.iter().find(|u| u.name == name)
+    }
+
+    fn has_admin(&self) -> bool {
+        self.users.iter().any(|u| u.role == Role::Admin)
+    }
+
+    /// Add a user, or change the role and secret of an existing one
+    pub fn save_user(&mut self, name: &str, role: Role, secret: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("User name is empty".to_string());
+        }
+        if secret.chars().count() < MIN_SECRET_LEN {
+            return Err(format!("PIN or password needs at least {} characters", MIN_SECRET_LEN));
+        }
+        if self.require_login && role != Role::Admin && self.user(name).map(|u| u.role) == Some(Role::Admin) {
+            self.ensure_other_admin(name)?;
+        }
+        let user = WebUser { name: name.to_string(), role, secret_hash: web_auth::hash_secret(secret)? };
+        match self.users.iter_mut().find(|u| u.name == name) {
+            Some(existing) => *existing = user,
+            None => self.users.push(user),
+        }
+        Ok(())
+    }
+
+    pub fn remove_user(&mut self, name: &str) -> Result<(), String> {
+        let user = self.user(name).ok_or_else(|| format!("No web user '{}'", name))?;
+        if self.require_login && user.role == Role::Admin {
+            self.ensure_other_admin(name)?;
+        }
+        self.users.retain(|u| u.name != name);
+        Ok(())
+    }
+
+    pub fn set_require_login(&mut self, require: bool) -> Result<(), String> {
+        if require && !self.has_admin() {
+            return Err("Add an admin user before requiring login".to_string());
+        }
+        self.require_login = require;
+        Ok(())
+    }
+
+    pub fn set_allowed_origins(&mut self, origins: Vec<String>) -> Result<(), String> {
+        let mut allowed = Vec::new();
+        for origin in origins {
+            let origin = origin.trim().trim_end_matches('/').to_string();
+            if origin.is_empty() {
+                continue;
+            }
+            if origin != "*" && !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                return Err(format!("Origin '{}' must start with http:// or https://", origin));
+            }
+            allowed.push(origin);
+        }
+        self.allowed_origins = allowed;
+        Ok(())
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*" || allowed == origin)
+    }
+
+    /// Admins can't lock everyone out while login is required
+    fn ensure_other_admin(&self, name: &str) -> Result<(), String> {
+        if self.users.iter().any(|u| u.role == Role::Admin && u.name != name) {
+            Ok(())
+        } else {
+            Err("Login is required and this is the last admin user".to_string())
+        }
+    }
+
+    /// Apply `ROCONTROL_ADMIN_PIN` if set and there is no admin yet. Returns whether the
+    /// settings changed.
+    pub fn apply_admin_pin(&mut self, pin: Option<String>) -> Result<bool, String> {
+        match pin {
+            Some(pin) if !self.has_admin() => {
+                self.save_user("admin", Role::Admin, &pin)?;
+                self.require_login = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Change the settings and save them. Nothing changes if the edit or the save fails.
+pub fn update(
+    settings: &std::sync::Mutex<WebSettings>,
+    edit: impl FnOnce(&mut WebSettings) -> Result<(), String>,
+) -> Result<WebAccessStatus, String> {
+    let mut settings = settings.lock().map_err(|e| e.to_string())?;
+    let mut edited = settings.clone();
+    edit(&mut edited)?;
+    edited.save(&settings_path())?;
+    *settings = edited;
+    Ok(settings.status())
+}
+
+/// Load the web settings at startup. Broken settings fall back to requiring a login
+/// nobody can pass rather than opening the remote to everyone.
+pub fn load_startup_settings() -> WebSettings {
+    let path = settings_path();
+    let mut settings = match WebSettings::load(&path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("[Web] {} - web remote locked until the settings are fixed", e);
+            return WebSettings { require_login: true, ..WebSettings::default() };
+        }
+    };
+    match settings.apply_admin_pin(std::env::var(ADMIN_PIN_ENV).ok()) {
+        Ok(true) => {
+            println!("[Web] Created admin user from {}; login is now required", ADMIN_PIN_ENV);
+            if let Err(e) = settings.save(&path) {
+                eprintln!("[Web] Failed to save web settings: {}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("[Web] {}: {}", ADMIN_PIN_ENV, e),
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_with_admin() -> WebSettings {
+        WebSettings {
+            require_login: true,
+            users: vec![WebUser { name: "admin".to_string(), role: Role::Admin, secret_hash: String::new() }],
+            allowed_origins: Vec::new(),
+            server: ServerSettings::default(),
+            uploads: UploadSettings::default(),
+        }
+    }
+
+    #[test]
+    fn test_last_admin_is_kept_while_login_is_required() {
+        let mut settings = settings_with_admin();
+        assert!(settings.remove_user("admin").is_err());
+        assert!(settings.remove_user("nobody").is_err());
+        settings.require_login = false;
+        assert!(settings.remove_user("admin").is_ok());
+        assert!(settings.set_require_login(true).is_err());
+    }
+
+    #[test]
+    fn test_allowed_origins() {
+        let mut settings = WebSettings::default();
+        assert!(!settings.allows_origin("http://deck.local:3000"));
+        settings.set_allowed_origins(vec!["http://deck.local:3000/".to_string(), " ".to_string()]).unwrap();
+        assert_eq!(settings.allowed_origins, vec!["http://deck.local:3000"]);
+        assert!(settings.allows_origin("http://deck.local:3000"));
+        assert!(!settings.allows_origin("http://evil.example"));
+        assert!(settings.set_allowed_origins(vec!["deck.local".to_string()]).is_err());
+        settings.set_allowed_origins(vec!["*".to_string()]).unwrap();
+        assert!(settings.allows_origin("http://evil.example"));
+    }
+
+    #[test]
+    fn test_server_settings() {
+        let settings: WebSettings = serde_json::from_str(r#"{"require_login": false}"#).unwrap();
+        assert_eq!(settings.server, ServerSettings::default());
+        assert_eq!(settings.uploads.max_video_bytes(), 4096 * 1024 * 1024);
+        assert_eq!(settings.server.local_address(), "127.0.0.1:8080");
+
+        let server = ServerSettings { bind_address: "10.0.0.5".to_string(), port: 9000, ..ServerSettings::default() };
+        assert_eq!(server.local_address(), "10.0.0.5:9000");
+        assert!(ServerSettings { bind_address: "deck".to_string(), ..ServerSettings::default() }.socket_addr().is_err());
+        assert!(ServerSettings { port: 0, ..ServerSettings::default() }.socket_addr().is_err());
+        let half_tls = ServerSettings { cert_path: Some(PathBuf::from("cert.pem")), ..ServerSettings::default() };
+        assert!(half_tls.socket_addr().is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use axum::extract::ws::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::cli::ProgrammerFixture;
+use crate::ndi_support::NdiSource;
+use crate::selection::SelectionCommand;
+
+/// WebSocket protocol version, sent in the welcome message. Bumped on breaking changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Level updates per second when a subscription doesn't ask for a rate, and the most allowed
+pub const DEFAULT_LEVEL_RATE: f64 = 10.0;
+pub const MAX_LEVEL_RATE: f64 = 30.0;
+
+/// Above this many changed channels a full buffer is smaller than the changes
+const MAX_LEVEL_CHANGES: usize = 128;
+
+/// First byte of a binary level frame
+const BINARY_FULL_FRAME: u8 = 1;
+
+/// Request from a web client. `id` is optional and echoed back in the reply so clients
+/// can match replies to requests; any JSON value works.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// CLI command line; the reply's result is the command result
+    Command {
+        #[serde(default)]
+        id: Option<Value>,
+        command: String,
+    },
+    /// Selection change; the reply's result is the new selection
+    Select {
+        #[serde(default)]
+        id: Option<Value>,
+        command: SelectionCommand,
+    },
+    ChaserTap {
+        #[serde(default)]
+        id: Option<Value>,
+        chaser: u32,
+    },
+    Undo {
+        #[serde(default)]
+        id: Option<Value>,
+    },
+    Redo {
+        #[serde(default)]
+        id: Option<Value>,
+    },
+    Ping {
+        #[serde(default)]
+        id: Option<Value>,
+    },
+    /// Stream output levels of these universes, replacing any earlier subscription
+    SubscribeLevels {
+        #[serde(default)]
+        id: Option<Value>,
+        universes: Vec<u8>,
+        /// Updates per second, 1-30
+        #[serde(default)]
+        rate: Option<f64>,
+        #[serde(default)]
+        format: LevelFormat,
+    },
+    UnsubscribeLevels {
+        #[serde(default)]
+        id: Option<Value>,
+    },
+}
+
+impl ClientMessage {
+    pub fn id(&self) -> Option<Value> {
+        match self {
+            ClientMessage::Command { id, .. }
+            | ClientMessage::Select { id, .. }
+            | ClientMessage::ChaserTap { id, .. }
+            | ClientMessage::Undo { id }
+            | ClientMessage::Redo { id }
+            | ClientMessage::Ping { id }
+            | ClientMessage::SubscribeLevels { id, .. }
+            | ClientMessage::UnsubscribeLevels { id } => id.clone(),
+        }
+    }
+}
+
+/// How level updates are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelFormat {
+    /// `levels` messages with the full buffer, then `level_changes` with changed channels only
+    #[default]
+    Json,
+    /// Binary frames: [1, universe, 512 levels], sent when the universe changed
+    Binary,
+}
+
+/// Message from the server: a reply to one client, or an event for all of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// First message on every connection
+    Welcome { version: u32, server_version: String },
+    Reply {
+        id: Option<Value>,
+        success: bool,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<Value>,
+    },
+    Event(Event),
+    /// Full output buffer of a subscribed universe (Art-Net numbering, 0-based)
+    Levels { universe: u8, levels: Vec<u8> },
+    /// Channels (1-512) of a subscribed universe that changed since the last update
+    LevelChanges { universe: u8, changes: Vec<(u16, u8)> },
+}
+
+impl ServerMessage {
+    pub fn welcome() -> Self {
+        ServerMessage::Welcome {
+            version: PROTOCOL_VERSION,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    pub fn reply(id: Option<Value>, outcome: Result<String, String>) -> Self {
+        let (success, message) = match outcome {
+            Ok(message) => (true, message),
+            Err(message) => (false, message),
+        };
+        ServerMessage::Reply { id, success, message, result: None }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Something that changed on the console. Published on `AppState::events` by whichever
+/// part of the backend made the change and forwarded to every WebSocket client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A command line ran, from any client
+    CommandExecuted { command: String, success: bool, message: String },
+    ProgrammerChanged { programmer: Vec<ProgrammerFixture> },
+    /// A fixture was patched or its patch changed
+    PatchChanged { fixture_id: String },
+    /// A cue list moved to another cue (None when it was released)
+    CueFired { cue_list: u32, cue: Option<f64> },
+    NdiSourceAdded { source: NdiSource },
+    NdiSourceRemoved { name: String },
+    SelectionChanged { active: Vec<String> },
+    ChaserStarted { chaser: u32 },
+    ChaserStopped { chaser: u32 },
+    ChaserBpm { chaser: u32, bpm: f64 },
+    SteamdeckButton { button: String, pressed: bool },
+    NavigateWindow { window_id: u32 },
+    /// Cue or executor button from a Steam Deck, for the desktop UI to run
+    ExecutorAction { command: String },
+    /// Bytes of a video upload received so far. `total` is the request size, when the
+    /// client sent one.
+    VideoUploadProgress { name: String, received: u64, total: Option<u64> },
+    /// An upload was saved, as `saved_as` if another video already had its name
+    VideoUploaded { name: String, saved_as: String, size: u64 },
+    VideoUploadFailed { name: String, message: String },
+}
+
+/// What one client asked to see of the DMX output
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelSubscription {
+    pub universes: Vec<u8>,
+    pub interval: Duration,
+    pub format: LevelFormat,
+}
+
+impl LevelSubscription {
+    pub fn new(mut universes: Vec<u8>, rate: Option<f64>, format: LevelFormat) -> Self {
+        universes.sort_unstable();
+        universes.dedup();
+        let rate = rate.filter(|r| r.is_finite()).unwrap_or(DEFAULT_LEVEL_RATE).clamp(1.0, MAX_LEVEL_RATE);
+        LevelSubscription { universes, interval: Duration::from_secs_f64(1.0 / rate), format }
+    }
+}
+
+/// Level stream of one client: remembers what it was sent so only changes go out
+#[derive(Debug)]
+pub struct LevelStream {
+    pub subscription: LevelSubscription,
+    sent: HashMap<u8, [u8; 512]>,
+}
+
+impl LevelStream {
+    pub fn new(subscription: LevelSubscription) -> Self {
+        LevelStream { subscription, sent: HashMap::new() }
+    }
+
+    /// Messages for the subscribed universes that changed. Universes missing from
+    /// `output` haven't been written yet and are all zero.
+    pub fn frames(&mut self, output: &HashMap<u8, [u8; 512]>) -> Vec<Message> {
+        let mut frames = Vec::new();
+        for &universe in &self.subscription.universes {
+            let levels = output.get(&universe).copied().unwrap_or([0; 512]);
+            let previous = self.sent.insert(universe, levels);
+            if previous == Some(levels) {
+                continue;
+            }
+            let message = match (self.subscription.format, previous) {
+                (LevelFormat::Binary, _) => {
+                    let mut frame = vec![BINARY_FULL_FRAME, universe];
+                    frame.extend_from_slice(&levels);
+                    frames.push(Message::Binary(frame));
+                    continue;
+                }
+                (LevelFormat::Json, Some(previous)) => {
+                    let changes = level_changes(&previous, &levels);
+                    if changes.len() <= MAX_LEVEL_CHANGES {
+                        ServerMessage::LevelChanges { universe, changes }
+                    } else {
+                        ServerMessage::Levels { universe, levels: levels.to_vec() }
+                    }
+                }
+                (LevelFormat::Json, None) => ServerMessage::Levels { universe, levels: levels.to_vec() },
+            };
+            frames.push(Message::Text(message.to_json()));
+        }
+        frames
+    }
+}
+
+/// Changed channels as (channel 1-512, new value)
+pub fn level_changes(previous: &[u8], current: &[u8]) -> Vec<(u16, u8)> {
+    previous
+        .iter()
+        .zip(current)
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(index, (_, after))| (index as u16 + 1, *after))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_messages_parse() {
+        let message: ClientMessage = serde_json::from_str(r#"{"type":"command","id":7,"command":"1 thru 4"}"#).unwrap();
+        assert!(matches!(message, ClientMessage::Command { id: Some(id), command } if id == 7 && command == "1 thru 4"));
+        let message: ClientMessage = serde_json::from_str(r#"{"type":"chaser_tap","chaser":2}"#).unwrap();
+        assert!(matches!(message, ClientMessage::ChaserTap { id: None, chaser: 2 }));
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"launch"}"#).is_err());
+    }
+
+    #[test]
+    fn test_server_message_shape() {
+        let reply: Value = serde_json::from_str(&ServerMessage::reply(Some(Value::from("a")), Err("No".to_string())).to_json()).unwrap();
+        assert_eq!(reply, serde_json::json!({ "type": "reply", "id": "a", "success": false, "message": "No" }));
+
+        let event = ServerMessage::Event(Event::CueFired { cue_list: 1, cue: Some(2.5) });
+        let event: Value = serde_json::from_str(&event.to_json()).unwrap();
+        assert_eq!(event, serde_json::json!({ "type": "event", "event": "cue_fired", "cue_list": 1, "cue": 2.5 }));
+    }
+
+    #[test]
+    fn test_level_stream_sends_full_buffer_then_changes() {
+        let mut stream = LevelStream::new(LevelSubscription::new(vec![0, 0], Some(100.0), LevelFormat::Json));
+        assert_eq!(stream.subscription.universes, vec![0]);
+        assert_eq!(stream.subscription.interval, Duration::from_secs_f64(1.0 / MAX_LEVEL_RATE));
+
+        let mut output = HashMap::new();
+        let frames = stream.frames(&output);
+        assert!(matches!(&frames[..], [Message::Text(text)] if text.contains("\"type\":\"levels\"")));
+        assert!(stream.frames(&output).is_empty());
+
+        let mut levels = [0; 512];
+        levels[0] = 255;
+        output.insert(0, levels);
+        let frames = stream.frames(&output);
+        assert!(matches!(&frames[..], [Message::Text(text)] if text.contains("\"changes\":[[1,255]]")));
+    }
+
+    #[test]
+    fn test_binary_level_frames() {
+        let mut stream = LevelStream::new(LevelSubscription::new(vec![3], None, LevelFormat::Binary));
+        let frames = stream.frames(&HashMap::from([(3, [7; 512])]));
+        match &frames[..] {
+            [Message::Binary(frame)] => {
+                assert_eq!(frame.len(), 514);
+                assert_eq!(&frame[..3], &[BINARY_FULL_FRAME, 3, 7]);
+            }
+            other => panic!("unexpected frames {:?}", other),
+        }
+    }
+}
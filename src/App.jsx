@@ -93,6 +93,7 @@ function App() {
   const [showGamepadDebug, setShowGamepadDebug] = useState(true)
   const [savedShows, setSavedShows] = useState([])
   const [newShowName, setNewShowName] = useState('')
+  const [autosaveSettings, setAutosaveSettings] = useState({ enabled: true, interval: 300, backups: 5 })
   const [recordedCues, setRecordedCues] = useState(() => {
     const saved = localStorage.getItem('dmx_recorded_cues')
     return saved ? JSON.parse(saved) : []
@@ -330,8 +331,52 @@ function App() {
     setupGamepadListener()
     loadSavedShows()
     loadNetworkInterfaces()
+    checkAutosaveRecovery()
+    loadAutosaveSettings()
   }, [])
 
+  const checkAutosaveRecovery = async () => {
+    try {
+      const recovery = await invoke('get_recovery_info')
+      if (!recovery) return
+
+      const savedAt = new Date(recovery.saved_at * 1000).toLocaleString()
+      const name = recovery.show_name || 'Untitled Show'
+      if (window.confirm(`RoControl did not shut down cleanly.\n\nRestore the autosave of "${name}" from ${savedAt}?`)) {
+        await invoke('restore_autosave')
+        loadFixtures()
+      } else {
+        await invoke('discard_recovery')
+      }
+    } catch (error) {
+      console.error('Error checking autosave recovery:', error)
+    }
+  }
+
+  const loadAutosaveSettings = async () => {
+    try {
+      setAutosaveSettings(await invoke('get_autosave_settings'))
+    } catch (error) {
+      console.error('Error loading autosave settings:', error)
+    }
+  }
+
+  const saveAutosaveSettings = async () => {
+    try {
+      const settings = await invoke('set_autosave_settings', {
+        settings: {
+          enabled: autosaveSettings.enabled,
+          interval: parseInt(autosaveSettings.interval) || 0,
+          backups: parseInt(autosaveSettings.backups) || 0
+        }
+      })
+      setAutosaveSettings(settings)
+      alert('Autosave settings saved')
+    } catch (error) {
+      alert('Error saving autosave settings: ' + error)
+    }
+  }
+
   const loadNetworkInterfaces = async () => {
     try {
       const interfaces = await invoke('get_network_interfaces')
@@ -671,20 +716,22 @@ function App() {
     }
 
     try {
-      // Add multiple fixtures with auto-incrementing addresses
+      // Add multiple fixtures with auto-incrementing addresses. The backend patches
+      // them all or none, so a clash doesn't leave half the batch behind.
+      const fixturesToAdd = []
       for (let i = 0; i < quantity; i++) {
-        const fixtureToAdd = {
+        fixturesToAdd.push({
           id: quantity > 1 ? `${newFixture.id}${i + 1}` : newFixture.id,
           name: quantity > 1 ? `${newFixture.name} ${i + 1}` : newFixture.name,
           fixture_type: newFixture.fixture_type,
           dmx_address: dmxAddress + (i * channelCount),
           universe: universe,
           channel_count: channelCount
-        }
-
-        await invoke('add_fixture', { fixture: fixtureToAdd })
+        })
       }
 
+      await invoke('add_fixtures', { fixtures: fixturesToAdd })
+
       await loadFixtures()
       setNewFixture({
         id: '',
@@ -1589,6 +1636,16 @@ function App() {
     return dispatcher.execute(command)
   }
 
+  // The backend CLI changes the backend selection; mirror it in the UI
+  const handleBackendCommand = async () => {
+    try {
+      const selection = await invoke('get_selection')
+      setSelectedFixtures(new Set(selection.fixture_ids))
+    } catch (error) {
+      console.error('Error loading selection:', error)
+    }
+  }
+
   // Prepare app state for views
   const appState = {
     fixtures,
@@ -2460,6 +2517,45 @@ function App() {
                     </div>
                   )}
 
+                  <h3 style={{ marginTop: '30px' }}>Autosave</h3>
+                  <div className="form-grid">
+                    <div className="form-group">
+                      <label>
+                        <input
+                          type="checkbox"
+                          checked={autosaveSettings.enabled}
+                          onChange={(e) => setAutosaveSettings({...autosaveSettings, enabled: e.target.checked})}
+                        />
+                        {' '}Autosave the show
+                      </label>
+                    </div>
+                    <div className="form-group">
+                      <label>Interval (seconds):</label>
+                      <input
+                        type="number"
+                        inputMode="numeric"
+                        min="1"
+                        max="86400"
+                        value={autosaveSettings.interval}
+                        onChange={(e) => setAutosaveSettings({...autosaveSettings, interval: e.target.value})}
+                      />
+                    </div>
+                    <div className="form-group">
+                      <label>Autosave files kept:</label>
+                      <input
+                        type="number"
+                        inputMode="numeric"
+                        min="1"
+                        max="100"
+                        value={autosaveSettings.backups}
+                        onChange={(e) => setAutosaveSettings({...autosaveSettings, backups: e.target.value})}
+                      />
+                    </div>
+                  </div>
+                  <button className="btn-primary" onClick={saveAutosaveSettings}>
+                    Save Autosave Settings
+                  </button>
+
                   <div className="form-help" style={{ marginTop: '30px' }}>
                     <p><strong>About Show Backup:</strong></p>
                     <ul>
@@ -2531,7 +2627,7 @@ function App() {
       )}
 
       {/* CLI - Command Line Interface (MA3/Hog style) */}
-      <CLI onCommand={handleCLICommand} appState={appState} />
+      <CLI onCommand={handleCLICommand} onBackendCommand={handleBackendCommand} appState={appState} />
     </div>
     </GamepadManager>
 
@@ -1,4 +1,5 @@
 import React, { useState, useRef, useEffect } from 'react'
+import { invoke } from '@tauri-apps/api/tauri'
 import '../styles/CLI.css'
 import { CLIParser } from '../utils/cliParser'
 
@@ -6,7 +7,7 @@ import { CLIParser } from '../utils/cliParser'
  * Command Line Interface - MA3/Hog style
  * Serves as the backbone for all show control operations
  */
-function CLI({ onCommand, appState }) {
+function CLI({ onCommand, onBackendCommand, appState }) {
   const [input, setInput] = useState('')
   const [feedback, setFeedback] = useState('')
   const [feedbackType, setFeedbackType] = useState('') // 'success', 'error', 'info'
@@ -47,36 +48,52 @@ function CLI({ onCommand, appState }) {
     }, 3000)
   }
 
-  const handleSubmit = (e) => {
+  const handleSubmit = async (e) => {
     e.preventDefault()
 
     const parser = parserRef.current
+    const line = input
 
     // If empty command, add "BAM!" to history
-    if (!input.trim()) {
+    if (!line.trim()) {
       parser.addToHistory('BAM!')
       showFeedback('BAM!', 'info')
       return
     }
 
-    const command = parser.parse(input)
+    // The backend runs the same grammar as the web remote and console. Window, video
+    // and conditional commands come back as 'desktop' and are handled here.
+    setInput('')
+    let result = null
+    try {
+      result = await invoke('execute_cli_command', { command: line })
+    } catch (error) {
+      console.error('Backend CLI unavailable, using the local parser:', error)
+    }
+    if (result && result.command?.type !== 'desktop') {
+      parser.addToHistory(line.trim())
+      showFeedback(result.message || (result.success ? 'Command executed' : 'Command failed'), result.success ? 'success' : 'error')
+      if (result.success && onBackendCommand) {
+        onBackendCommand(result)
+      }
+      return
+    }
+
+    const command = parser.parse(line)
 
     // Execute command via callback
     if (onCommand) {
-      const result = onCommand(command)
+      const local = onCommand(command)
 
       // Show feedback
-      if (result) {
-        if (result.success) {
-          showFeedback(result.message || 'Command executed', 'success')
+      if (local) {
+        if (local.success) {
+          showFeedback(local.message || 'Command executed', 'success')
         } else {
-          showFeedback(result.message || 'Command failed', 'error')
+          showFeedback(local.message || 'Command failed', 'error')
         }
       }
     }
-
-    // Clear input
-    setInput('')
   }
 
   const handleKeyDown = (e) => {